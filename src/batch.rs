@@ -0,0 +1,389 @@
+use crate::sudoku_puzzle::SudokuPuzzle;
+use crate::guess_ordering::GuessOrdering;
+use crate::guess_ordering::random_seed;
+use crate::solver::Solver;
+use crate::config::Settings;
+use crate::constraint::ConstraintViolation;
+use crate::stopwatch::Stopwatch;
+use std::fmt;
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    pub solved: usize,
+    // None when uniqueness was not checked: puzzles with several solutions
+    // then count as solved, since only the first solution is looked for
+    pub multiple: Option<usize>,
+    pub unsolvable: usize,
+    pub invalid: usize,
+    // the solver gave up without deciding, e.g. on reaching the depth limit
+    pub errors: usize,
+    pub total_guesses: usize,
+    pub max_depth_reached: usize,
+    pub elapsed_millis: u128,
+    // every puzzle is solved with this seed, see Settings::seed
+    pub seed: u64,
+}
+
+impl BatchSummary {
+    pub fn puzzles(&self) -> usize {
+        self.solved + self.multiple.unwrap_or(0) + self.unsolvable + self.invalid + self.errors
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let puzzles = self.puzzles();
+        let per_puzzle = |total: u128| match puzzles {
+            0 => 0,
+            _ => total / puzzles as u128,
+        };
+        write!(f, "puzzles: {}, solved: {}, ", puzzles, self.solved)?;
+        if let Some(multiple) = self.multiple {
+            write!(f, "multiple solutions: {}, ", multiple)?;
+        }
+        writeln!(f, "unsolvable: {}, invalid: {}, errors: {}", self.unsolvable, self.invalid, self.errors)?;
+        writeln!(f, "guesses: {} total, {} avg; max depth reached: {}",
+                 self.total_guesses, per_puzzle(self.total_guesses as u128), self.max_depth_reached)?;
+        write!(f, "total time: {}ms, {}ms avg; seed: {}", self.elapsed_millis, per_puzzle(self.elapsed_millis), self.seed)
+    }
+}
+
+// the puzzle is the first whitespace-separated field of the line, so
+// collections with a trailing rating or comment can be read as-is;
+// blank lines and lines starting with '#' are skipped
+fn puzzle_field(line: &str) -> Option<&str> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    line.split_whitespace().next()
+}
+
+// solves one puzzle field, returning the single-line solutions found: one,
+// or up to two when check_uniqueness is set; malformed input comes back as
+// ConstraintViolation::InvalidInput
+fn solve_line(
+    field: &str,
+    guess_ordering: &dyn GuessOrdering,
+    settings: &Settings,
+    check_uniqueness: bool,
+    summary: &mut BatchSummary,
+) -> Result<Vec<String>, ConstraintViolation> {
+    let initial_strings = SudokuPuzzle::parse_line(field)?;
+    let mut s = Solver::new(SudokuPuzzle::new(initial_strings), guess_ordering.clone());
+    s.with_settings(settings.clone());
+    let result = match check_uniqueness {
+        true => s.count_solutions(2).map(|_| ()),
+        false => s.solve(),
+    };
+
+    summary.total_guesses += s.get_total_guesses();
+    if s.get_max_depth_reached() > summary.max_depth_reached {
+        summary.max_depth_reached = s.get_max_depth_reached();
+    }
+
+    result?;
+    if check_uniqueness {
+        return Ok(s.get_solutions().iter().map(|solution| solution.to_line()).collect());
+    }
+    Ok(vec![solution_line(s.get_puzzle())?])
+}
+
+// solve only returns Ok once the grid is filled and validated; should it
+// ever not, the puzzle is reported as an error instead of a partial solution
+fn solution_line(puzzle: &SudokuPuzzle) -> Result<String, ConstraintViolation> {
+    let line = puzzle.to_line();
+    match line.find('.') {
+        Some(index) => Err(ConstraintViolation::Unfinished { row: index / puzzle.size() + 1, column: index % puzzle.size() + 1 }),
+        None => Ok(line),
+    }
+}
+
+fn write_result<W: Write>(
+    output: &mut W,
+    result: Result<Vec<String>, ConstraintViolation>,
+    summary: &mut BatchSummary,
+) -> std::io::Result<()> {
+    match result {
+        Ok(solutions) if solutions.len() == 1 => {
+            summary.solved += 1;
+            writeln!(output, "{}", solutions[0])
+        },
+        Ok(solutions) if solutions.len() > 1 => {
+            *summary.multiple.get_or_insert(0) += 1;
+            writeln!(output, "multiple")
+        },
+        Err(ConstraintViolation::InvalidInput(e)) => {
+            summary.invalid += 1;
+            writeln!(output, "invalid: {}", e)
+        },
+        // only a contradiction shows there is no solution
+        Ok(_) |
+        Err(ConstraintViolation::NoCandidates { .. }) |
+        Err(ConstraintViolation::DigitMissing { .. }) |
+        Err(ConstraintViolation::DuplicateDigit { .. }) => {
+            summary.unsolvable += 1;
+            writeln!(output, "unsolvable")
+        },
+        Err(e) => {
+            summary.errors += 1;
+            writeln!(output, "error: {}", e)
+        },
+    }
+}
+
+// writes one line per puzzle: the single-line solution, "unsolvable",
+// "invalid: <reason>" or, should the solver give up on it without finding a
+// contradiction (e.g. at the depth limit), "error: <reason>"; with
+// check_uniqueness, puzzles with more than one
+// solution are written as "multiple" instead. Every puzzle gets the same
+// seed, so a puzzle solves the same way wherever it is in the batch
+pub fn solve_batch<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    guess_ordering: &dyn GuessOrdering,
+    settings: &Settings,
+    check_uniqueness: bool,
+) -> std::io::Result<BatchSummary> {
+    let seed = settings.seed.unwrap_or_else(random_seed);
+    let mut settings = settings.clone();
+    settings.seed = Some(seed);
+    let settings = &settings;
+    if settings.batch_jobs > 1 {
+        return solve_batch_in_parallel(input, output, guess_ordering, settings, check_uniqueness);
+    }
+    let mut summary = BatchSummary { seed, ..BatchSummary::default() };
+    if check_uniqueness {
+        summary.multiple = Some(0);
+    }
+    let mut stopwatch = Stopwatch::new();
+
+    for line in input.lines() {
+        let line = line?;
+        let field = match puzzle_field(&line) {
+            Some(field) => field,
+            None => continue,
+        };
+
+        stopwatch.start();
+        let result = solve_line(field, guess_ordering, settings, check_uniqueness, &mut summary);
+        stopwatch.stop();
+
+        write_result(output, result, &mut summary)?;
+    }
+
+    summary.elapsed_millis = stopwatch.elapsed_millis();
+    Ok(summary)
+}
+
+// what solve_line came to, with the guesses and depth it took
+type LineResult = (Result<Vec<String>, ConstraintViolation>, BatchSummary);
+
+// Settings::batch_jobs puzzles at a time, each solved on one thread; the
+// whole input is read first and the output keeps its order. The total time
+// is the time the batch took, not the sum over the puzzles
+fn solve_batch_in_parallel<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    guess_ordering: &dyn GuessOrdering,
+    settings: &Settings,
+    check_uniqueness: bool,
+) -> std::io::Result<BatchSummary> {
+    let mut fields: Vec<String> = vec![];
+    for line in input.lines() {
+        if let Some(field) = puzzle_field(&line?) {
+            fields.push(field.to_string());
+        }
+    }
+    let mut single_threaded = settings.clone();
+    single_threaded.search_threads = 1;
+
+    let mut stopwatch = Stopwatch::new();
+    stopwatch.start();
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<LineResult>>> = Mutex::new((0..fields.len()).map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..settings.batch_jobs.min(fields.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                if index >= fields.len() {
+                    break;
+                }
+                let mut puzzle_summary = BatchSummary::default();
+                let result = solve_line(&fields[index], guess_ordering, &single_threaded, check_uniqueness, &mut puzzle_summary);
+                results.lock().unwrap()[index] = Some((result, puzzle_summary));
+            });
+        }
+    });
+    stopwatch.stop();
+
+    let mut summary = BatchSummary { seed: settings.seed.unwrap_or_default(), ..BatchSummary::default() };
+    if check_uniqueness {
+        summary.multiple = Some(0);
+    }
+    for (result, puzzle_summary) in results.into_inner().unwrap().into_iter().flatten() {
+        summary.total_guesses += puzzle_summary.total_guesses;
+        if puzzle_summary.max_depth_reached > summary.max_depth_reached {
+            summary.max_depth_reached = puzzle_summary.max_depth_reached;
+        }
+        write_result(output, result, &mut summary)?;
+    }
+    summary.elapsed_millis = stopwatch.elapsed_millis();
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::guess_ordering::SimpleGuessOrderingByTuple;
+
+    const SJM20200425: &str = "7...12.89..8.57.....1.6....8......91......6..12......4....2.7.....64.2..43.17...6";
+    const SJM20200425_SOLUTION: &str = "765312489348957162291864375874236591953481627126795834619523748587649213432178956";
+    // square (1,9) has no candidates left
+    const CONTRADICTION: &str = "12345678.........9...............................................................";
+
+    #[test]
+    fn batch() {
+        let input = format!("# header\n{}  rating 2/4\n\n{}\n{}x\n", SJM20200425, CONTRADICTION, SJM20200425);
+        let mut output: Vec<u8> = vec![];
+        let ordering = SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+        let summary = solve_batch(input.as_bytes(), &mut output, &ordering, &Settings::new(), false).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], SJM20200425_SOLUTION);
+        assert_eq!(lines[1], "unsolvable");
+        assert!(lines[2].starts_with("invalid: "));
+
+        assert_eq!(summary.puzzles(), 3);
+        assert_eq!(summary.solved, 1);
+        assert_eq!(summary.unsolvable, 1);
+        assert_eq!(summary.invalid, 1);
+        // not checked, so not reported as 0
+        assert_eq!(summary.multiple, None);
+        assert!(!summary.to_string().contains("multiple"));
+    }
+
+    #[test]
+    fn batch_with_uniqueness() {
+        // rows 1 and 2 blank: swapping them gives a second solution
+        let multiple = format!("{}{}", ".".repeat(18), &SJM20200425_SOLUTION[18..]);
+        let input = format!("{}\n{}\n{}\n", SJM20200425, multiple, CONTRADICTION);
+        let mut output: Vec<u8> = vec![];
+        let ordering = SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+        let summary = solve_batch(input.as_bytes(), &mut output, &ordering, &Settings::new(), true).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().collect::<Vec<&str>>(), vec![SJM20200425_SOLUTION, "multiple", "unsolvable"]);
+        assert_eq!(summary.solved, 1);
+        assert_eq!(summary.multiple, Some(1));
+        assert!(summary.to_string().contains("multiple solutions: 1,"));
+        assert_eq!(summary.unsolvable, 1);
+    }
+
+    #[test]
+    fn batch_on_threads() {
+        let input = format!("{}\n{}\n{}x\n{}\n", SJM20200425, CONTRADICTION, SJM20200425, SJM20200425);
+        let mut ordering = SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+        ordering.with_randomization(false);
+        let mut sequential: Vec<u8> = vec![];
+        let expected = solve_batch(input.as_bytes(), &mut sequential, &ordering, &Settings::new(), false).unwrap();
+
+        let mut settings = Settings::new();
+        settings.batch_jobs = 3;
+        let mut output: Vec<u8> = vec![];
+        let summary = solve_batch(input.as_bytes(), &mut output, &ordering, &settings, false).unwrap();
+        // same lines in the same order
+        assert_eq!(String::from_utf8(output).unwrap(), String::from_utf8(sequential).unwrap());
+        assert_eq!(summary.puzzles(), 4);
+        assert_eq!(summary.solved, 2);
+        assert_eq!(summary.unsolvable, 1);
+        assert_eq!(summary.invalid, 1);
+        assert_eq!(summary.total_guesses, expected.total_guesses);
+    }
+
+    #[test]
+    fn batch_replays_with_seed() {
+        let hardest = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle).to_line();
+        let input = format!("{}\n{}\n", hardest, SJM20200425);
+        // randomized, so only the seed makes the runs agree
+        let ordering = SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+        let mut settings = Settings::new();
+        settings.seed = Some(99);
+        let first = solve_batch(input.as_bytes(), &mut vec![], &ordering, &settings, false).unwrap();
+        assert_eq!(first.seed, 99);
+        assert!(first.to_string().ends_with("seed: 99"));
+        settings.batch_jobs = 2;
+        let second = solve_batch(input.as_bytes(), &mut vec![], &ordering, &settings, false).unwrap();
+        assert_eq!(second.seed, 99);
+        assert_eq!(second.total_guesses, first.total_guesses);
+        assert_eq!(second.max_depth_reached, first.max_depth_reached);
+
+        // without one, a seed is picked and reported
+        let mut output: Vec<u8> = vec![];
+        let summary = solve_batch(input.as_bytes(), &mut output, &ordering, &Settings::new(), false).unwrap();
+        let mut settings = Settings::new();
+        settings.seed = Some(summary.seed);
+        let replayed = solve_batch(input.as_bytes(), &mut vec![], &ordering, &settings, false).unwrap();
+        assert_eq!(replayed.total_guesses, summary.total_guesses);
+    }
+
+    #[test]
+    fn unfinished_solutions_are_errors() {
+        let puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line(SJM20200425).unwrap());
+        let result = solution_line(&puzzle);
+        assert_eq!(result, Err(ConstraintViolation::Unfinished { row: 1, column: 2 }));
+        let mut output: Vec<u8> = vec![];
+        let mut summary = BatchSummary::default();
+        write_result(&mut output, result.map(|line| vec![line]), &mut summary).unwrap();
+        write_result(&mut output, Ok(vec![SJM20200425_SOLUTION.to_string()]), &mut summary).unwrap();
+        // the next puzzle still gets its line
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().collect::<Vec<&str>>(),
+                   vec!["error: the solver stopped with square (1,2) still open", SJM20200425_SOLUTION]);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.unsolvable, 0);
+        assert_eq!(summary.solved, 1);
+
+        let solved = SudokuPuzzle::new(SudokuPuzzle::parse_line(SJM20200425_SOLUTION).unwrap());
+        assert_eq!(solution_line(&solved), Ok(SJM20200425_SOLUTION.to_string()));
+    }
+
+    #[test]
+    fn depth_limit_is_an_error() {
+        let hardest = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle).to_line();
+        let input = format!("{}\n{}\n{}\n", hardest, SJM20200425, CONTRADICTION);
+        let ordering = SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+        let mut settings = Settings::new();
+        settings.max_search_depth = Some(1);
+        for jobs in [1, 2] {
+            settings.batch_jobs = jobs;
+            let mut output: Vec<u8> = vec![];
+            let summary = solve_batch(input.as_bytes(), &mut output, &ordering, &settings, false).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            let lines: Vec<&str> = output.lines().collect();
+            assert!(lines[0].starts_with("error: search depth limit of 1 exceeded"), "{}", lines[0]);
+            // logic alone solves this one
+            assert_eq!(lines[1], SJM20200425_SOLUTION);
+            assert_eq!(lines[2], "unsolvable");
+            assert_eq!((summary.errors, summary.solved, summary.unsolvable), (1, 1, 1));
+            assert_eq!(summary.puzzles(), 3);
+            assert!(summary.to_string().contains("unsolvable: 1, invalid: 0, errors: 1"));
+        }
+    }
+
+    #[test]
+    fn puzzle_fields() {
+        assert_eq!(puzzle_field("  "), None);
+        assert_eq!(puzzle_field("# comment"), None);
+        assert_eq!(puzzle_field("abc def"), Some("abc"));
+    }
+}
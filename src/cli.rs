@@ -0,0 +1,527 @@
+use crate::config::Settings;
+use crate::guess_ordering::GuessOrdering;
+use crate::guess_ordering;
+use crate::generator;
+use crate::generator::Difficulty;
+use crate::generator::Symmetry;
+use crate::pipeline::Pipeline;
+
+pub type OrderingTuple = (usize, usize, usize, usize, usize, usize, usize, usize);
+
+pub const DEFAULT_ORDERING: OrderingTuple = (4,3,2,5,6,7,8,9);
+pub const DEFAULT_PROFILE_PUZZLE_ID: &str = "worldshardest";
+pub const DEFAULT_PROFILE_TRIALS: usize = 100;
+
+pub const USAGE: &str = "\
+usage: sudoku_solver [<command> [options]]
+
+commands:
+  solve <file|->       solve the puzzle in <file> (or stdin when '-'), given
+                       as one line (81 characters for 9x9) or one line per row;
+                       4x4, 9x9, 16x16 (hex digits 0-F) and 25x25 (letters
+                       A-Y) boards are supported
+  batch <file|->       solve one single-line puzzle per line, print one
+                       solution per line and a summary on stderr
+  grade <file|->       solve with logic only and report the hardest technique
+                       needed, on the newspapers' 1-4 scale
+  explain <file|->     like grade, listing every deduction step by step
+  logic <file|->       run the solver's constraints without guessing; print
+                       the solution, or the stuck grid with its candidates
+  hint <file|->        show the simplest next deduction for a partly filled grid,
+                       using the solver's constraints in their order
+  generate             print a new 9x9 puzzle with a unique solution as one
+                       81-character line, its grade and solution on stderr;
+                       other board sizes are not generated
+  profile              solve a sample puzzle repeatedly and report timings
+  list-samples         list the ids and names of the sample puzzles
+
+  with no command, the interactive menu is shown
+
+options:
+  --ordering a,b,...   guess ordering tuple (8 candidate counts), e.g. 4,3,2,5,6,7,8,9
+  --strategy s,...     what to guess next: tuple (by --ordering, the default),
+                       mrv (fewest candidates, then most unsolved peers), digit
+                       (the digit with the fewest squares left in a unit) or
+                       lcv (as mrv, the values its peers need least first);
+                       profile runs every one listed, the rest use the first
+  --randomize          randomize the guess list (default)
+  --no-randomize       always guess in the same order
+  --max-depth <n>      maximum search depth, default the number of squares
+  --unique             (solve, batch) also check that the solution is unique;
+                       batch writes 'multiple' for puzzles with several
+  --poe                enable the ProcessOfElimination constraint
+  --no-poe             disable the ProcessOfElimination constraint
+  --constraints a,b,.. (solve, batch, logic, hint, profile) run these
+                       constraints in this order instead of the standard
+                       ones, e.g. advanced (adds locked candidates, subsets
+                       and fish) or standard,XYWing,XChain; see
+                       pipeline::CONSTRAINT_NAMES
+  --assume-unique      (solve, batch, grade, explain, logic, hint) the puzzle
+                       is known to have one solution, so the uniqueness
+                       techniques (unique rectangles, BUG+1) may be used
+  --search-threads <n> (solve, profile) split the search into branches and
+                       search them on <n> threads, 0 for one per core
+  --split-depth <n>    (solve, profile) levels of guesses made up front to get
+                       the branches for --search-threads, default 1
+  --jobs <n>           (batch) solve <n> puzzles at a time, each on one
+                       thread, 0 for one per core
+  --seed <n>           (solve, batch, profile, generate) seed the random
+                       choices, to replay a run with the seed it printed;
+                       default a random seed
+  --steps              (solve) also print every deduction and guess on the way
+                       to the solution; searches on one thread
+  --json               (explain, hint, solve --steps) print the steps as JSON
+  --symmetry <s>       (generate) rotational, mirror or none (default)
+  --givens <n>         (generate) remove givens until <n> are left, at most
+                       81; stops short of <n> when no more can be removed
+                       without a second solution; with --symmetry, <n>+1
+                       when only pairs could be removed
+  --difficulty <d>     (generate) easy, medium, hard or fiendish
+  --puzzle <id>        (profile) sample puzzle to profile, default worldshardest
+  --trials <n>         (profile) number of trials, default 100
+  -h, --help           show this message
+";
+
+#[derive(Debug, PartialEq)]
+pub enum PuzzleSource {
+    Stdin,
+    File(String),
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub ordering: Option<OrderingTuple>,
+    // see guess_ordering::STRATEGY_NAMES; empty for the tuple ordering
+    pub strategies: Vec<String>,
+    pub is_randomized: bool,
+    pub settings: Settings,
+    pub check_uniqueness: bool,
+    pub is_json: bool,
+    pub puzzle_id: String,
+    pub number_of_trials: usize,
+    pub symmetry: Symmetry,
+    pub target_givens: Option<usize>,
+    pub difficulty: Option<Difficulty>,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Options {
+            ordering: None,
+            strategies: vec![],
+            is_randomized: crate::config::RANDOMIZE_GUESSLIST,
+            settings: Settings::new(),
+            check_uniqueness: false,
+            is_json: false,
+            puzzle_id: DEFAULT_PROFILE_PUZZLE_ID.to_string(),
+            number_of_trials: DEFAULT_PROFILE_TRIALS,
+            symmetry: Symmetry::None,
+            target_givens: None,
+            difficulty: None,
+        }
+    }
+    // the first of the strategies
+    pub fn guess_ordering(&self) -> Box<dyn GuessOrdering> {
+        let name = self.strategies.first().map(|name| name.as_str()).unwrap_or("tuple");
+        guess_ordering::by_name(name, self.ordering.unwrap_or(DEFAULT_ORDERING), self.is_randomized)
+            .expect("strategy names are checked by parse_args")
+    }
+    pub fn guess_orderings(&self) -> Vec<Box<dyn GuessOrdering>> {
+        self.strategies
+            .iter()
+            .map(|name| guess_ordering::by_name(name, self.ordering.unwrap_or(DEFAULT_ORDERING), self.is_randomized)
+                 .expect("strategy names are checked by parse_args"))
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Interactive,
+    Help,
+    Solve(PuzzleSource, Options),
+    Batch(PuzzleSource, Options),
+    Grade(PuzzleSource, Options),
+    Explain(PuzzleSource, Options),
+    Logic(PuzzleSource, Options),
+    Hint(PuzzleSource, Options),
+    Profile(Options),
+    Generate(Options),
+    ListSamples,
+}
+
+// args excludes the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    if args.is_empty() {
+        return Ok(Command::Interactive);
+    }
+
+    let mut options = Options::new();
+    let mut positional: Vec<String> = vec![];
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--randomize" => options.is_randomized = true,
+            "--no-randomize" => options.is_randomized = false,
+            "--unique" => options.check_uniqueness = true,
+            "--json" => options.is_json = true,
+            "--steps" => options.settings.is_recording = true,
+            "--poe" => options.settings.is_poe_enabled = true,
+            "--no-poe" => options.settings.is_poe_enabled = false,
+            "--assume-unique" => options.settings.is_unique = true,
+            "--ordering" => {
+                let value = next_value(&mut iter, arg)?;
+                options.ordering = Some(parse_ordering(&value)?);
+            },
+            "--max-depth" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.max_search_depth = Some(parse_number(&value, arg)?);
+            },
+            "--strategy" => {
+                let value = next_value(&mut iter, arg)?;
+                for name in value.split(',').map(|name| name.trim()) {
+                    guess_ordering::by_name(name, DEFAULT_ORDERING, false)?;
+                    options.strategies.push(name.to_string());
+                }
+            },
+            "--search-threads" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.search_threads = parse_thread_count(&value, arg)?;
+            },
+            "--jobs" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.batch_jobs = parse_thread_count(&value, arg)?;
+            },
+            "--split-depth" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.split_depth = parse_number(&value, arg)?;
+                if options.settings.split_depth == 0 {
+                    return Err("--split-depth must be at least 1".to_string());
+                }
+            },
+            "--seed" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.seed = Some(value.parse()
+                    .map_err(|_| format!("{} expects a number, got '{}'", arg, value))?);
+            },
+            "--constraints" => {
+                let value = next_value(&mut iter, arg)?;
+                options.settings.pipeline = Pipeline::parse(&value)?;
+            },
+            "--symmetry" => {
+                let value = next_value(&mut iter, arg)?;
+                options.symmetry = Symmetry::parse(&value)
+                    .ok_or(format!("--symmetry expects rotational, mirror or none, got '{}'", value))?;
+            },
+            "--givens" => {
+                let value = next_value(&mut iter, arg)?;
+                let target_givens = parse_number(&value, arg)?;
+                if target_givens > generator::SQUARES {
+                    return Err(format!("--givens must be at most {}", generator::SQUARES));
+                }
+                options.target_givens = Some(target_givens);
+            },
+            "--difficulty" => {
+                let value = next_value(&mut iter, arg)?;
+                options.difficulty = Some(Difficulty::parse(&value)
+                    .ok_or(format!("--difficulty expects easy, medium, hard or fiendish, got '{}'", value))?);
+            },
+            "--puzzle" => options.puzzle_id = next_value(&mut iter, arg)?,
+            "--trials" => {
+                let value = next_value(&mut iter, arg)?;
+                options.number_of_trials = parse_number(&value, arg)?;
+                if options.number_of_trials == 0 {
+                    return Err("--trials must be at least 1".to_string());
+                }
+            },
+            // a lone '-' is the stdin placeholder, not a flag
+            _ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option: {}", arg)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+
+    if positional.is_empty() {
+        return Err("missing command".to_string());
+    }
+    let command = positional.remove(0);
+    match command.as_str() {
+        "solve" | "batch" | "grade" | "explain" | "hint" | "logic" => {
+            if positional.len() != 1 {
+                return Err(format!("{} expects exactly one <file|-> argument", command));
+            }
+            let source = match positional[0].as_str() {
+                "-" => PuzzleSource::Stdin,
+                path => PuzzleSource::File(path.to_string()),
+            };
+            match command.as_str() {
+                "solve" => Ok(Command::Solve(source, options)),
+                "grade" => Ok(Command::Grade(source, options)),
+                "explain" => Ok(Command::Explain(source, options)),
+                "hint" => Ok(Command::Hint(source, options)),
+                "logic" => Ok(Command::Logic(source, options)),
+                _ => Ok(Command::Batch(source, options)),
+            }
+        },
+        "profile" | "generate" | "list-samples" if !positional.is_empty() => {
+            Err(format!("unexpected argument for {}: {}", command, positional[0]))
+        },
+        "profile" => Ok(Command::Profile(options)),
+        "generate" => Ok(Command::Generate(options)),
+        "list-samples" => Ok(Command::ListSamples),
+        _ => Err(format!("unknown command: {}", command)),
+    }
+}
+
+fn next_value(iter: &mut std::slice::Iter<String>, flag: &str) -> Result<String, String> {
+    match iter.next() {
+        Some(value) => Ok(value.clone()),
+        None => Err(format!("{} requires a value", flag)),
+    }
+}
+
+fn parse_number(value: &str, flag: &str) -> Result<usize, String> {
+    value.parse::<usize>().map_err(|_| format!("{} expects a number, got '{}'", flag, value))
+}
+
+// 0 stands for one per core
+fn parse_thread_count(value: &str, flag: &str) -> Result<usize, String> {
+    match parse_number(value, flag)? {
+        0 => Ok(std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)),
+        threads => Ok(threads),
+    }
+}
+
+pub fn parse_ordering(value: &str) -> Result<OrderingTuple, String> {
+    let degrees: Vec<usize> = value
+        .split(',')
+        .map(|s| s.trim().parse::<usize>())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("--ordering expects comma separated numbers, got '{}'", value))?;
+    if degrees.len() != 8 {
+        return Err(format!("--ordering expects 8 values, got {}", degrees.len()));
+    }
+    // every candidate count from 2 through 9 must appear exactly once
+    let mut sorted = degrees.clone();
+    sorted.sort_unstable();
+    if sorted != (2..=9).collect::<Vec<usize>>() {
+        return Err(format!("--ordering must be a permutation of 2..9, got '{}'", value));
+    }
+    Ok((degrees[0], degrees[1], degrees[2], degrees[3],
+        degrees[4], degrees[5], degrees[6], degrees[7]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn no_arguments_is_interactive() {
+        match parse_args(&[]) {
+            Ok(Command::Interactive) => (),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn solve_file_with_flags() {
+        let parsed = parse_args(&args(&[
+            "solve", "puzzle.txt", "--ordering", "2,3,4,5,6,7,8,9",
+            "--no-randomize", "--max-depth", "12", "--poe", "--unique",
+        ]));
+        match parsed {
+            Ok(Command::Solve(source, options)) => {
+                assert_eq!(source, PuzzleSource::File("puzzle.txt".to_string()));
+                assert_eq!(options.ordering, Some((2,3,4,5,6,7,8,9)));
+                assert!(!options.is_randomized);
+                assert_eq!(options.settings.max_search_depth, Some(12));
+                assert!(options.settings.is_poe_enabled);
+                assert!(options.check_uniqueness);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn solve_stdin() {
+        match parse_args(&args(&["solve", "-"])) {
+            Ok(Command::Solve(PuzzleSource::Stdin, _)) => (),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn grade() {
+        match parse_args(&args(&["grade", "-"])) {
+            Ok(Command::Grade(PuzzleSource::Stdin, options)) => assert!(!options.settings.is_unique),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["grade", "-", "--assume-unique"])) {
+            Ok(Command::Grade(PuzzleSource::Stdin, options)) => assert!(options.settings.is_unique),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["hint", "-"])) {
+            Ok(Command::Hint(PuzzleSource::Stdin, options)) => assert!(!options.is_json),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["logic", "-", "--poe"])) {
+            Ok(Command::Logic(PuzzleSource::Stdin, options)) => assert!(options.settings.is_poe_enabled),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["explain", "puzzle.txt", "--json"])) {
+            Ok(Command::Explain(PuzzleSource::File(path), options)) => {
+                assert_eq!(path, "puzzle.txt");
+                assert!(options.is_json);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn steps() {
+        match parse_args(&args(&["solve", "-", "--steps", "--json"])) {
+            Ok(Command::Solve(_, options)) => {
+                assert!(options.settings.is_recording);
+                assert!(options.is_json);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn batch() {
+        match parse_args(&args(&["batch", "top95.txt", "--max-depth", "40"])) {
+            Ok(Command::Batch(PuzzleSource::File(path), options)) => {
+                assert_eq!(path, "top95.txt");
+                assert_eq!(options.settings.max_search_depth, Some(40));
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(parse_args(&args(&["batch"])).is_err());
+    }
+
+    #[test]
+    fn strategies() {
+        match parse_args(&args(&["profile", "--strategy", "tuple,MRV, lcv", "--ordering", "2,3,4,5,6,7,8,9"])) {
+            Ok(Command::Profile(options)) => {
+                assert_eq!(options.strategies, vec!["tuple", "MRV", "lcv"]);
+                let names: Vec<String> = options.guess_orderings().iter().map(|ordering| ordering.name()).collect();
+                assert_eq!(names, vec!["Simple (2,3,4,5,6,7,8,9)", "MRV (degree tie-break)", "least constraining value"]);
+                assert_eq!(options.guess_ordering().name(), "Simple (2,3,4,5,6,7,8,9)");
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["solve", "-"])) {
+            Ok(Command::Solve(_, options)) => assert_eq!(options.guess_ordering().name(), "Simple (4,3,2,5,6,7,8,9)"),
+            other => panic!("unexpected: {:?}", other),
+        }
+        let error = parse_args(&args(&["solve", "-", "--strategy", "mrv,magic"])).unwrap_err();
+        assert!(error.starts_with("unknown strategy 'magic'"));
+    }
+
+    #[test]
+    fn threads() {
+        match parse_args(&args(&["solve", "-", "--search-threads", "4", "--split-depth", "2"])) {
+            Ok(Command::Solve(_, options)) => {
+                assert_eq!(options.settings.search_threads, 4);
+                assert_eq!(options.settings.split_depth, 2);
+                assert_eq!(options.settings.batch_jobs, 1);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["batch", "-", "--jobs", "0"])) {
+            Ok(Command::Batch(_, options)) => {
+                assert!(options.settings.batch_jobs >= 1);
+                assert_eq!(options.settings.search_threads, 1);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(parse_args(&args(&["solve", "-", "--threads", "4"])).is_err());
+        assert!(parse_args(&args(&["solve", "-", "--split-depth", "0"])).is_err());
+    }
+
+    #[test]
+    fn seed() {
+        match parse_args(&args(&["profile", "--seed", "18446744073709551615"])) {
+            Ok(Command::Profile(options)) => assert_eq!(options.settings.seed, Some(u64::MAX)),
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["solve", "-"])) {
+            Ok(Command::Solve(_, options)) => assert_eq!(options.settings.seed, None),
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(parse_args(&args(&["solve", "-", "--seed", "-1"])).is_err());
+        assert!(parse_args(&args(&["solve", "-", "--seed"])).is_err());
+    }
+
+    #[test]
+    fn constraints() {
+        match parse_args(&args(&["solve", "-", "--constraints", "NoRowDuplicates,xywing"])) {
+            Ok(Command::Solve(_, options)) => {
+                let names: Vec<String> = options.settings.pipeline
+                    .build(&options.settings)
+                    .iter()
+                    .map(|constraint| constraint.name())
+                    .collect();
+                assert_eq!(names, vec!["NoRowDuplicates", "XYWing"]);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        assert!(parse_args(&args(&["solve", "-", "--constraints", "NoRowDuplicates,Magic"])).is_err());
+        assert!(parse_args(&args(&["solve", "-", "--constraints"])).is_err());
+    }
+
+    #[test]
+    fn profile_and_list_samples() {
+        match parse_args(&args(&["profile", "--trials", "5", "--puzzle", "sjm20190118"])) {
+            Ok(Command::Profile(options)) => {
+                assert_eq!(options.number_of_trials, 5);
+                assert_eq!(options.puzzle_id, "sjm20190118");
+                assert_eq!(options.ordering, None);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["generate", "--symmetry", "rotational", "--givens", "28", "--difficulty", "hard"])) {
+            Ok(Command::Generate(options)) => {
+                assert_eq!(options.symmetry, Symmetry::Rotational);
+                assert_eq!(options.target_givens, Some(28));
+                assert_eq!(options.difficulty, Some(Difficulty::Hard));
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        match parse_args(&args(&["list-samples"])) {
+            Ok(Command::ListSamples) => (),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_args(&args(&["solve"])).is_err());
+        assert!(parse_args(&args(&["--poe"])).is_err());
+        assert!(parse_args(&args(&["solve", "a", "b"])).is_err());
+        assert!(parse_args(&args(&["frobnicate"])).is_err());
+        assert!(parse_args(&args(&["profile", "--bogus"])).is_err());
+        assert!(parse_args(&args(&["profile", "--trials"])).is_err());
+        assert!(parse_args(&args(&["profile", "--trials", "0"])).is_err());
+        assert!(parse_args(&args(&["solve", "-", "--max-depth", "x"])).is_err());
+        assert!(parse_args(&args(&["list-samples", "extra"])).is_err());
+        assert!(parse_args(&args(&["generate", "--symmetry", "diagonal"])).is_err());
+        assert!(parse_args(&args(&["generate", "--difficulty", "trivial"])).is_err());
+        assert!(parse_args(&args(&["generate", "--givens", "82"])).is_err());
+    }
+
+    #[test]
+    fn ordering() {
+        assert_eq!(parse_ordering("4,3,2,5,6,7,8,9"), Ok(DEFAULT_ORDERING));
+        assert!(parse_ordering("4,3,2").is_err());
+        assert!(parse_ordering("4,3,2,5,6,7,8,8").is_err());
+        assert!(parse_ordering("1,3,2,5,6,7,8,9").is_err());
+        assert!(parse_ordering("a,3,2,5,6,7,8,9").is_err());
+    }
+}
@@ -1,5 +1,65 @@
 pub const DEBUG_PRINT_ENABLED: bool = false;
 pub const RANDOMIZE_GUESSLIST: bool = true;
-pub const MAX_SEARCH_DEPTH: usize = 30;
 pub const SAMPLE_PUZZLE_ID: &str = "sjm20200425";
 pub const IS_POE_ENABLED: bool = false;
+// 1 searches on the calling thread only
+pub const SEARCH_THREADS: usize = 1;
+pub const SPLIT_DEPTH: usize = 1;
+// 1 solves one puzzle after the other
+pub const BATCH_JOBS: usize = 1;
+
+use crate::pipeline::Pipeline;
+
+// the constants above are the defaults; Settings lets the command line
+// override them at runtime without a recompile
+#[derive(Clone, Debug)]
+pub struct Settings {
+    // None: the board's default, see default_max_search_depth
+    pub max_search_depth: Option<usize>,
+    pub is_poe_enabled: bool,
+    // the caller promises the puzzle has exactly one solution, which lets
+    // UniqueRectangle and BugPlusOne run
+    pub is_unique: bool,
+    // the constraints the solver runs, see Pipeline::standard
+    pub pipeline: Pipeline,
+    // more than 1: Solver::solve hands the branches of the first guesses to
+    // this many worker threads. The workers race, so the guess count can
+    // change from run to run; the solution of a puzzle with one does not
+    pub search_threads: usize,
+    // how many levels of guesses are made up front to get the branches the
+    // workers share out
+    pub split_depth: usize,
+    // more than 1: batch solves this many puzzles at a time, each on one
+    // thread, so every puzzle is searched just as it would be on its own
+    pub batch_jobs: usize,
+    // where the guess orderings' random choices come from; the same seed
+    // replays the same search (with search_threads 1, see above).
+    // None: a random one, see Solver::get_seed
+    pub seed: Option<u64>,
+    // the solver keeps every deduction and the guesses that held as steps,
+    // see Solver::get_steps; the search then stays on one thread
+    pub is_recording: bool,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            max_search_depth: None,
+            is_poe_enabled: IS_POE_ENABLED,
+            is_unique: false,
+            pipeline: Pipeline::standard(),
+            search_threads: SEARCH_THREADS,
+            split_depth: SPLIT_DEPTH,
+            batch_jobs: BATCH_JOBS,
+            seed: None,
+            is_recording: false,
+        }
+    }
+}
+
+// every guess fills in one more square, so no search on a board with size
+// squares per row needs to go deeper than size * size; 81 for 9x9, 256 for
+// 16x16, where an empty board already takes around 150 levels
+pub fn default_max_search_depth(size: usize) -> usize {
+    size * size
+}
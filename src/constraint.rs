@@ -1,157 +1,1857 @@
 use crate::sudoku_puzzle::SudokuPuzzle;
+use crate::sudoku_puzzle::ParseError;
 use crate::solver::QueueItem;
 use crate::squares;
+use crate::grid::Grid;
 use crate::stopwatch::Stopwatch;
 use crate::config;
+use crate::config::Settings;
+use crate::pipeline::Pipeline;
+use crate::explain::Recorder;
+use crate::explain::Step;
+use crate::explain::Technique;
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+// memoization key for ProcessOfElimination: unit id followed by the unit's bitmaps
+type UnitKey = Vec<usize>;
 
 pub trait Constraint {
     fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation>;
     fn name(&self) -> String;
     fn elapsed_millis(&self) -> u128;
     fn elapsed_sub_millis(&self) -> Option<u128> { None }
+    // explanations, see explain::Recorder; constraints that never change
+    // candidates can ignore these
+    fn set_recording(&mut self, _is_recording: bool) {}
+    fn take_steps(&mut self) -> Vec<Step> { vec![] }
+    // unit-based constraints only ever look at whole rows, columns and boxes;
+    // the solver then calls process_unit once per changed unit and round
+    // instead of process once per changed square. removed is the candidate
+    // bits the unit's squares lost since it was last processed
+    fn is_unit_based(&self) -> bool { false }
+    fn process_unit(&mut self, _unit: Unit, _removed: usize, _puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        Ok(())
+    }
+    // drops whatever was remembered about earlier boards; the solver calls it
+    // for every guess, since backtracking can bring back a board it has seen
+    // with the changes made to it undone
+    fn forget(&mut self) {}
+}
+
+// rows, columns and boxes are numbered 1 to the board size (1-9 on a 9x9
+// board); boxes go left to right, top to bottom
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Row(usize),
+    Column(usize),
+    Box(usize),
+}
+impl Unit {
+    pub fn containing_box(box_size: usize, row: usize, column: usize) -> Self {
+        Unit::Box(box_util::box_number(box_size, row, column))
+    }
+    pub fn cells(&self, box_size: usize) -> Vec<(usize, usize)> {
+        let size = box_size * box_size;
+        match *self {
+            Unit::Row(row) => (1..=size).map(|c| (row, c)).collect(),
+            Unit::Column(column) => (1..=size).map(|r| (r, column)).collect(),
+            Unit::Box(number) => box_util::box_cells(box_size,
+                                                     box_size*((number-1)/box_size) + 1,
+                                                     box_size*((number-1)%box_size) + 1),
+        }
+    }
+    // position in Unit::all
+    pub fn index(&self, box_size: usize) -> usize {
+        let size = box_size * box_size;
+        match *self {
+            Unit::Row(row) => row - 1,
+            Unit::Column(column) => size + column - 1,
+            Unit::Box(number) => 2*size + number - 1,
+        }
+    }
+    pub fn all(box_size: usize) -> Vec<Unit> {
+        let size = box_size * box_size;
+        (1..=size).map(Unit::Row)
+            .chain((1..=size).map(Unit::Column))
+            .chain((1..=size).map(Unit::Box))
+            .collect()
+    }
+}
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Row(n) => write!(f, "row {}", n),
+            Unit::Column(n) => write!(f, "column {}", n),
+            Unit::Box(n) => write!(f, "box {}", n),
+        }
+    }
 }
 
-#[derive(Debug)]
-pub struct ConstraintViolation {
-    pub msg: String,
+// every way a puzzle (or a guess) can fail; squares are (row, column), 1-based
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintViolation {
+    // every candidate of the square has been eliminated
+    NoCandidates { row: usize, column: usize },
+    // no square in the unit can hold the digit any more
+    DigitMissing { unit: Unit, digit: usize },
+    // the digit is placed more than once in the unit, e.g. a duplicate given;
+    // row and column name one of the offending squares
+    DuplicateDigit { unit: Unit, digit: usize, row: usize, column: usize },
+    // the search went deeper than Settings::max_search_depth while guessing digit at (row, column)
+    DepthLimitExceeded { row: usize, column: usize, digit: usize, limit: usize },
+    InvalidInput(ParseError),
+    // another worker of a parallel search found the solution first; the
+    // search that started the workers never returns it, see Settings::search_threads
+    Cancelled,
+    // the solver reported success with the square still open, which is a
+    // bug; batch reports it as an error rather than a partial solution
+    Unfinished { row: usize, column: usize },
+}
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstraintViolation::NoCandidates { row, column } =>
+                write!(f, "square ({},{}) has no candidates left", row, column),
+            ConstraintViolation::DigitMissing { unit, digit } =>
+                write!(f, "{} has no square left for {}", unit, digit),
+            ConstraintViolation::DuplicateDigit { unit, digit, row, column } =>
+                write!(f, "{} appears more than once in {} (at ({},{}))", digit, unit, row, column),
+            ConstraintViolation::DepthLimitExceeded { row, column, digit, limit } =>
+                write!(f, "search depth limit of {} exceeded guessing {} at ({},{})", limit, digit, row, column),
+            ConstraintViolation::InvalidInput(e) =>
+                write!(f, "invalid input: {}", e),
+            ConstraintViolation::Cancelled =>
+                write!(f, "cancelled, the solution was found elsewhere"),
+            ConstraintViolation::Unfinished { row, column } =>
+                write!(f, "the solver stopped with square ({},{}) still open", row, column),
+        }
+    }
+}
+impl From<ParseError> for ConstraintViolation {
+    fn from(e: ParseError) -> Self {
+        ConstraintViolation::InvalidInput(e)
+    }
 }
 
 pub struct DoubleDoubleBox {
     stopwatch: Stopwatch,
+    recorder: Recorder,
 }
 impl DoubleDoubleBox {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
             DoubleDoubleBox {
-                stopwatch
+                stopwatch,
+                recorder: Recorder::new(),
+            }
+        }
+    }
+    // 9x9 only
+    fn _process_deprecated(&self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
+            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
+
+            [1,1,1,2,2,2,3,3,3].iter().zip([1,2,3,1,2,3,1,2,3].iter())
+                .map(|(i,j)| { (i+((box_util::index_to_box(3, queue_item.row)-1)*3),
+                                j+((box_util::index_to_box(3, queue_item.column)-1)*3))})
+                .filter(|(i,j)| *i!=queue_item.row || *j!=queue_item.column)
+                .for_each(|(i,j)| {
+                    if puzzle.get_square(i,j).get_bitmap() == bitmap {
+                        // bingo!
+                        [1,1,1,2,2,2,3,3,3].iter().zip([1,2,3,1,2,3,1,2,3].iter())
+                            .map(|(i2,j2)| { (i2+((box_util::index_to_box(3, queue_item.row)-1)*3),
+                                              j2+((box_util::index_to_box(3, queue_item.column)-1)*3))})
+                            .filter(|(i2,j2)| *i2!=queue_item.row || *j2!=queue_item.column)
+                            .filter(|(i2,j2)| *i2!=i || *j2!=j)
+                            .for_each(|(i,j)| puzzle.get_square(i,j).bitmap_elimination(bitmap));
+                    }
+                });
+        }
+        Ok(())
+    }
+}
+impl Constraint for DoubleDoubleBox {
+    fn name(&self) -> String {
+        "DoubleDoubleBox".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        let row = queue_item.row;
+        let col = queue_item.column;
+        let box_size = puzzle.box_size();
+        if puzzle.get_square(row,col).count_remaining() == 2 {
+            let bitmap = puzzle.get_square(row,col).get_bitmap();
+            let recorder = &mut self.recorder;
+            box_util::box_peers(box_size,row,col).iter().for_each(|(i,j)| {
+                if puzzle.get_square(*i,*j).get_bitmap() == bitmap {
+                    // bingo!
+                    let snapshot = recorder.snapshot(puzzle, || box_util::box_peers(box_size,row,col));
+                    box_util::box_cells(box_size,row,col).iter()
+                        .filter(|(i2,j2)| *i2!=row || *j2!=col)
+                        .filter(|(i2,j2)| *i2!=*i || *j2!=*j)
+                        .for_each(|(i,j)| puzzle.get_square(*i,*j).bitmap_elimination(bitmap));
+                    let digits = puzzle.get_square(row,col).values_remaining();
+                    recorder.record(Technique::NakedPair, Some(Unit::containing_box(box_size,row,col)), vec![(row,col),(*i,*j)], digits, puzzle, snapshot);
+                }
+            });
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+pub struct DoubleDoubleColumn {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+}
+impl DoubleDoubleColumn {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            DoubleDoubleColumn {
+                stopwatch,
+                recorder: Recorder::new(),
+            }
+        }
+    }
+}
+impl Constraint for DoubleDoubleColumn {
+    fn name(&self) -> String {
+        "DoubleDoubleColumn".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        let size = puzzle.size();
+        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
+            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
+            let recorder = &mut self.recorder;
+            (1..=size)
+                .filter(|r| *r != queue_item.row)
+                .for_each(|r| {
+                    if puzzle.get_square(r, queue_item.column).get_bitmap() == bitmap {
+                        // bingo!
+                        let snapshot = recorder.snapshot(puzzle, || (1..=size).map(|r| (r, queue_item.column)).collect());
+                        (1..=size).filter(|r2| *r2 != queue_item.row && *r2 != r)
+                            .for_each(|r| puzzle.get_square(r, queue_item.column).bitmap_elimination(bitmap));
+                        let digits = puzzle.get_square(r, queue_item.column).values_remaining();
+                        recorder.record(Technique::NakedPair, Some(Unit::Column(queue_item.column)),
+                                        vec![(queue_item.row, queue_item.column), (r, queue_item.column)], digits, puzzle, snapshot);
+                    }
+                });
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+pub struct DoubleDoubleRow {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+}
+impl DoubleDoubleRow {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            DoubleDoubleRow {
+                stopwatch,
+                recorder: Recorder::new(),
+            }
+        }
+    }
+}
+impl Constraint for DoubleDoubleRow {
+    fn name(&self) -> String {
+        "DoubleDoubleRow".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        let size = puzzle.size();
+        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
+            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
+            let recorder = &mut self.recorder;
+            (1..=size)
+                .filter(|c| *c != queue_item.column)
+                .for_each(|c| {
+                    if puzzle.get_square(queue_item.row, c).get_bitmap() == bitmap {
+                        // bingo!
+                        let snapshot = recorder.snapshot(puzzle, || (1..=size).map(|c| (queue_item.row, c)).collect());
+                        (1..=size).filter(|c2| *c2 != queue_item.column && *c2 != c)
+                            .for_each(|c| puzzle.get_square(queue_item.row, c).bitmap_elimination(bitmap));
+                        let digits = puzzle.get_square(queue_item.row, c).values_remaining();
+                        recorder.record(Technique::NakedPair, Some(Unit::Row(queue_item.row)),
+                                        vec![(queue_item.row, queue_item.column), (queue_item.row, c)], digits, puzzle, snapshot);
+                    }
+                });
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// every way to pick n of the items, keeping their order
+fn combinations<T: Copy>(items: &[T], n: usize) -> Vec<Vec<T>> {
+    if n == 0 {
+        return vec![vec![]];
+    }
+    if items.len() < n {
+        return vec![];
+    }
+    let mut with_first: Vec<Vec<T>> = combinations(&items[1..], n - 1)
+        .into_iter()
+        .map(|mut rest| { rest.insert(0, items[0]); rest })
+        .collect();
+    with_first.append(&mut combinations(&items[1..], n));
+    with_first
+}
+
+// the row, column and box of the queue item
+pub fn units_of(queue_item: &QueueItem, box_size: usize) -> [Unit; 3] {
+    [
+        Unit::Row(queue_item.row),
+        Unit::Column(queue_item.column),
+        Unit::containing_box(box_size, queue_item.row, queue_item.column),
+    ]
+}
+
+// generalizes the DoubleDouble* constraints: n unsolved squares of a unit whose
+// candidates together are exactly n digits; those digits can go nowhere else
+// in the unit
+pub struct NakedSubsets {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    sizes: RangeInclusive<usize>,
+}
+impl NakedSubsets {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            NakedSubsets {
+                stopwatch,
+                recorder: Recorder::new(),
+                sizes: 2..=4,
+            }
+        }
+    }
+    // only look for subsets of exactly this many squares (2 for pairs, ...)
+    pub fn with_size(mut self, size: usize) -> Self {
+        self.sizes = size..=size;
+        self
+    }
+    fn technique(size: usize) -> Technique {
+        match size {
+            2 => Technique::NakedPair,
+            3 => Technique::NakedTriple,
+            _ => Technique::NakedQuad,
+        }
+    }
+    fn search_unit(&mut self, unit: Unit, size: usize, puzzle: &mut SudokuPuzzle) {
+        let cells = unit.cells(puzzle.box_size());
+        let open: Vec<(usize, usize)> = cells
+            .iter()
+            .filter(|(r,c)| (2..=size).contains(&puzzle.get_square(*r,*c).count_remaining()))
+            .copied()
+            .collect();
+        for subset in combinations(&open, size) {
+            // earlier subsets may have changed the candidates, so read them afresh
+            let union = subset.iter().fold(0, |acc, (r,c)| acc | puzzle.get_square(*r,*c).get_bitmap());
+            if squares::number_of_bits(union) != size {
+                continue;
+            }
+            let others: Vec<(usize, usize)> = cells
+                .iter()
+                .filter(|cell| !subset.contains(cell))
+                .filter(|(r,c)| puzzle.get_square(*r,*c).get_bitmap() & union != 0)
+                .copied()
+                .collect();
+            if others.is_empty() {
+                continue;
+            }
+            let snapshot = self.recorder.snapshot(puzzle, || others.clone());
+            others.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).bitmap_elimination(union));
+            self.recorder.record(NakedSubsets::technique(size), Some(unit), subset,
+                                 squares::bitmap_values(union), puzzle, snapshot);
+        }
+    }
+}
+impl Constraint for NakedSubsets {
+    fn name(&self) -> String {
+        "NakedSubsets".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        if !puzzle.get_square(queue_item.row, queue_item.column).has_single_value() {
+            for unit in units_of(queue_item, puzzle.box_size()) {
+                for size in self.sizes.clone() {
+                    self.search_unit(unit, size, puzzle);
+                }
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+    fn is_unit_based(&self) -> bool {
+        true
+    }
+    fn process_unit(&mut self, unit: Unit, _removed: usize, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        for size in self.sizes.clone() {
+            self.search_unit(unit, size, puzzle);
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// the mirror image of NakedSubsets: n digits that, within a unit, only fit in
+// the same n squares; those squares can hold nothing else
+pub struct HiddenSubsets {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    sizes: RangeInclusive<usize>,
+}
+impl HiddenSubsets {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            HiddenSubsets {
+                stopwatch,
+                recorder: Recorder::new(),
+                sizes: 2..=4,
+            }
+        }
+    }
+    // only look for subsets of exactly this many digits (2 for pairs, ...)
+    pub fn with_size(mut self, size: usize) -> Self {
+        self.sizes = size..=size;
+        self
+    }
+    fn technique(size: usize) -> Technique {
+        match size {
+            2 => Technique::HiddenPair,
+            3 => Technique::HiddenTriple,
+            _ => Technique::HiddenQuad,
+        }
+    }
+    fn search_unit(&mut self, unit: Unit, size: usize, puzzle: &mut SudokuPuzzle) {
+        let cells = unit.cells(puzzle.box_size());
+        // for each digit not yet placed in the unit, a bitmap of the squares
+        // (by position within the unit) where it still fits
+        let mut positions: Vec<(usize, usize)> = vec![];
+        let mut placed = 0;
+        for (r,c) in cells.iter() {
+            if puzzle.get_square(*r,*c).has_single_value() {
+                placed |= puzzle.get_square(*r,*c).get_bitmap();
+            }
+        }
+        for digit in 1..=puzzle.size() {
+            if placed & squares::bit(digit) != 0 {
+                continue;
+            }
+            let mut where_possible = 0;
+            for (index, (r,c)) in cells.iter().enumerate() {
+                if puzzle.get_square(*r,*c).is_possible(digit) {
+                    where_possible |= 1 << index;
+                }
+            }
+            if (2..=size).contains(&squares::number_of_bits(where_possible)) {
+                positions.push((digit, where_possible));
+            }
+        }
+
+        for subset in combinations(&positions, size) {
+            let union = subset.iter().fold(0, |acc, (_, where_possible)| acc | where_possible);
+            if squares::number_of_bits(union) != size {
+                continue;
+            }
+            let digits: Vec<usize> = subset.iter().map(|(digit, _)| *digit).collect();
+            let keep = digits.iter().fold(0, |acc, digit| acc | squares::bit(*digit));
+            let hidden_in: Vec<(usize, usize)> = cells
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| union & (1 << index) != 0)
+                .map(|(_, cell)| *cell)
+                .collect();
+            if hidden_in.iter().all(|(r,c)| puzzle.get_square(*r,*c).get_bitmap() & !keep == 0) {
+                continue;
+            }
+            let snapshot = self.recorder.snapshot(puzzle, || hidden_in.clone());
+            hidden_in.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).bitmap_elimination(!keep));
+            self.recorder.record(HiddenSubsets::technique(size), Some(unit), hidden_in, digits, puzzle, snapshot);
+        }
+    }
+}
+impl Constraint for HiddenSubsets {
+    fn name(&self) -> String {
+        "HiddenSubsets".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        // a square losing a candidate can leave a hidden subset elsewhere in
+        // its units, so this runs for solved squares too
+        for unit in units_of(queue_item, puzzle.box_size()) {
+            for size in self.sizes.clone() {
+                self.search_unit(unit, size, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+    fn is_unit_based(&self) -> bool {
+        true
+    }
+    fn process_unit(&mut self, unit: Unit, _removed: usize, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        for size in self.sizes.clone() {
+            self.search_unit(unit, size, puzzle);
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// interactions between a box and a line (row or column): when a digit's
+// candidates in the box all lie in one line, the digit must go there, so it
+// is eliminated from the rest of the line ("pointing"); when its candidates
+// in a line all lie in one box, it is eliminated from the rest of the box
+// ("claiming", or box/line reduction)
+pub struct LockedCandidates {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+}
+impl LockedCandidates {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            LockedCandidates {
+                stopwatch,
+                recorder: Recorder::new(),
+            }
+        }
+    }
+    // the squares of the unit where digit still fits; empty once it is placed
+    fn candidates(digit: usize, unit: Unit, puzzle: &mut SudokuPuzzle) -> Vec<(usize, usize)> {
+        let mut cells = vec![];
+        for (r,c) in unit.cells(puzzle.box_size()) {
+            if puzzle.get_square(r,c).is_possible(digit) {
+                if puzzle.get_square(r,c).has_single_value() {
+                    return vec![];
+                }
+                cells.push((r,c));
+            }
+        }
+        cells
+    }
+    // digit must go in one of locked_in (its candidates in source), which all
+    // lie in target, so it is removed from the rest of target
+    fn lock(&mut self, digit: usize, locked_in: Vec<(usize, usize)>, source: Unit, target: Unit,
+            puzzle: &mut SudokuPuzzle) {
+        let others: Vec<(usize, usize)> = target.cells(puzzle.box_size())
+            .into_iter()
+            .filter(|cell| !locked_in.contains(cell))
+            .filter(|(r,c)| puzzle.get_square(*r,*c).is_possible(digit))
+            .collect();
+        if others.is_empty() {
+            return;
+        }
+        let snapshot = self.recorder.snapshot(puzzle, || others.clone());
+        others.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).eliminate(digit));
+        self.recorder.record(Technique::LockedCandidates, Some(source), locked_in, vec![digit], puzzle, snapshot);
+    }
+    // pointing from a box to a row or column, claiming from a row or column
+    // to a box
+    fn search_unit(&mut self, digit: usize, unit: Unit, puzzle: &mut SudokuPuzzle) {
+        let box_size = puzzle.box_size();
+        let in_unit = LockedCandidates::candidates(digit, unit, puzzle);
+        let (first_row, first_col) = match in_unit.first() {
+            Some(first) => *first,
+            None => return,
+        };
+        match unit {
+            Unit::Box(_) => {
+                if in_unit.iter().all(|(r,_)| *r == first_row) {
+                    self.lock(digit, in_unit, unit, Unit::Row(first_row), puzzle);
+                } else if in_unit.iter().all(|(_,c)| *c == first_col) {
+                    self.lock(digit, in_unit, unit, Unit::Column(first_col), puzzle);
+                }
+            },
+            _ => {
+                let line_box = Unit::containing_box(box_size, first_row, first_col);
+                if in_unit.iter().all(|(r,c)| Unit::containing_box(box_size, *r, *c) == line_box) {
+                    self.lock(digit, in_unit, unit, line_box, puzzle);
+                }
+            },
+        }
+    }
+}
+impl Constraint for LockedCandidates {
+    fn name(&self) -> String {
+        "LockedCandidates".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        let [row, column, the_box] = units_of(queue_item, puzzle.box_size());
+        for digit in 1..=puzzle.size() {
+            for unit in [the_box, row, column] {
+                self.search_unit(digit, unit, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+    fn is_unit_based(&self) -> bool {
+        true
+    }
+    // only a digit that lost candidates in the unit can have become locked
+    fn process_unit(&mut self, unit: Unit, removed: usize, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        for digit in 1..=puzzle.size() {
+            if removed & squares::bit(digit) != 0 {
+                self.search_unit(digit, unit, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// basic fish: for one digit, n rows whose candidates all lie in the same n
+// columns; each of those columns then gets its digit from one of the rows,
+// so it is eliminated from the rest of the columns (and the same with rows
+// and columns swapped); n = 2 is an X-Wing, 3 a Swordfish, 4 a Jellyfish
+pub struct Fish {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    sizes: RangeInclusive<usize>,
+    // the base lines last searched per (digit, is_row_based); candidates only
+    // ever shrink, so the same base lines cannot hold a new fish
+    last_searched: HashMap<(usize, bool), Vec<(usize, usize)>>,
+}
+impl Fish {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            Fish {
+                stopwatch,
+                recorder: Recorder::new(),
+                sizes: 2..=4,
+                last_searched: HashMap::new(),
+            }
+        }
+    }
+    // only look for fish of exactly this size
+    pub fn with_size(mut self, size: usize) -> Self {
+        self.sizes = size..=size;
+        self
+    }
+    fn technique(size: usize) -> Technique {
+        match size {
+            2 => Technique::XWing,
+            3 => Technique::Swordfish,
+            _ => Technique::Jellyfish,
+        }
+    }
+    // (row, column) of the square at position index of line number line
+    fn square(is_row_based: bool, line: usize, index: usize) -> (usize, usize) {
+        match is_row_based {
+            true => (line, index),
+            false => (index, line),
+        }
+    }
+    // base lines where the digit is not placed and fits in 2..=max_size
+    // squares, with a bitmap of those squares' positions along the line
+    fn base_lines(digit: usize, max_size: usize, is_row_based: bool, grid: &Grid) -> Vec<(usize, usize)> {
+        let board_size = grid.size();
+        let mut base_lines: Vec<(usize, usize)> = vec![];
+        for line in 1..=board_size {
+            let mut positions = 0;
+            let mut is_placed = false;
+            for index in 1..=board_size {
+                let (r,c) = Fish::square(is_row_based, line, index);
+                let bitmap = grid.get_bitmap((r-1)*board_size + (c-1));
+                if bitmap & squares::bit(digit) != 0 {
+                    is_placed |= squares::number_of_bits(bitmap) == 1;
+                    positions |= squares::bit(index);
+                }
+            }
+            if !is_placed && (2..=max_size).contains(&squares::number_of_bits(positions)) {
+                base_lines.push((line, positions));
+            }
+        }
+        base_lines
+    }
+    fn find(&mut self, digit: usize, size: usize, is_row_based: bool, base_lines: &[(usize, usize)],
+            puzzle: &mut SudokuPuzzle) {
+        let board_size = puzzle.size();
+        let base_lines: Vec<(usize, usize)> = base_lines
+            .iter()
+            .filter(|(_, positions)| squares::number_of_bits(*positions) <= size)
+            .copied()
+            .collect();
+        for fish in combinations(&base_lines, size) {
+            let cover = fish.iter().fold(0, |acc, (_, positions)| acc | positions);
+            if squares::number_of_bits(cover) != size {
+                continue;
+            }
+            let lines: Vec<usize> = fish.iter().map(|(line, _)| *line).collect();
+            let cover_lines = squares::bitmap_values(cover);
+            let mut others: Vec<(usize, usize)> = vec![];
+            for cover_line in cover_lines.iter() {
+                for line in (1..=board_size).filter(|line| !lines.contains(line)) {
+                    let (r,c) = Fish::square(is_row_based, line, *cover_line);
+                    if puzzle.get_square(r,c).is_possible(digit) {
+                        others.push((r,c));
+                    }
+                }
+            }
+            if others.is_empty() {
+                continue;
+            }
+            let cells: Vec<(usize, usize)> = lines
+                .iter()
+                .flat_map(|line| cover_lines.iter().map(move |index| Fish::square(is_row_based, *line, *index)))
+                .filter(|(r,c)| puzzle.get_square(*r,*c).is_possible(digit))
+                .collect();
+            let snapshot = self.recorder.snapshot(puzzle, || others.clone());
+            others.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).eliminate(digit));
+            self.recorder.record(Fish::technique(size), None, cells, vec![digit], puzzle, snapshot);
+        }
+    }
+}
+impl Constraint for Fish {
+    fn name(&self) -> String {
+        match (*self.sizes.start(), *self.sizes.end()) {
+            (2, 2) => "FishXWing".to_string(),
+            (3, 3) => "FishSwordfish".to_string(),
+            (4, 4) => "FishJellyfish".to_string(),
+            _ => "Fish".to_string(),
+        }
+    }
+    fn forget(&mut self) {
+        self.last_searched.clear();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    // fish span the whole board; a new one can only appear for a digit the
+    // queue item's square has lost, so the digits it still has are skipped
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.stopwatch.start();
+        // a copy, the board changes as fish are found
+        let grid = puzzle.grid().clone();
+        for digit in 1..=puzzle.size() {
+            if puzzle.get_square(queue_item.row, queue_item.column).is_possible(digit) &&
+                !puzzle.get_square(queue_item.row, queue_item.column).has_single_value() {
+                continue;
+            }
+            for is_row_based in [true, false] {
+                let base_lines = Fish::base_lines(digit, *self.sizes.end(), is_row_based, &grid);
+                if self.last_searched.get(&(digit, is_row_based)) == Some(&base_lines) {
+                    continue;
+                }
+                for size in self.sizes.clone() {
+                    self.find(digit, size, is_row_based, &base_lines, puzzle);
+                }
+                self.last_searched.insert((digit, is_row_based), base_lines);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// every other square in the same row, column or box
+pub fn peers(box_size: usize, row: usize, column: usize) -> Vec<(usize, usize)> {
+    let size = box_size * box_size;
+    let mut peers: Vec<(usize, usize)> = (1..=size).filter(|c| *c != column).map(|c| (row, c)).collect();
+    peers.extend((1..=size).filter(|r| *r != row).map(|r| (r, column)));
+    peers.extend(box_util::box_peers(box_size, row, column)
+                 .into_iter()
+                 .filter(|(r,c)| *r != row && *c != column));
+    peers
+}
+
+fn sees(box_size: usize, a: (usize, usize), b: (usize, usize)) -> bool {
+    a != b && (a.0 == b.0 || a.1 == b.1 ||
+               box_util::box_number(box_size, a.0, a.1) == box_util::box_number(box_size, b.0, b.1))
+}
+
+// the squares other than ends that see every one of ends and still have digit
+fn seen_by_all(puzzle: &mut SudokuPuzzle, ends: &[(usize, usize)], digit: usize) -> Vec<(usize, usize)> {
+    let box_size = puzzle.box_size();
+    peers(box_size, ends[0].0, ends[0].1)
+        .into_iter()
+        .filter(|cell| !ends.contains(cell))
+        .filter(|cell| ends.iter().all(|end| sees(box_size, *cell, *end)))
+        .filter(|(r,c)| !puzzle.get_square(*r,*c).has_single_value() && puzzle.get_square(*r,*c).is_possible(digit))
+        .collect()
+}
+
+// the board-wide constraints below ignore the queue item and look at the whole
+// grid; this tells them whether anything changed since their last look
+struct BoardMemo {
+    last_grid: Option<Grid>,
+}
+impl BoardMemo {
+    fn new() -> Self {
+        BoardMemo { last_grid: None }
+    }
+    // remembers the board as it is now, so a scan that changes it gets to
+    // look again next time
+    fn has_changed(&mut self, puzzle: &SudokuPuzzle) -> bool {
+        let grid = puzzle.grid();
+        match self.last_grid.as_ref() == Some(grid) {
+            true => false,
+            false => {
+                match self.last_grid.as_mut() {
+                    Some(last) => last.clone_from(grid),
+                    None => self.last_grid = Some(grid.clone()),
+                }
+                true
+            }
+        }
+    }
+    fn forget(&mut self) {
+        self.last_grid = None;
+    }
+}
+
+// removes digit from targets and records the step; cells is the pattern or
+// chain that justifies it
+fn eliminate_with_step(recorder: &mut Recorder, technique: Technique, cells: Vec<(usize, usize)>,
+                       digit: usize, targets: Vec<(usize, usize)>, puzzle: &mut SudokuPuzzle) {
+    if targets.is_empty() {
+        return;
+    }
+    let snapshot = recorder.snapshot(puzzle, || targets.clone());
+    targets.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).eliminate(digit));
+    recorder.record(technique, None, cells, vec![digit], puzzle, snapshot);
+}
+
+// a bivalue pivot {x,y} seeing two bivalue pincers {x,z} and {y,z}: one of
+// the pincers is z, so z goes from every square that sees both pincers
+pub struct XYWing {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+}
+impl XYWing {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            XYWing {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+            }
+        }
+    }
+}
+impl Constraint for XYWing {
+    fn name(&self) -> String {
+        "XYWing".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
+        self.stopwatch.start();
+        let box_size = puzzle.box_size();
+        for (pr, pc) in puzzle.coordinates() {
+            if puzzle.get_square(pr,pc).count_remaining() != 2 {
+                continue;
+            }
+            let pivot = puzzle.get_square(pr,pc).get_bitmap();
+            let pincers: Vec<((usize, usize), usize)> = peers(box_size, pr, pc)
+                .into_iter()
+                .map(|(r,c)| ((r,c), puzzle.get_square(r,c).get_bitmap()))
+                .filter(|(_, bitmap)| squares::number_of_bits(*bitmap) == 2)
+                .filter(|(_, bitmap)| squares::number_of_bits(bitmap & pivot) == 1)
+                .collect();
+            for pair in combinations(&pincers, 2) {
+                let ((a, a_bitmap), (b, b_bitmap)) = (pair[0], pair[1]);
+                let z = a_bitmap & b_bitmap & !pivot;
+                // the pincers share z and cover both of the pivot's digits
+                if squares::number_of_bits(z) != 1 || (a_bitmap | b_bitmap) & pivot != pivot {
+                    continue;
+                }
+                let digit = squares::bitmap_values(z)[0];
+                let targets = seen_by_all(puzzle, &[a, b], digit);
+                eliminate_with_step(&mut self.recorder, Technique::XYWing, vec![a, (pr,pc), b], digit, targets, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// like XYWing, but the pivot also has z: {x,y,z} with pincers {x,z} and
+// {y,z}; z goes from every square that sees all three
+pub struct XYZWing {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+}
+impl XYZWing {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            XYZWing {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+            }
+        }
+    }
+}
+impl Constraint for XYZWing {
+    fn name(&self) -> String {
+        "XYZWing".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
+        self.stopwatch.start();
+        let box_size = puzzle.box_size();
+        for (pr, pc) in puzzle.coordinates() {
+            if puzzle.get_square(pr,pc).count_remaining() != 3 {
+                continue;
+            }
+            let pivot = puzzle.get_square(pr,pc).get_bitmap();
+            let pincers: Vec<((usize, usize), usize)> = peers(box_size, pr, pc)
+                .into_iter()
+                .map(|(r,c)| ((r,c), puzzle.get_square(r,c).get_bitmap()))
+                .filter(|(_, bitmap)| squares::number_of_bits(*bitmap) == 2)
+                .filter(|(_, bitmap)| bitmap & pivot == *bitmap)
+                .collect();
+            for pair in combinations(&pincers, 2) {
+                let ((a, a_bitmap), (b, b_bitmap)) = (pair[0], pair[1]);
+                let z = a_bitmap & b_bitmap;
+                if squares::number_of_bits(z) != 1 || a_bitmap | b_bitmap != pivot {
+                    continue;
+                }
+                let digit = squares::bitmap_values(z)[0];
+                let targets = seen_by_all(puzzle, &[a, (pr,pc), b], digit);
+                eliminate_with_step(&mut self.recorder, Technique::XYZWing, vec![a, (pr,pc), b], digit, targets, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// the squares linked to cell by a strong link for digit: the only other
+// square of one of its units where digit still fits
+fn strong_links(puzzle: &mut SudokuPuzzle, cell: (usize, usize), digit: usize) -> Vec<(usize, usize)> {
+    let box_size = puzzle.box_size();
+    let mut links = vec![];
+    for unit in units_of(&QueueItem::new(cell.0, cell.1), box_size) {
+        let others: Vec<(usize, usize)> = unit.cells(box_size)
+            .into_iter()
+            .filter(|other| *other != cell)
+            .filter(|(r,c)| puzzle.get_square(*r,*c).is_possible(digit))
+            .collect();
+        if others.len() == 1 && !links.contains(&others[0]) {
+            links.push(others[0]);
+        }
+    }
+    links
+}
+
+// squares where digit is still open, i.e. not placed
+fn open_squares(puzzle: &mut SudokuPuzzle, digit: usize) -> Vec<(usize, usize)> {
+    puzzle.coordinates()
+        .into_iter()
+        .filter(|(r,c)| puzzle.get_square(*r,*c).is_possible(digit) && !puzzle.get_square(*r,*c).has_single_value())
+        .collect()
+}
+
+// single-digit coloring: squares joined by strong links alternate between
+// true and false, so they split into two colors; if two squares of one color
+// see each other, that color is false, and a square that sees both colors
+// cannot hold the digit
+pub struct SimpleColoring {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+}
+impl SimpleColoring {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            SimpleColoring {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+            }
+        }
+    }
+    // the connected strong-link cluster around start, in the order found,
+    // with each square's color
+    fn cluster(puzzle: &mut SudokuPuzzle, start: (usize, usize), digit: usize) -> Vec<((usize, usize), bool)> {
+        let mut cluster = vec![(start, true)];
+        let mut next = 0;
+        while next < cluster.len() {
+            let (cell, color) = cluster[next];
+            for linked in strong_links(puzzle, cell, digit) {
+                if !cluster.iter().any(|(other, _)| *other == linked) {
+                    cluster.push((linked, !color));
+                }
+            }
+            next += 1;
+        }
+        cluster
+    }
+}
+impl Constraint for SimpleColoring {
+    fn name(&self) -> String {
+        "SimpleColoring".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
+        self.stopwatch.start();
+        let box_size = puzzle.box_size();
+        for digit in 1..=puzzle.size() {
+            let mut colored: Vec<(usize, usize)> = vec![];
+            for start in open_squares(puzzle, digit) {
+                if colored.contains(&start) {
+                    continue;
+                }
+                let cluster = SimpleColoring::cluster(puzzle, start, digit);
+                colored.extend(cluster.iter().map(|(cell, _)| *cell));
+                if cluster.len() < 3 {
+                    continue;
+                }
+                let cells: Vec<(usize, usize)> = cluster.iter().map(|(cell, _)| *cell).collect();
+                // color wrap
+                let wrapped = [true, false].iter().copied().find(|color| {
+                    let same: Vec<(usize, usize)> = cluster.iter().filter(|(_, c)| c == color).map(|(cell, _)| *cell).collect();
+                    combinations(&same, 2).iter().any(|pair| sees(box_size, pair[0], pair[1]))
+                });
+                if let Some(color) = wrapped {
+                    let targets = cluster.iter().filter(|(_, c)| *c == color).map(|(cell, _)| *cell).collect();
+                    eliminate_with_step(&mut self.recorder, Technique::SimpleColoring, cells, digit, targets, puzzle);
+                    continue;
+                }
+                // color trap
+                let targets: Vec<(usize, usize)> = open_squares(puzzle, digit)
+                    .into_iter()
+                    .filter(|cell| !cells.contains(cell))
+                    .filter(|cell| [true, false].iter().all(|color| {
+                        cluster.iter().any(|(other, c)| c == color && sees(box_size, *cell, *other))
+                    }))
+                    .collect();
+                eliminate_with_step(&mut self.recorder, Technique::SimpleColoring, cells, digit, targets, puzzle);
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// the shortest alternating chain from start found by a breadth-first search;
+// next(cell, position) lists the squares the link after position may go to
+fn shortest_chains<F>(start: (usize, usize), mut next: F) -> Vec<Vec<(usize, usize)>>
+    where F: FnMut(&[(usize, usize)]) -> Vec<(usize, usize)>
+{
+    let mut chains: Vec<Vec<(usize, usize)>> = vec![vec![start]];
+    let mut visited: Vec<((usize, usize), usize)> = vec![(start, 0)];
+    let mut next_chain = 0;
+    while next_chain < chains.len() {
+        let chain = chains[next_chain].clone();
+        for cell in next(&chain) {
+            let parity = chain.len() % 2;
+            if visited.contains(&(cell, parity)) {
+                continue;
+            }
+            visited.push((cell, parity));
+            let mut longer = chain.clone();
+            longer.push(cell);
+            chains.push(longer);
+        }
+        next_chain += 1;
+    }
+    chains
+}
+
+// single-digit chains of alternating strong and weak links that start and
+// end with a strong link: one of the two ends holds the digit, so it goes
+// from every square that sees both ends
+pub struct XChain {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+}
+impl XChain {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            XChain {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+            }
+        }
+    }
+}
+impl Constraint for XChain {
+    fn name(&self) -> String {
+        "XChain".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
+        self.stopwatch.start();
+        let box_size = puzzle.box_size();
+        for digit in 1..=puzzle.size() {
+            for start in open_squares(puzzle, digit) {
+                let open = open_squares(puzzle, digit);
+                // odd links (1st, 3rd, ...) are strong, even ones weak
+                let chains = shortest_chains(start, |chain| {
+                    let last = chain[chain.len() - 1];
+                    match chain.len() % 2 {
+                        1 => strong_links(puzzle, last, digit),
+                        _ => open.iter().filter(|cell| sees(box_size, last, **cell)).copied().collect(),
+                    }
+                });
+                // chains ending on a strong link, with at least three links
+                for chain in chains.into_iter().filter(|chain| chain.len() % 2 == 0 && chain.len() >= 4) {
+                    let end = chain[chain.len() - 1];
+                    if end == start {
+                        continue;
+                    }
+                    let targets = seen_by_all(puzzle, &[start, end], digit);
+                    eliminate_with_step(&mut self.recorder, Technique::XChain, chain, digit, targets, puzzle);
+                }
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// chains of bivalue squares, each seeing the next and sharing a digit with
+// it: if the first square is not z, the last one is, so z goes from every
+// square that sees both ends
+pub struct XYChain {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+}
+impl XYChain {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            XYChain {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+            }
+        }
+    }
+    // the digit a chain of bivalue squares forces into its last square,
+    // given the first square is not z
+    fn forced_digit(puzzle: &mut SudokuPuzzle, chain: &[(usize, usize)], z: usize) -> Option<usize> {
+        let mut value = z;
+        for (r,c) in chain {
+            let bitmap = puzzle.get_square(*r,*c).get_bitmap();
+            // squares stop being bivalue as the scan eliminates
+            if bitmap & squares::bit(value) == 0 || squares::number_of_bits(bitmap) != 2 {
+                return None;
+            }
+            value = squares::bitmap_values(bitmap & !squares::bit(value))[0];
+        }
+        Some(value)
+    }
+}
+impl Constraint for XYChain {
+    fn name(&self) -> String {
+        "XYChain".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        self.stopwatch.elapsed_millis()
+    }
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
+        self.stopwatch.start();
+        let box_size = puzzle.box_size();
+        let bivalue: Vec<(usize, usize)> = puzzle.coordinates()
+            .into_iter()
+            .filter(|(r,c)| puzzle.get_square(*r,*c).count_remaining() == 2)
+            .collect();
+        for start in bivalue.iter() {
+            for z in puzzle.get_square(start.0, start.1).values_remaining() {
+                let chains = shortest_chains(*start, |chain| {
+                    let last = chain[chain.len() - 1];
+                    // the digit last is forced to, which the next square must not be
+                    let forced = match XYChain::forced_digit(puzzle, chain, z) {
+                        Some(forced) => forced,
+                        None => return vec![],
+                    };
+                    bivalue.iter()
+                        .filter(|cell| sees(box_size, last, **cell))
+                        .filter(|(r,c)| puzzle.get_square(*r,*c).is_possible(forced))
+                        .copied()
+                        .collect()
+                });
+                for chain in chains.into_iter().filter(|chain| chain.len() >= 3) {
+                    let end = chain[chain.len() - 1];
+                    if end == *start || XYChain::forced_digit(puzzle, &chain, z) != Some(z) {
+                        continue;
+                    }
+                    let targets = seen_by_all(puzzle, &[*start, end], z);
+                    eliminate_with_step(&mut self.recorder, Technique::XYChain, chain, z, targets, puzzle);
+                }
+            }
+        }
+        self.stopwatch.stop();
+        Ok(())
+    }
+}
+
+// the uniqueness techniques below assume the puzzle has exactly one
+// solution, which the caller has to declare (Settings::is_unique); on a
+// puzzle with several they can eliminate every solution
+
+// four unsolved squares at the corners of a rectangle spanning two boxes,
+// all with candidates a and b: if they could only be a and b the two digits
+// could be swapped, giving a second solution, so something has to break the
+// pattern. Types 1-4 as on the usual solver sites
+pub struct UniqueRectangle {
+    stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+    is_enabled: bool,
+}
+impl UniqueRectangle {
+    pub fn new() -> Self {
+        {
+            let stopwatch = Stopwatch::new();
+            UniqueRectangle {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+                is_enabled: false,
+            }
+        }
+    }
+    pub fn with_enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
+    }
+    // removes bitmap from targets, recording the rectangle and its pair
+    fn eliminate(&mut self, corners: &[(usize, usize)], pair: usize, bitmap: usize,
+                 targets: Vec<(usize, usize)>, puzzle: &mut SudokuPuzzle) {
+        let targets: Vec<(usize, usize)> = targets
+            .into_iter()
+            .filter(|(r,c)| !puzzle.get_square(*r,*c).has_single_value() && puzzle.get_square(*r,*c).get_bitmap() & bitmap != 0)
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let snapshot = self.recorder.snapshot(puzzle, || targets.clone());
+        targets.iter().for_each(|(r,c)| puzzle.get_square(*r,*c).bitmap_elimination(bitmap));
+        self.recorder.record(Technique::UniqueRectangle, None, corners.to_vec(), squares::bitmap_values(pair), puzzle, snapshot);
+    }
+    // the rows, columns and boxes both squares are in
+    fn shared_units(box_size: usize, a: (usize, usize), b: (usize, usize)) -> Vec<Unit> {
+        let mut units = vec![];
+        if a.0 == b.0 {
+            units.push(Unit::Row(a.0));
+        }
+        if a.1 == b.1 {
+            units.push(Unit::Column(a.1));
+        }
+        if Unit::containing_box(box_size, a.0, a.1) == Unit::containing_box(box_size, b.0, b.1) {
+            units.push(Unit::containing_box(box_size, a.0, a.1));
+        }
+        units
+    }
+    // the two floor squares hold exactly the pair, the two roof squares have
+    // extra candidates and lie in the same row or column
+    fn two_floors(&mut self, corners: &[(usize, usize)], pair: usize, roof: [(usize, usize); 2],
+                  puzzle: &mut SudokuPuzzle) {
+        let box_size = puzzle.box_size();
+        let extras: Vec<usize> = roof.iter().map(|(r,c)| puzzle.get_square(*r,*c).get_bitmap() & !pair).collect();
+        // type 2: both roof squares have the same one extra candidate, so one
+        // of them holds it
+        if extras[0] == extras[1] && squares::number_of_bits(extras[0]) == 1 {
+            let digit = squares::bitmap_values(extras[0])[0];
+            let targets = seen_by_all(puzzle, &roof, digit);
+            self.eliminate(corners, pair, extras[0], targets, puzzle);
+        }
+        for unit in UniqueRectangle::shared_units(box_size, roof[0], roof[1]) {
+            let others: Vec<(usize, usize)> = unit.cells(box_size)
+                .into_iter()
+                .filter(|cell| !roof.contains(cell))
+                .filter(|(r,c)| !puzzle.get_square(*r,*c).has_single_value())
+                .collect();
+            // type 3: the roof's extras act as one more square, forming a
+            // naked subset with n other squares of the unit
+            let virtual_square = extras[0] | extras[1];
+            for n in 1..=3 {
+                for subset in combinations(&others, n) {
+                    let union = subset.iter().fold(virtual_square, |acc, (r,c)| acc | puzzle.get_square(*r,*c).get_bitmap());
+                    if squares::number_of_bits(union) != n + 1 {
+                        continue;
+                    }
+                    let targets = others.iter().filter(|cell| !subset.contains(cell)).copied().collect();
+                    self.eliminate(corners, pair, union, targets, puzzle);
+                }
+            }
+            // type 4: one of the pair only fits in the roof within the unit,
+            // so the roof cannot also hold the other; a solved square holding
+            // the digit means it is not in the roof at all
+            for digit in squares::bitmap_values(pair) {
+                let is_locked = unit.cells(box_size)
+                    .into_iter()
+                    .filter(|cell| !roof.contains(cell))
+                    .all(|(r,c)| !puzzle.get_square(r,c).is_possible(digit));
+                if is_locked {
+                    self.eliminate(corners, pair, pair & !squares::bit(digit), roof.to_vec(), puzzle);
+                }
             }
         }
     }
-    fn _process_deprecated(&self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
-        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
-            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
-
-            [1,1,1,2,2,2,3,3,3].iter().zip([1,2,3,1,2,3,1,2,3].iter())
-                .map(|(i,j)| { (i+((box_util::index_to_box(queue_item.row)-1)*3),
-                                j+((box_util::index_to_box(queue_item.column)-1)*3))})
-                .filter(|(i,j)| *i!=queue_item.row || *j!=queue_item.column)
-                .for_each(|(i,j)| {
-                    if puzzle.get_square(i,j).get_bitmap() == bitmap {
-                        // bingo!
-                        [1,1,1,2,2,2,3,3,3].iter().zip([1,2,3,1,2,3,1,2,3].iter())
-                            .map(|(i2,j2)| { (i2+((box_util::index_to_box(queue_item.row)-1)*3),
-                                              j2+((box_util::index_to_box(queue_item.column)-1)*3))})
-                            .filter(|(i2,j2)| *i2!=queue_item.row || *j2!=queue_item.column)
-                            .filter(|(i2,j2)| *i2!=i || *j2!=j)
-                            .for_each(|(i,j)| puzzle.get_square(i,j).bitmap_elimination(bitmap));
+    fn search_rectangle(&mut self, corners: [(usize, usize); 4], puzzle: &mut SudokuPuzzle) {
+        let bitmaps: Vec<usize> = corners.iter().map(|(r,c)| puzzle.get_square(*r,*c).get_bitmap()).collect();
+        if bitmaps.iter().any(|bitmap| squares::number_of_bits(*bitmap) < 2) {
+            return;
+        }
+        let common = bitmaps.iter().fold(!0, |acc, bitmap| acc & bitmap);
+        for digits in combinations(&squares::bitmap_values(common), 2) {
+            let pair = squares::bit(digits[0]) | squares::bit(digits[1]);
+            // earlier eliminations may have changed the corners
+            let floors: Vec<usize> = (0..4).filter(|i| {
+                let (r,c) = corners[*i];
+                puzzle.get_square(r,c).get_bitmap() == pair
+            }).collect();
+            match floors.len() {
+                // type 1: the one corner with extras cannot be a or b
+                3 => {
+                    let roof = (0..4).find(|i| !floors.contains(i)).unwrap();
+                    self.eliminate(&corners, pair, pair, vec![corners[roof]], puzzle);
+                },
+                2 => {
+                    let roof: Vec<(usize, usize)> = (0..4).filter(|i| !floors.contains(i)).map(|i| corners[i]).collect();
+                    // diagonal floors have no roof in a line
+                    if roof[0].0 == roof[1].0 || roof[0].1 == roof[1].1 {
+                        self.two_floors(&corners, pair, [roof[0], roof[1]], puzzle);
                     }
-                });
+                },
+                _ => (),
+            }
         }
-        Ok(())
     }
 }
-impl Constraint for DoubleDoubleBox {
+impl Constraint for UniqueRectangle {
     fn name(&self) -> String {
-        "DoubleDoubleBox".to_string()
+        "UniqueRectangle".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
     }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
-    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.is_enabled || !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
         self.stopwatch.start();
-        let row = queue_item.row;
-        let col = queue_item.column;
-        if puzzle.get_square(row,col).count_remaining() == 2 {
-            let bitmap = puzzle.get_square(row,col).get_bitmap();
-            box_util::box8(row,col).iter().for_each(|(i,j)| {
-                if puzzle.get_square(*i,*j).get_bitmap() == bitmap {
-                    // bingo!
-                    box_util::box9(row,col).iter()
-                        .filter(|(i2,j2)| *i2!=row || *j2!=col)
-                        .filter(|(i2,j2)| *i2!=*i || *j2!=*j)
-                        .for_each(|(i,j)| puzzle.get_square(*i,*j).bitmap_elimination(bitmap));
+        let box_size = puzzle.box_size();
+        let size = puzzle.size();
+        // every type has two floor squares in a row or column, so start from
+        // those rather than trying every rectangle
+        let bivalues: Vec<(usize, usize)> = puzzle.coordinates()
+            .into_iter()
+            .filter(|(r,c)| puzzle.get_square(*r,*c).count_remaining() == 2)
+            .collect();
+        for floors in combinations(&bivalues, 2) {
+            let ((r1,c1), (r2,c2)) = (floors[0], floors[1]);
+            if puzzle.get_square(r1,c1).get_bitmap() != puzzle.get_square(r2,c2).get_bitmap() {
+                continue;
+            }
+            let rectangles: Vec<([usize; 2], [usize; 2])> = match (r1 == r2, c1 == c2) {
+                (true, false) => (1..=size).filter(|r| *r != r1).map(|r| ([r1.min(r), r1.max(r)], [c1, c2])).collect(),
+                (false, true) => (1..=size).filter(|c| *c != c1).map(|c| ([r1, r2], [c1.min(c), c1.max(c)])).collect(),
+                _ => continue,
+            };
+            for (rows, columns) in rectangles {
+                // exactly two boxes: the rows in one band or the columns in
+                // one stack, but not both
+                let same_band = box_util::index_to_box(box_size, rows[0]) == box_util::index_to_box(box_size, rows[1]);
+                let same_stack = box_util::index_to_box(box_size, columns[0]) == box_util::index_to_box(box_size, columns[1]);
+                if same_band == same_stack {
+                    continue;
                 }
-            });
+                let corners = [(rows[0], columns[0]), (rows[0], columns[1]), (rows[1], columns[0]), (rows[1], columns[1])];
+                self.search_rectangle(corners, puzzle);
+            }
         }
         self.stopwatch.stop();
         Ok(())
     }
 }
 
-pub struct DoubleDoubleColumn {
+// bivalue universal grave + 1: when every unsolved square but one has two
+// candidates, and every candidate appears exactly twice in each unit except
+// in that square, which has three, the digit appearing three times in its
+// units is the one that stops the grid from having two solutions
+pub struct BugPlusOne {
     stopwatch: Stopwatch,
+    recorder: Recorder,
+    is_enabled: bool,
 }
-impl DoubleDoubleColumn {
+impl BugPlusOne {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
-            DoubleDoubleColumn {
-                stopwatch
+            BugPlusOne {
+                stopwatch,
+                recorder: Recorder::new(),
+                is_enabled: false,
             }
         }
     }
+    pub fn with_enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
+    }
+    // how often digit is a candidate of an unsolved square of the unit
+    fn count(digit: usize, unit: Unit, puzzle: &mut SudokuPuzzle) -> usize {
+        unit.cells(puzzle.box_size())
+            .into_iter()
+            .filter(|(r,c)| !puzzle.get_square(*r,*c).has_single_value() && puzzle.get_square(*r,*c).is_possible(digit))
+            .count()
+    }
 }
-impl Constraint for DoubleDoubleColumn {
+impl Constraint for BugPlusOne {
     fn name(&self) -> String {
-        "DoubleDoubleColumn".to_string()
+        "BugPlusOne".to_string()
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
     }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
-    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.is_enabled {
+            return Ok(());
+        }
         self.stopwatch.start();
-        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
-            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
-            (1..=9)
-                .filter(|r| *r != queue_item.row)
-                .for_each(|r| {
-                    if puzzle.get_square(r, queue_item.column).get_bitmap() == bitmap {
-                        // bingo!
-                        (1..=9).filter(|r2| *r2 != queue_item.row && *r2 != r)
-                            .for_each(|r| puzzle.get_square(r, queue_item.column).bitmap_elimination(bitmap));
+        let mut plus_one = None;
+        for (r,c) in puzzle.coordinates() {
+            match puzzle.get_square(r,c).count_remaining() {
+                1 | 2 => (),
+                3 if plus_one.is_none() => plus_one = Some((r,c)),
+                _ => {
+                    self.stopwatch.stop();
+                    return Ok(());
+                },
+            }
+        }
+        if let Some((row, col)) = plus_one {
+            let box_size = puzzle.box_size();
+            let units = units_of(&QueueItem::new(row, col), box_size);
+            let three_times: Vec<usize> = puzzle.get_square(row, col).values_remaining()
+                .into_iter()
+                .filter(|digit| units.iter().all(|unit| BugPlusOne::count(*digit, *unit, puzzle) == 3))
+                .collect();
+            // without that digit in the +1 square, every digit has to appear
+            // twice (or not at all) in every unit, and no placed digit may
+            // still be a candidate of a peer for the counts to mean anything
+            if three_times.len() == 1 {
+                let digit = three_times[0];
+                let is_grave = Unit::all(box_size).into_iter().all(|unit| {
+                    let cells = unit.cells(box_size);
+                    let (mut placed, mut open) = (0, 0);
+                    for (r,c) in cells.iter() {
+                        let square = puzzle.get_square(*r,*c);
+                        match square.has_single_value() {
+                            true => placed |= square.get_bitmap(),
+                            false => open |= square.get_bitmap(),
+                        }
                     }
+                    let extra = match cells.contains(&(row, col)) {
+                        true => squares::bit(digit),
+                        false => 0,
+                    };
+                    placed & open == 0 && squares::bitmap_values(open).into_iter().all(|d| {
+                        let count = BugPlusOne::count(d, unit, puzzle);
+                        count == 2 || (extra == squares::bit(d) && count == 3)
+                    })
                 });
+                if is_grave {
+                    let snapshot = self.recorder.snapshot(puzzle, || vec![(row, col)]);
+                    puzzle.get_square(row, col).select(digit);
+                    self.recorder.record(Technique::BugPlusOne, None, vec![(row, col)], vec![digit], puzzle, snapshot);
+                }
+            }
         }
         self.stopwatch.stop();
         Ok(())
     }
 }
 
-pub struct DoubleDoubleRow {
+// what a lookahead from one assumed digit ended with; chain is the digits it
+// placed in order, starting with the assumption
+struct Lookahead {
+    chain: Vec<(usize, usize, usize)>,
+    contradiction: Option<ConstraintViolation>,
+    puzzle: SudokuPuzzle,
+}
+
+// nishio / forcing chains: assumes each candidate of a bivalue square in turn
+// and propagates on a copy of the board for a few rounds, without guessing.
+// A candidate that leads to a contradiction goes, and a digit placed by both
+// assumptions has to be right. Somewhere between the logic constraints and a
+// full search, so it is only in the grader and --constraints
+pub struct ForcingChains {
     stopwatch: Stopwatch,
+    recorder: Recorder,
+    memo: BoardMemo,
+    max_rounds: usize,
+    // the lookahead runs Pipeline::singles
+    settings: Settings,
 }
-impl DoubleDoubleRow {
+impl ForcingChains {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
-            DoubleDoubleRow {
-                stopwatch
+            let mut settings = Settings::new();
+            settings.pipeline = Pipeline::singles();
+            ForcingChains {
+                stopwatch,
+                recorder: Recorder::new(),
+                memo: BoardMemo::new(),
+                max_rounds: 8,
+                settings,
+            }
+        }
+    }
+    // how many propagation rounds each lookahead gets
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+    // places digit at (row, col) on a copy of the puzzle and propagates with
+    // constraints (the lookahead pipeline, forgetting what the last
+    // lookahead taught them) until nothing changes, something breaks or
+    // max_rounds is up. As in Solver::propagate, each round only goes over
+    // the squares that changed and the units they are in
+    fn look_ahead(&self, row: usize, col: usize, digit: usize, puzzle: &SudokuPuzzle,
+                  constraints: &mut [Box<dyn Constraint>]) -> Lookahead {
+        constraints.iter_mut().for_each(|constraint| constraint.forget());
+        let mut puzzle = SudokuPuzzle::clone_from(puzzle);
+        let mut seen = puzzle.grid().clone();
+        let mut chain = vec![];
+        puzzle.get_square(row, col).select(digit);
+        let (mut queue, _) = ForcingChains::take_changes(&mut puzzle, &mut seen, &mut chain);
+
+        let mut contradiction = None;
+        for _ in 0..self.max_rounds {
+            if queue.is_empty() {
+                break;
+            }
+            let result = ForcingChains::run_round(&queue, &mut puzzle, constraints);
+            let (next, emptied) = ForcingChains::take_changes(&mut puzzle, &mut seen, &mut chain);
+            queue = next;
+            if let Err(e) = result.and(emptied) {
+                contradiction = Some(e);
+                break;
+            }
+        }
+        Lookahead { chain, contradiction, puzzle }
+    }
+    // the queue items, in board order, and their units through the
+    // constraints
+    fn run_round(queue: &[QueueItem], puzzle: &mut SudokuPuzzle, constraints: &mut [Box<dyn Constraint>])
+                 -> Result<(), ConstraintViolation> {
+        let box_size = puzzle.box_size();
+        let mut pending_units = vec![0; 3 * puzzle.size()];
+        for qitem in queue {
+            for constraint in constraints.iter_mut().filter(|constraint| !constraint.is_unit_based()) {
+                constraint.process(qitem, puzzle)?;
+            }
+            for unit in units_of(qitem, box_size) {
+                pending_units[unit.index(box_size)] |= qitem.removed;
+            }
+        }
+        for unit in Unit::all(box_size) {
+            let removed = pending_units[unit.index(box_size)];
+            if removed == 0 {
+                continue;
+            }
+            for constraint in constraints.iter_mut().filter(|constraint| constraint.is_unit_based()) {
+                constraint.process_unit(unit, removed, puzzle)?;
             }
         }
+        Ok(())
+    }
+    // the squares that changed since seen, in board order, queued with what
+    // they lost; the ones that got down to one candidate join the chain.
+    // Constraints only look at the squares they are given, so this also
+    // catches a square emptied elsewhere
+    fn take_changes(puzzle: &mut SudokuPuzzle, seen: &mut Grid, chain: &mut Vec<(usize, usize, usize)>)
+                    -> (Vec<QueueItem>, Result<(), ConstraintViolation>) {
+        let size = puzzle.size();
+        let mut touched = puzzle.take_touched();
+        touched.sort_unstable();
+        let mut queue = vec![];
+        let mut emptied = Ok(());
+        for index in touched {
+            if !puzzle.is_dirty(index) {
+                continue;
+            }
+            puzzle.clean(index);
+            let (row, column) = (index / size + 1, index % size + 1);
+            let bitmap = puzzle.grid().get_bitmap(index);
+            let removed = seen.get_bitmap(index) & !bitmap;
+            if squares::number_of_bits(seen.get_bitmap(index)) > 1 && squares::number_of_bits(bitmap) == 1 {
+                chain.push((row, column, puzzle.grid().get_single_value(index).unwrap()));
+            }
+            if bitmap == 0 && emptied.is_ok() {
+                emptied = Err(ConstraintViolation::NoCandidates { row, column });
+            }
+            seen.restore(index, bitmap);
+            queue.push(QueueItem { row, column, removed });
+        }
+        (queue, emptied)
+    }
+    // e.g. "r1c1=3 -> r1c5=7 -> row 2 has no square left for 4"
+    fn describe(chain: &[(usize, usize, usize)], end: Option<&ConstraintViolation>) -> String {
+        let mut links: Vec<String> = chain.iter().map(|(r,c,d)| format!("r{}c{}={}", r, c, d)).collect();
+        if let Some(e) = end {
+            links.push(e.to_string());
+        }
+        links.join(" -> ")
+    }
+    // the chain up to and including the placement at (row, col)
+    fn chain_to(chain: &[(usize, usize, usize)], row: usize, col: usize) -> &[(usize, usize, usize)] {
+        match chain.iter().position(|(r,c,_)| (*r,*c) == (row, col)) {
+            Some(index) => &chain[..=index],
+            None => chain,
+        }
+    }
+    // tries both candidates of the bivalue square at (row, col); true if the
+    // puzzle changed
+    fn search_square(&mut self, row: usize, col: usize, puzzle: &mut SudokuPuzzle,
+                     constraints: &mut [Box<dyn Constraint>]) -> Result<bool, ConstraintViolation> {
+        let digits = puzzle.get_square(row, col).values_remaining();
+        let branches: Vec<Lookahead> = digits.iter().map(|digit| self.look_ahead(row, col, *digit, puzzle, constraints)).collect();
+        match (&branches[0].contradiction, &branches[1].contradiction) {
+            // neither candidate works, so neither does the puzzle
+            (Some(e), Some(_)) => Err(e.clone()),
+            (Some(_), None) | (None, Some(_)) => {
+                let (wrong, branch) = match branches[0].contradiction.is_some() {
+                    true => (digits[0], &branches[0]),
+                    false => (digits[1], &branches[1]),
+                };
+                let chain = vec![ForcingChains::describe(&branch.chain, branch.contradiction.as_ref())];
+                let snapshot = self.recorder.snapshot(puzzle, || vec![(row, col)]);
+                puzzle.get_square(row, col).eliminate(wrong);
+                self.recorder.record_with_chain(Technique::ForcingChain, None, vec![(row, col)], vec![wrong], chain, puzzle, snapshot);
+                Ok(true)
+            },
+            (None, None) => {
+                let other = &branches[1].puzzle;
+                let forced: Vec<(usize, usize, usize)> = branches[0].chain
+                    .iter()
+                    .filter(|(r,c,d)| other.grid().get_bitmap((r-1)*other.size() + (c-1)) == squares::bit(*d))
+                    .copied()
+                    .collect();
+                if forced.is_empty() {
+                    return Ok(false);
+                }
+                // one step per forced digit, each with both chains leading to it
+                for (r,c,d) in forced {
+                    let chain = branches
+                        .iter()
+                        .map(|branch| ForcingChains::describe(ForcingChains::chain_to(&branch.chain, r, c), None))
+                        .collect();
+                    let snapshot = self.recorder.snapshot(puzzle, || vec![(r, c)]);
+                    puzzle.get_square(r,c).select(d);
+                    self.recorder.record_with_chain(Technique::ForcingChain, None, vec![(row, col), (r, c)], digits.clone(), chain, puzzle, snapshot);
+                }
+                Ok(true)
+            },
+        }
     }
 }
-impl Constraint for DoubleDoubleRow {
+impl Constraint for ForcingChains {
     fn name(&self) -> String {
-        "DoubleDoubleRow".to_string()
+        "ForcingChains".to_string()
+    }
+    fn forget(&mut self) {
+        self.memo.forget();
+    }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
     }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
-    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+    fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        if !self.memo.has_changed(puzzle) {
+            return Ok(());
+        }
         self.stopwatch.start();
-        if puzzle.get_square(queue_item.row,queue_item.column).count_remaining() == 2 {
-            let bitmap = puzzle.get_square(queue_item.row,queue_item.column).get_bitmap();
-            (1..=9)
-                .filter(|c| *c != queue_item.column)
-                .for_each(|c| {
-                    if puzzle.get_square(queue_item.row, c).get_bitmap() == bitmap {
-                        // bingo!
-                        (1..=9).filter(|c2| *c2 != queue_item.column && *c2 != c)
-                            .for_each(|c| puzzle.get_square(queue_item.row, c).bitmap_elimination(bitmap));
-                    }
-                });
+        let bivalues: Vec<(usize, usize)> = puzzle.coordinates()
+            .into_iter()
+            .filter(|(r,c)| puzzle.get_square(*r,*c).count_remaining() == 2)
+            .collect();
+        // built once for every lookahead of this pass, see look_ahead
+        let mut constraints = self.settings.pipeline.build(&self.settings);
+        // the lookaheads are the expensive part, so stop at the first square
+        // that gives something and let the cheaper constraints go first again
+        for (r,c) in bivalues {
+            match self.search_square(r, c, puzzle, &mut constraints) {
+                Ok(false) => continue,
+                Ok(true) => break,
+                Err(e) => {
+                    self.stopwatch.stop();
+                    return Err(e);
+                },
+            }
         }
         self.stopwatch.stop();
         Ok(())
@@ -184,7 +1884,7 @@ impl Constraint for AllCannotBeEliminated {
         let col = queue_item.column;
         self.stopwatch.stop();
         match puzzle.get_square(row,col).count_remaining() {
-            0 => Err(ConstraintViolation{msg: "Contradiction".to_string()}),
+            0 => Err(ConstraintViolation::NoCandidates { row, column: col }),
             _ => Ok(()),
         }
     }
@@ -193,8 +1893,10 @@ impl Constraint for AllCannotBeEliminated {
 pub struct ProcessOfElimination {
     stopwatch: Stopwatch,
     stopwatch2: Stopwatch,
-    map: HashMap<(usize, usize, usize, usize, usize, usize, usize, usize, usize, usize), bool>,
+    map: HashMap<UnitKey, bool>,
     is_memoization_enabled: bool,
+    is_enabled: bool,
+    recorder: Recorder,
 }
 impl ProcessOfElimination {
     pub fn new() -> Self {
@@ -206,13 +1908,19 @@ impl ProcessOfElimination {
                 stopwatch2,
                 map: HashMap::new(),
                 is_memoization_enabled: true,
+                is_enabled: config::IS_POE_ENABLED,
+                recorder: Recorder::new(),
             }
         }
     }
-    fn already_processed(&self, key: (usize, usize, usize, usize, usize, usize, usize, usize, usize, usize)) -> bool {
-        self.map.contains_key(&key)
+    pub fn with_enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
     }
-    fn set_already_processed(&mut self, key: (usize, usize, usize, usize, usize, usize, usize, usize, usize, usize)) {
+    fn already_processed(&self, key: &UnitKey) -> bool {
+        self.map.contains_key(key)
+    }
+    fn set_already_processed(&mut self, key: UnitKey) {
         self.map.insert(key, true);
     }
 }
@@ -220,12 +1928,21 @@ impl Constraint for ProcessOfElimination {
     fn elapsed_sub_millis(&self) -> Option<u128> {
         Some(self.stopwatch2.elapsed_millis())
     }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
     fn name(&self) -> String {
         match self.is_memoization_enabled {
             true => "ProcessOfEliminationWithMemoization".to_string(),
             false => "ProcessOfElimination".to_string(),
         }
     }
+    fn forget(&mut self) {
+        self.map.clear();
+    }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
@@ -233,7 +1950,7 @@ impl Constraint for ProcessOfElimination {
                -> Result<(), ConstraintViolation>
     {
 
-        if !config::IS_POE_ENABLED {
+        if !self.is_enabled {
             return Ok(());
         }
 
@@ -241,48 +1958,47 @@ impl Constraint for ProcessOfElimination {
 
         //self.stopwatch2.start();
 
-        let function_list = vec![
-            // row/column/box squares 9 follow this format: f(queue_item) => Vec<(i,j)>
-            Box::new(|qitem: &QueueItem| {
-                // row squares 9
+        let box_size = puzzle.box_size();
+        let size = puzzle.size();
+        let full_bitmap = squares::full_bitmap(size);
+
+        let function_list = [
+            // row/column/box squares follow this format: f(queue_item) => Vec<(i,j)>
+            Box::new(move |qitem: &QueueItem| {
+                // row squares
                 //vec![(1 as usize,1 as usize)]})
                 //(1..=9).map(|c| (queue_item.row, c as usize)).collect()})
-                (1..=9).map(|c| (qitem.row, c as usize)).collect()})
+                (1..=size).map(|c| (qitem.row, c)).collect()})
                 as Box<dyn Fn(&QueueItem) -> Vec<(usize, usize)>>,
-            Box::new(|qitem: &QueueItem| {
-                // column squares 9
-                (1..=9).map(|r| (r as usize, qitem.column)).collect()})
+            Box::new(move |qitem: &QueueItem| {
+                // column squares
+                (1..=size).map(|r| (r, qitem.column)).collect()})
                 as Box<dyn Fn(&QueueItem) -> Vec<(usize, usize)>>,
-            Box::new(|qitem: &QueueItem| {
-                // box squares 9
-                let row_box = box_util::index_to_box(qitem.row);
-                let col_box = box_util::index_to_box(qitem.column);
-                [1,1,1,2,2,2,3,3,3]
-                    .iter()
-                    .zip([1,2,3,1,2,3,1,2,3].iter())
-                    .map(|(i,j)| {
-                        let row_offset = (row_box - 1) * 3;
-                        let col_offset = (col_box - 1) * 3;
-                        (i+row_offset, j+col_offset)
-                    })
-                    .collect()})
+            Box::new(move |qitem: &QueueItem| {
+                // box squares
+                box_util::box_cells(box_size, qitem.row, qitem.column)})
                 as Box<dyn Fn(&QueueItem) -> Vec<(usize, usize)>>,
         ];
 
         //self.stopwatch2.stop();
         //self.stopwatch2.start();
 
-        let mut function_list_index = 0;
+        let units = units_of(queue_item, box_size);
+
+        // each closure runs against its own unit; a memo hit returns early, so
+        // the unit can't be tracked with a counter bumped at the end
         let return_early = function_list
             .iter()
-            .try_fold((), |_acc, f| {
+            .zip(units.iter())
+            .enumerate()
+            .try_fold((), |_acc, (kind, (f, &unit))| {
                 // self.stopwatch2.start(); // 44%
 
                 // this is an expensive constraint
                 // under what heuristics would it make sense to continue?
                 //
                 // summary:
-                // 1. get all 9 bitmaps for the row (or column or box)
+                // 1. get all the bitmaps for the row (or column or box)
                 // 2. calculate (bitwise) whether all values are:
                 //    set ever
                 //    set an odd number of times
@@ -297,15 +2013,11 @@ impl Constraint for ProcessOfElimination {
 
                 // memoization
                 // self.stopwatch2.start(); // 0%
-                let unique_key = match function_list_index {
+                let unique_key = match kind {
                     0 => queue_item.row,
-                    1 => 10 + queue_item.column,
-                    2 => {
-                        let row_box_number = box_util::index_to_box(queue_item.row);
-                        let col_box_number = box_util::index_to_box(queue_item.column);
-                        100 + (10 * row_box_number) + col_box_number
-                    },
-                    _ => panic!("unexpected value for function_list_index!!"),
+                    1 => 100 + queue_item.column,
+                    2 => 200 + box_util::box_number(box_size, queue_item.row, queue_item.column),
+                    _ => panic!("unexpected unit kind!!"),
                 };
                 // self.stopwatch2.stop(); // 0%
 
@@ -336,24 +2048,11 @@ impl Constraint for ProcessOfElimination {
                 //self.stopwatch2.stop(); // 3%
 
                 // from bitmaps, get already selected values
-                let mut already_selected: Vec<bool> = vec![false; 9];
+                let mut already_selected: Vec<bool> = vec![false; size];
                 bitmaps
                     .iter()
-                    .filter(|&bitmap| squares::DerivedSquare::number_of_bits(*bitmap) == 1)
-                    .for_each(|bitmap| {
-                        match bitmap {
-                            1 => already_selected[0] = true,
-                            2 => already_selected[1] = true,
-                            4 => already_selected[2] = true,
-                            8 => already_selected[3] = true,
-                            16 => already_selected[4] = true,
-                            32 => already_selected[5] = true,
-                            64 => already_selected[6] = true,
-                            128 => already_selected[7] = true,
-                            256 => already_selected[8] = true,
-                            _ => panic!("This should not happen"),
-                        }
-                    });
+                    .filter(|&bitmap| squares::number_of_bits(*bitmap) == 1)
+                    .for_each(|bitmap| already_selected[bitmap.trailing_zeros() as usize] = true);
 
                 // // self.stopwatch2.start(); // 4%
                 // let mut already_selected: Vec<bool> = vec![false; 9];
@@ -367,19 +2066,9 @@ impl Constraint for ProcessOfElimination {
                 //     });
                 // // self.stopwatch2.stop(); // 4%
 
-                let already_processed_key = (
-                    unique_key,
-                    bitmaps[0],
-                    bitmaps[1],
-                    bitmaps[2],
-                    bitmaps[3],
-                    bitmaps[4],
-                    bitmaps[5],
-                    bitmaps[6],
-                    bitmaps[7],
-                    bitmaps[8],
-                );
-                if self.is_memoization_enabled && self.already_processed(already_processed_key) {
+                let mut already_processed_key: UnitKey = vec![unique_key];
+                already_processed_key.extend(bitmaps.iter());
+                if self.is_memoization_enabled && self.already_processed(&already_processed_key) {
                     //self.stopwatch2.stop();
                     return Ok(());
                 }
@@ -389,80 +2078,87 @@ impl Constraint for ProcessOfElimination {
                 bitmaps
                     .iter()
                     .for_each(|bitmap| {
-                        set_ever = set_ever | bitmap;
-                        set_odd = set_odd ^ bitmap;
-                        set_two_plus_times = set_two_plus_times | (set_ever & (!set_odd & 511));
+                        set_ever |= bitmap;
+                        set_odd ^= bitmap;
+                        set_two_plus_times |= set_ever & (!set_odd & full_bitmap);
                     });
                 //self.stopwatch2.stop(); // 0%
 
-                if set_ever != 511 {
+                if set_ever != full_bitmap {
                     //self.stopwatch2.stop();
+                    let digit = (1..=size)
+                        .find(|digit| set_ever & squares::bit(*digit) == 0)
+                        .unwrap();
                     return Err::<(),ConstraintViolation>
-                        (ConstraintViolation{msg: "Contradiction".to_string()});
+                        (ConstraintViolation::DigitMissing { unit, digit });
                 }
 
-                let set_exactly_once = set_odd & (511 & !set_two_plus_times);
+                let set_exactly_once = set_odd & (full_bitmap & !set_two_plus_times);
 
                 //self.stopwatch2.stop(); // 44%
                 //self.stopwatch2.start(); // 75% -> 3% -> 10%
 
                 let mut iterator_error: Option<ConstraintViolation> = None;
-                // have to filter out the squares which are InitialSquares!
+                // have to filter out the squares which are givens!
                 // filter out values which are "set exactly once" only because
                 // a square has it already selected!
-                (1..=9)
-                    .map(|i| i as usize)
+                (1..=size)
                     .filter(|possible_value|
                             !already_selected[possible_value-1])
                     .filter(|possible_value|
-                            squares::get_bitmap_values()[*possible_value] & set_exactly_once != 0)
+                            squares::bit(*possible_value) & set_exactly_once != 0)
                     .for_each(|possible_value| {
                         //self.stopwatch2.start();
                         let mut found = false;
                         let mut found_two_plus = false;
+                        let mut found_at: Vec<(usize, usize)> = vec![];
+                        let snapshot = self.recorder.snapshot(puzzle, || f(queue_item));
                         f(queue_item)
                             .iter()
                             .for_each(|(i,j)| {
-                                let s = puzzle.get_square(*i,*j);
+                                let mut s = puzzle.get_square(*i,*j);
                                 if s.is_possible(possible_value) {
                                     found_two_plus = found;
                                     found = true;
                                     s.select(possible_value);
+                                    found_at.push((*i,*j));
                                 }
                             });
+                        // where the digit turned up a second time, if it did
+                        let second = found_at.get(1).copied();
+                        self.recorder.record(Technique::HiddenSingle, Some(unit),
+                                             found_at, vec![possible_value], puzzle, snapshot);
                         if !found {
                             iterator_error =
-                                Some(ConstraintViolation{msg:"should be found".to_string()});
+                                Some(ConstraintViolation::DigitMissing {
+                                    unit,
+                                    digit: possible_value,
+                                });
                         }
                         if found_two_plus {
+                            // the square in conflict, not the queue item
+                            let (row, column) = second.unwrap();
                             iterator_error =
-                                Some(ConstraintViolation
-                                     {msg:"should not be found more than once!".to_string()});
+                                Some(ConstraintViolation::DuplicateDigit {
+                                    unit,
+                                    digit: possible_value,
+                                    row,
+                                    column,
+                                });
                         }
                         //self.stopwatch2.stop();
                     });
                 //self.stopwatch2.stop(); // 75% -> 3% -> 10%
-                match iterator_error {
-                    Some(e) => {
-                        return Err(e);
-                    }
-                    _ => ()
+                if let Some(e) = iterator_error {
+                    return Err(e);
                 }
-                function_list_index = function_list_index + 1;
                 self.set_already_processed(already_processed_key);
                 Ok(())
             });
 
         self.stopwatch.stop();
         //self.stopwatch2.stop();
-        match return_early {
-            Ok(_) => {
-                Ok(())
-            },
-            Err(_) => {
-                Err(ConstraintViolation{msg: "Contradiction".to_string()})
-            }
-        }
+        return_early
 
     }
     // e.g. in 4-sudoku, you have (23) (24) (1234) (34), you would select 1
@@ -488,13 +2184,15 @@ impl Constraint for ProcessOfElimination {
 
 pub struct NoRowDuplicates {
     stopwatch: Stopwatch,
+    recorder: Recorder,
 }
 impl NoRowDuplicates {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
             NoRowDuplicates {
-                stopwatch
+                stopwatch,
+                recorder: Recorder::new(),
             }
         }
     }
@@ -503,6 +2201,12 @@ impl Constraint for NoRowDuplicates {
     fn name(&self) -> String {
         "NoRowDuplicates".to_string()
     }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
@@ -512,9 +2216,13 @@ impl Constraint for NoRowDuplicates {
         let col = queue_item.column;
         if puzzle.get_square(row,col).has_single_value() {
             let bitmap = puzzle.get_square(row,col).get_bitmap();
-            (1..10)
+            let size = puzzle.size();
+            let snapshot = self.recorder.snapshot(puzzle, || (1..=size).filter(|c| *c != col).map(|c| (row,c)).collect());
+            (1..=size)
                 .filter(|c| *c != col)
                 .for_each(|c| puzzle.get_square(row,c).bitmap_elimination(bitmap));
+            let value = puzzle.get_square(row,col).get_single_value();
+            self.recorder.record(Technique::NakedSingle, Some(Unit::Row(row)), vec![(row,col)], vec![value], puzzle, snapshot);
         }
         self.stopwatch.stop();
         Ok(())
@@ -523,13 +2231,15 @@ impl Constraint for NoRowDuplicates {
 
 pub struct NoColumnDuplicates {
     stopwatch: Stopwatch,
+    recorder: Recorder,
 }
 impl NoColumnDuplicates {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
             NoColumnDuplicates {
-                stopwatch
+                stopwatch,
+                recorder: Recorder::new(),
             }
         }
     }
@@ -538,6 +2248,12 @@ impl Constraint for NoColumnDuplicates {
     fn name(&self) -> String {
         "NoColumnDuplicates".to_string()
     }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
@@ -547,72 +2263,83 @@ impl Constraint for NoColumnDuplicates {
         let col = queue_item.column;
         if puzzle.get_square(row,col).has_single_value() {
             let bitmap = puzzle.get_square(row,col).get_bitmap();
-            (1..10)
+            let size = puzzle.size();
+            let snapshot = self.recorder.snapshot(puzzle, || (1..=size).filter(|r| *r != row).map(|r| (r,col)).collect());
+            (1..=size)
                 .filter(|r| *r != row)
                 .for_each(|r| puzzle.get_square(r,col).bitmap_elimination(bitmap));
+            let value = puzzle.get_square(row,col).get_single_value();
+            self.recorder.record(Technique::NakedSingle, Some(Unit::Column(col)), vec![(row,col)], vec![value], puzzle, snapshot);
         }
         self.stopwatch.stop();
         Ok(())
     }
 }
 
+// box_size is 3 on a 9x9 board, see SudokuPuzzle::box_size
 pub mod box_util {
-    pub fn box8(row: usize, column: usize) -> Vec<(usize, usize)> {
-        box9(row, column).iter()
+    // the other squares of the box (8 of them on a 9x9 board)
+    pub fn box_peers(box_size: usize, row: usize, column: usize) -> Vec<(usize, usize)> {
+        box_cells(box_size, row, column).iter()
             .map(|(i,j)| (*i,*j))
             .filter(|(i,j)| (*i)!=row || (*j)!=column)
             .collect()
     }
-    pub fn box9(row: usize, column: usize) -> Vec<(usize, usize)> {
-        let row_box = index_to_box(row);
-        let column_box = index_to_box(column);
+    // every square of the box, row by row
+    pub fn box_cells(box_size: usize, row: usize, column: usize) -> Vec<(usize, usize)> {
+        let row_box = index_to_box(box_size, row);
+        let column_box = index_to_box(box_size, column);
 
-        [1,1,1,2,2,2,3,3,3].iter().zip([1,2,3,1,2,3,1,2,3].iter())
-            .map(|(i,j)| (*i as usize, *j as usize))
+        (1..=box_size).flat_map(|i| (1..=box_size).map(move |j| (i,j)))
             .map(|(i,j)| {
-                // row_box of 1 is add 0 to everything
+                // (9x9) row_box of 1 is add 0 to everything
                 // row_box of 2 is add 3 to everything
                 // row_box of 3 is add 6 to everything
-                let row_offset = (row_box - 1) * 3;
-                let column_offset = (column_box - 1) * 3;
+                let row_offset = (row_box - 1) * box_size;
+                let column_offset = (column_box - 1) * box_size;
                 (i+row_offset, j+column_offset)
             })
             .collect()
     }
-    pub fn index_to_box(row: usize) -> usize {
+    pub fn box_number(box_size: usize, row: usize, column: usize) -> usize {
+        // 1 2 3
+        // 4 5 6
+        // 7 8 9
+        (index_to_box(box_size, row) - 1) * box_size + index_to_box(box_size, column)
+    }
+    pub fn index_to_box(box_size: usize, row: usize) -> usize {
         // calling it row but works for column
-        // 1-3 => 1
+        // (9x9) 1-3 => 1
         // 4-6 => 2
         // 7-9 => 3
-        match row {
-            1 ..= 3 => 1,
-            4 ..= 6 => 2,
-            7 ..= 9 => 3,
-            _ => panic!("non-sudoku value!!"),
+        match (1..=box_size*box_size).contains(&row) {
+            true => (row - 1) / box_size + 1,
+            false => panic!("non-sudoku value!!"),
         }
     }
-    fn _box_to_range(box_number: usize) -> std::ops::Range<usize> {
-        match box_number {
-            1 => (1..4),
-            2 => (4..7),
-            3 => (7..10),
-            _ => panic!("non-sudoku value!!"),
+    fn _box_to_range(box_size: usize, box_number: usize) -> std::ops::Range<usize> {
+        match (1..=box_size).contains(&box_number) {
+            true => (box_number - 1) * box_size + 1..box_number * box_size + 1,
+            false => panic!("non-sudoku value!!"),
         }
     }
 }
 
 pub struct NoBoxDuplicates {
     stopwatch: Stopwatch,
+    recorder: Recorder,
 }
 impl NoBoxDuplicates {
     pub fn new() -> Self {
         {
             let stopwatch = Stopwatch::new();
             NoBoxDuplicates {
-                stopwatch
+                stopwatch,
+                recorder: Recorder::new(),
             }
         }
     }
+    // 9x9 only
     fn _process_deprecated(&self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
         let row = queue_item.row;
         let col = queue_item.column;
@@ -621,8 +2348,8 @@ impl NoBoxDuplicates {
 
             // can I use two ranges and zip??
 
-            let row_box = box_util::index_to_box(row);
-            let col_box = box_util::index_to_box(col);
+            let row_box = box_util::index_to_box(3, row);
+            let col_box = box_util::index_to_box(3, col);
 
             // the two ranges will be in principle
             // [1,1,1,2,2,2,3,3,3] and [1,2,3,1,2,3,1,2,3]
@@ -641,7 +2368,6 @@ impl NoBoxDuplicates {
                 })
                 .filter(|(i,j)| *i!=row || *j!=col)
                 .for_each(|(i,j)| puzzle.get_square(i,j).bitmap_elimination(bitmap));
-
         }
         Ok(())
     }
@@ -650,6 +2376,12 @@ impl Constraint for NoBoxDuplicates {
     fn name(&self) -> String {
         "NoBoxDuplicates".to_string()
     }
+    fn set_recording(&mut self, is_recording: bool) {
+        self.recorder.set_recording(is_recording);
+    }
+    fn take_steps(&mut self) -> Vec<Step> {
+        self.recorder.take_steps()
+    }
     fn elapsed_millis(&self) -> u128 {
         self.stopwatch.elapsed_millis()
     }
@@ -659,8 +2391,12 @@ impl Constraint for NoBoxDuplicates {
         let col = queue_item.column;
         if puzzle.get_square(row,col).has_single_value() {
             let bitmap = puzzle.get_square(row,col).get_bitmap();
-            box_util::box8(row,col).iter()
+            let box_size = puzzle.box_size();
+            let snapshot = self.recorder.snapshot(puzzle, || box_util::box_peers(box_size,row,col));
+            box_util::box_peers(box_size,row,col).iter()
                 .for_each(|(i,j)| puzzle.get_square(*i,*j).bitmap_elimination(bitmap));
+            let value = puzzle.get_square(row,col).get_single_value();
+            self.recorder.record(Technique::NakedSingle, Some(Unit::containing_box(box_size,row,col)), vec![(row,col)], vec![value], puzzle, snapshot);
         }
         self.stopwatch.stop();
         Ok(())
@@ -676,15 +2412,30 @@ mod tests {
     use crate::constraint::NoColumnDuplicates;
     use crate::constraint::NoBoxDuplicates;
     use crate::constraint::ProcessOfElimination;
+    use crate::constraint::AllCannotBeEliminated;
+    use crate::constraint::ConstraintViolation;
+    use crate::constraint::Unit;
+    use crate::constraint::NakedSubsets;
+    use crate::constraint::HiddenSubsets;
+    use crate::constraint::LockedCandidates;
+    use crate::constraint::Fish;
+    use crate::constraint::XYWing;
+    use crate::constraint::XYZWing;
+    use crate::constraint::XChain;
+    use crate::constraint::UniqueRectangle;
+    use crate::constraint::BugPlusOne;
+    use crate::constraint::ForcingChains;
+    use crate::explain::Technique;
     use crate::solver::QueueItem;
     use crate::config;
     use super::box_util;
+    use crate::squares;
 
     #[test]
     fn test_break_loop() {
         let mut b = false;
 
-        for i in (0..10) {
+        for i in 0..10 {
             if i == 5 {
                 b = true;
                 break;
@@ -693,9 +2444,8 @@ mod tests {
 
         assert!(b);
 
-        let mut b = false;
-        'outer: for i in (0..10) {
-            'inner: for j in (0..10) {
+        'outer: for i in 0..10 {
+            for j in 0..10 {
                 if j == 8 {
                     break 'outer;
                 }
@@ -704,7 +2454,7 @@ mod tests {
                 panic!("should not reach 1");
             }
         }
-        
+
 
     }
 
@@ -727,8 +2477,8 @@ mod tests {
 
         // assert!(result == Ok(3));
 
-        let mut it = [1 as usize, 2, 3, 4, 5].iter();
-        let result = it.try_fold(0 as usize, |acc, x| {
+        let mut it = [1_usize, 2, 3, 4, 5].iter();
+        let result = it.try_fold(0_usize, |acc, x| {
             println!("about to call foo({}), acc={:?}", *x, acc);
             foo(*x)
         });
@@ -736,7 +2486,6 @@ mod tests {
         assert!(result == Err(()));
         assert!(it.len() == 1);
         assert!(it.next() == Some(&5));
-        
     }
 
     #[test]
@@ -761,7 +2510,6 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint1 = ProcessOfElimination:: new();
         let mut constraint1: Box<dyn Constraint> = Box::new(ProcessOfElimination::new());
         let mut constraint2: Box<dyn Constraint> = Box::new(NoColumnDuplicates::new());
         let mut constraint3: Box<dyn Constraint> = Box::new(NoRowDuplicates::new());
@@ -772,15 +2520,28 @@ mod tests {
         [(1,1),(2,1),(3,1),(4,1),(5,1),(6,1),(7,1),(1,2),(2,4),(3,7),(4,3),(5,5),(6,8),(7,6),(8,9),(1,1),]
             .iter()
             .for_each(|(i,j)| {
-                constraint1.process(&QueueItem{row:*i, column:*j}, &mut puzzle);
-                constraint2.process(&QueueItem{row:*i, column:*j}, &mut puzzle);
-                constraint3.process(&QueueItem{row:*i, column:*j}, &mut puzzle);
+                constraint1.process(&QueueItem::new(*i, *j), &mut puzzle).unwrap();
+                constraint2.process(&QueueItem::new(*i, *j), &mut puzzle).unwrap();
+                constraint3.process(&QueueItem::new(*i, *j), &mut puzzle).unwrap();
             });
         // hack for now
         if config::IS_POE_ENABLED {
             assert!(puzzle.get_square(9,1).has_single_value());
         }
-        
+
+    }
+
+    #[test]
+    fn process_of_elimination_blames_the_unit_in_conflict() {
+        let mut puzzle = empty_puzzle();
+        puzzle.get_square(1,1).eliminate(5);
+        let mut constraint = ProcessOfElimination::new().with_enabled(true);
+        assert_eq!(constraint.process(&QueueItem::new(1, 1), &mut puzzle), Ok(()));
+
+        // row 1 is as before and is skipped as memoized; 5 is gone from column 1
+        (2..=9).for_each(|r| puzzle.get_square(r,1).eliminate(5));
+        assert_eq!(constraint.process(&QueueItem::new(1, 1), &mut puzzle),
+                   Err(ConstraintViolation::DigitMissing { unit: Unit::Column(1), digit: 5 }));
     }
 
     #[test]
@@ -798,7 +2559,7 @@ mod tests {
 |897632415|
 -----------
          */
-       
+
         let sample_puzzle: Vec<String> =
 
         [
@@ -819,7 +2580,6 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint = NoBoxDuplicates::new();
         let mut constraint: Box<dyn Constraint> = Box::new(NoBoxDuplicates::new());
 
         // reminder: (1..10) goes from 1-9
@@ -836,7 +2596,7 @@ mod tests {
             .map(|(i,j)| (*i as usize, *j as usize))
             .filter(|(i,j)| *i!=row || *j!=col)
             .for_each(|(i,j)| {
-                constraint.process(&QueueItem{row:i, column:j}, &mut puzzle);
+                constraint.process(&QueueItem::new(i, j), &mut puzzle).unwrap();
             });
         assert!(puzzle.get_square(row,col).has_single_value());
         assert!(puzzle.get_square(row,col).get_single_value() == mystery_value);
@@ -849,16 +2609,16 @@ mod tests {
             range2: Vec<usize>,
             puzzle: &mut SudokuPuzzle,
             constraint: &mut Box<dyn Constraint>
-        ) -> () {
+        ) {
             assert!(!puzzle.get_square(row,col).has_single_value());
             range1
                 .iter()
                 .zip(range2.iter())
-                .map(|(i,j)| (*i as usize, *j as usize))
+                .map(|(i,j)| (*i, *j))
                 .filter(|(i,j)| *i!=row || *j!=col)
                 .for_each(|(i,j)| {
                     println!("i,j={},{}",i,j);
-                    constraint.process(&QueueItem{row:i, column:j}, puzzle);
+                    constraint.process(&QueueItem::new(i, j), puzzle).unwrap();
                 });
             assert!(puzzle.get_square(row,col).has_single_value());
             assert!(puzzle.get_square(row,col).get_single_value() == mystery_value);
@@ -878,36 +2638,36 @@ mod tests {
         let range1 = [4,4,4,5,5,5,6,6,6].to_vec();
         let range2 = [1,2,3,1,2,3,1,2,3].to_vec();
         assertions(5,1,1,range1,range2, &mut puzzle, &mut constraint);
-        
+
         // box 5 (middle middle)
         let range1 = [4,4,4,5,5,5,6,6,6].to_vec();
         let range2 = [4,5,6,4,5,6,4,5,6].to_vec();
         assertions(5,5,7,range1,range2, &mut puzzle, &mut constraint);
-        
+
         // box 6 (middle right)
         let range1 = [4,4,4,5,5,5,6,6,6].to_vec();
         let range2 = [7,8,9,7,8,9,7,8,9].to_vec();
         assertions(5,9,6,range1,range2, &mut puzzle, &mut constraint);
-        
+
         // box 7 (bottom left)
         let range1 = [7,7,7,8,8,8,9,9,9].to_vec();
         let range2 = [1,2,3,1,2,3,1,2,3].to_vec();
         assertions(7,3,6,range1,range2, &mut puzzle, &mut constraint);
-        
+
         // box 8 (bottom middle)
         let range1 = [7,7,7,8,8,8,9,9,9].to_vec();
         let range2 = [4,5,6,4,5,6,4,5,6].to_vec();
         assertions(7,5,9,range1,range2, &mut puzzle, &mut constraint);
-        
+
         // box 9 (bottom right)
         let range1 = [7,7,7,8,8,8,9,9,9].to_vec();
         let range2 = [7,8,9,7,8,9,7,8,9].to_vec();
         assertions(7,7,3,range1,range2, &mut puzzle, &mut constraint);
-        
     }
 
+    #[test]
     fn test_no_box_duplicates() {
-        
+
         let sample_puzzle: Vec<String> =
 
         [
@@ -928,7 +2688,6 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint = NoBoxDuplicates::new();
         let mut constraint: Box<dyn Constraint> = Box::new(NoBoxDuplicates::new());
 
         // reminder: (1..10) goes from 1-9
@@ -941,16 +2700,15 @@ mod tests {
             .map(|(i,j)| (*i as usize, *j as usize))
             .filter(|(i,j)| *i!=row || *j!=col)
             .for_each(|(i,j)| {
-                constraint.process(&QueueItem{row:i, column:j}, &mut puzzle);
+                constraint.process(&QueueItem::new(i, j), &mut puzzle).unwrap();
             });
         assert!(puzzle.get_square(row,col).has_single_value());
         assert!(puzzle.get_square(row,col).get_single_value() == 9);
-
     }
 
     #[test]
     fn box_iterators() {
-        let actual = box_util::box8(5,5);
+        let actual = box_util::box_peers(3, 5,5);
         assert_eq!(actual.len(), 8);
         let expected = [(4,4),(4,5),(4,6),(5,4),(5,6),(6,4),(6,5),(6,6)];
         (0..8).for_each(|i| assert_eq!(actual.get(i),expected.get(i)));
@@ -960,189 +2718,189 @@ mod tests {
     fn box_vectors() {
         {
             // upper left box
-            let example = box_util::box9(1,1);
-            let rest = vec![
-                box_util::box9(1,2),
-                box_util::box9(1,3),
-                box_util::box9(2,1),
-                box_util::box9(2,2),
-                box_util::box9(2,3),
-                box_util::box9(3,1),
-                box_util::box9(3,2),
-                box_util::box9(3,3),
+            let example = box_util::box_cells(3, 1,1);
+            let rest = [
+                box_util::box_cells(3, 1,2),
+                box_util::box_cells(3, 1,3),
+                box_util::box_cells(3, 2,1),
+                box_util::box_cells(3, 2,2),
+                box_util::box_cells(3, 2,3),
+                box_util::box_cells(3, 3,1),
+                box_util::box_cells(3, 3,2),
+                box_util::box_cells(3, 3,3),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // upper middle box
-            let example = box_util::box9(1,4);
-            let rest = vec![
-                box_util::box9(1,5),
-                box_util::box9(1,6),
-                box_util::box9(2,4),
-                box_util::box9(2,5),
-                box_util::box9(2,6),
-                box_util::box9(3,4),
-                box_util::box9(3,5),
-                box_util::box9(3,6),
+            let example = box_util::box_cells(3, 1,4);
+            let rest = [
+                box_util::box_cells(3, 1,5),
+                box_util::box_cells(3, 1,6),
+                box_util::box_cells(3, 2,4),
+                box_util::box_cells(3, 2,5),
+                box_util::box_cells(3, 2,6),
+                box_util::box_cells(3, 3,4),
+                box_util::box_cells(3, 3,5),
+                box_util::box_cells(3, 3,6),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // upper right box
-            let example = box_util::box9(1,7);
-            let rest = vec![
-                box_util::box9(1,8),
-                box_util::box9(1,9),
-                box_util::box9(2,7),
-                box_util::box9(2,8),
-                box_util::box9(2,9),
-                box_util::box9(3,7),
-                box_util::box9(3,8),
-                box_util::box9(3,9),
+            let example = box_util::box_cells(3, 1,7);
+            let rest = [
+                box_util::box_cells(3, 1,8),
+                box_util::box_cells(3, 1,9),
+                box_util::box_cells(3, 2,7),
+                box_util::box_cells(3, 2,8),
+                box_util::box_cells(3, 2,9),
+                box_util::box_cells(3, 3,7),
+                box_util::box_cells(3, 3,8),
+                box_util::box_cells(3, 3,9),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // middle left box
-            let example = box_util::box9(4,1);
-            let rest = vec![
-                box_util::box9(4,2),
-                box_util::box9(4,3),
-                box_util::box9(5,1),
-                box_util::box9(5,2),
-                box_util::box9(5,3),
-                box_util::box9(6,1),
-                box_util::box9(6,2),
-                box_util::box9(6,3),
+            let example = box_util::box_cells(3, 4,1);
+            let rest = [
+                box_util::box_cells(3, 4,2),
+                box_util::box_cells(3, 4,3),
+                box_util::box_cells(3, 5,1),
+                box_util::box_cells(3, 5,2),
+                box_util::box_cells(3, 5,3),
+                box_util::box_cells(3, 6,1),
+                box_util::box_cells(3, 6,2),
+                box_util::box_cells(3, 6,3),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // middle middle box
-            let example = box_util::box9(4,4);
-            let rest = vec![
-                box_util::box9(4,5),
-                box_util::box9(4,6),
-                box_util::box9(5,4),
-                box_util::box9(5,5),
-                box_util::box9(5,6),
-                box_util::box9(6,4),
-                box_util::box9(6,5),
-                box_util::box9(6,6),
+            let example = box_util::box_cells(3, 4,4);
+            let rest = [
+                box_util::box_cells(3, 4,5),
+                box_util::box_cells(3, 4,6),
+                box_util::box_cells(3, 5,4),
+                box_util::box_cells(3, 5,5),
+                box_util::box_cells(3, 5,6),
+                box_util::box_cells(3, 6,4),
+                box_util::box_cells(3, 6,5),
+                box_util::box_cells(3, 6,6),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // middle right box
-            let example = box_util::box9(4,7);
-            let rest = vec![
-                box_util::box9(4,8),
-                box_util::box9(4,9),
-                box_util::box9(5,7),
-                box_util::box9(5,8),
-                box_util::box9(5,9),
-                box_util::box9(6,7),
-                box_util::box9(6,8),
-                box_util::box9(6,9),
+            let example = box_util::box_cells(3, 4,7);
+            let rest = [
+                box_util::box_cells(3, 4,8),
+                box_util::box_cells(3, 4,9),
+                box_util::box_cells(3, 5,7),
+                box_util::box_cells(3, 5,8),
+                box_util::box_cells(3, 5,9),
+                box_util::box_cells(3, 6,7),
+                box_util::box_cells(3, 6,8),
+                box_util::box_cells(3, 6,9),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // bottom left box
-            let example = box_util::box9(7,1);
-            let rest = vec![
-                box_util::box9(7,2),
-                box_util::box9(7,3),
-                box_util::box9(8,1),
-                box_util::box9(8,2),
-                box_util::box9(8,3),
-                box_util::box9(9,1),
-                box_util::box9(9,2),
-                box_util::box9(9,3),
+            let example = box_util::box_cells(3, 7,1);
+            let rest = [
+                box_util::box_cells(3, 7,2),
+                box_util::box_cells(3, 7,3),
+                box_util::box_cells(3, 8,1),
+                box_util::box_cells(3, 8,2),
+                box_util::box_cells(3, 8,3),
+                box_util::box_cells(3, 9,1),
+                box_util::box_cells(3, 9,2),
+                box_util::box_cells(3, 9,3),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // bottom middle box
-            let example = box_util::box9(7,4);
-            let rest = vec![
-                box_util::box9(7,5),
-                box_util::box9(7,6),
-                box_util::box9(8,4),
-                box_util::box9(8,5),
-                box_util::box9(8,6),
-                box_util::box9(9,4),
-                box_util::box9(9,5),
-                box_util::box9(9,6),
+            let example = box_util::box_cells(3, 7,4);
+            let rest = [
+                box_util::box_cells(3, 7,5),
+                box_util::box_cells(3, 7,6),
+                box_util::box_cells(3, 8,4),
+                box_util::box_cells(3, 8,5),
+                box_util::box_cells(3, 8,6),
+                box_util::box_cells(3, 9,4),
+                box_util::box_cells(3, 9,5),
+                box_util::box_cells(3, 9,6),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
         }
         {
             // bottom right box
-            let example = box_util::box9(7,7);
-            let rest = vec![
-                box_util::box9(7,8),
-                box_util::box9(7,9),
-                box_util::box9(8,7),
-                box_util::box9(8,8),
-                box_util::box9(8,9),
-                box_util::box9(9,7),
-                box_util::box9(9,8),
-                box_util::box9(9,9),
+            let example = box_util::box_cells(3, 7,7);
+            let rest = [
+                box_util::box_cells(3, 7,8),
+                box_util::box_cells(3, 7,9),
+                box_util::box_cells(3, 8,7),
+                box_util::box_cells(3, 8,8),
+                box_util::box_cells(3, 8,9),
+                box_util::box_cells(3, 9,7),
+                box_util::box_cells(3, 9,8),
+                box_util::box_cells(3, 9,9),
             ];
             assert_eq!(example.len(), 9);
             rest.iter().for_each(|el| {
                 assert_eq!(el.len(), 9);
-                for i in (0..9) {
+                for i in 0..9 {
                     assert_eq!(example.get(i), el.get(i));
                 }
             });
@@ -1151,15 +2909,23 @@ mod tests {
 
     #[test]
     fn box_index_to_box() {
-        assert!(box_util::index_to_box(1) == 1);
-        assert!(box_util::index_to_box(2) == 1);
-        assert!(box_util::index_to_box(3) == 1);
-        assert!(box_util::index_to_box(4) == 2);
-        assert!(box_util::index_to_box(5) == 2);
-        assert!(box_util::index_to_box(6) == 2);
-        assert!(box_util::index_to_box(7) == 3);
-        assert!(box_util::index_to_box(8) == 3);
-        assert!(box_util::index_to_box(9) == 3);
+        assert!(box_util::index_to_box(3, 1) == 1);
+        assert!(box_util::index_to_box(3, 2) == 1);
+        assert!(box_util::index_to_box(3, 3) == 1);
+        assert!(box_util::index_to_box(3, 4) == 2);
+        assert!(box_util::index_to_box(3, 5) == 2);
+        assert!(box_util::index_to_box(3, 6) == 2);
+        assert!(box_util::index_to_box(3, 7) == 3);
+        assert!(box_util::index_to_box(3, 8) == 3);
+        assert!(box_util::index_to_box(3, 9) == 3);
+        // 16x16
+        assert!(box_util::index_to_box(4, 4) == 1);
+        assert!(box_util::index_to_box(4, 5) == 2);
+        assert!(box_util::index_to_box(4, 16) == 4);
+        assert_eq!(box_util::box_number(4, 5, 16), 8);
+        assert_eq!(box_util::box_cells(4, 5, 16).len(), 16);
+        assert_eq!(box_util::box_cells(4, 5, 16)[0], (5, 13));
+        assert_eq!(box_util::box_peers(2, 1, 1), vec![(1,2), (2,1), (2,2)]);
     }
 
     #[test]
@@ -1197,7 +2963,6 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint = NoColumnDuplicates::new();
         let mut constraint: Box<dyn Constraint> = Box::new(NoColumnDuplicates::new());
 
         // reminder: (1..10) goes from 1-9
@@ -1207,7 +2972,7 @@ mod tests {
 
         // process the first column and test
         (1..9)
-            .for_each(|r| { constraint.process(&QueueItem{row:r, column:1}, &mut puzzle); });
+            .for_each(|r| { constraint.process(&QueueItem::new(r, 1), &mut puzzle).unwrap(); });
         (1..2)
             .for_each(|c| assert!(puzzle.get_square(9,c).has_single_value()));
         (2..10)
@@ -1217,13 +2982,13 @@ mod tests {
         (2..10)
             .for_each(|column| {
                 (1..9)
-                    .for_each(|r| { constraint.process(&QueueItem{row:r, column:column}, &mut puzzle); });
+                    .for_each(|r| { constraint.process(&QueueItem::new(r, column), &mut puzzle).unwrap(); });
                 (1..column+1)
                     .for_each(|c| assert!(puzzle.get_square(9,c).has_single_value()));
                 (column+1..10)
                     .for_each(|c| assert!(!puzzle.get_square(9,c).has_single_value()));
             });
-        
+
         // test all the values now
         assert!(puzzle.get_square(9,1).get_single_value() == 8);
         assert!(puzzle.get_square(9,2).get_single_value() == 9);
@@ -1271,7 +3036,6 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint = NoRowDuplicates::new();
         let mut constraint: Box<dyn Constraint> = Box::new(NoRowDuplicates::new());
 
         // reminder: (1..10) goes from 1-9
@@ -1281,7 +3045,7 @@ mod tests {
 
         // process the first row and test
         (1..9)
-            .for_each(|c| { constraint.process(&QueueItem{row:1, column:c}, &mut puzzle); });
+            .for_each(|c| { constraint.process(&QueueItem::new(1, c), &mut puzzle).unwrap(); });
         (1..2)
             .for_each(|r| assert!(puzzle.get_square(r,9).has_single_value()));
         (2..10)
@@ -1291,13 +3055,13 @@ mod tests {
         (2..10)
             .for_each(|row| {
                 (1..9)
-                    .for_each(|c| { constraint.process(&QueueItem{row:row, column:c}, &mut puzzle); });
+                    .for_each(|c| { constraint.process(&QueueItem::new(row, c), &mut puzzle).unwrap(); });
                 (1..row+1)
                     .for_each(|r| assert!(puzzle.get_square(r,9).has_single_value()));
                 (row+1..10)
                     .for_each(|r| assert!(!puzzle.get_square(r,9).has_single_value()));
             });
-        
+
         // test all the values now
         assert!(puzzle.get_square(1,9).get_single_value() == 7);
         assert!(puzzle.get_square(2,9).get_single_value() == 4);
@@ -1331,10 +3095,458 @@ mod tests {
 
         let mut puzzle = SudokuPuzzle::new(sample_puzzle);
 
-        let mut constraint = NoRowDuplicates::new();
         let mut constraint: Box<dyn Constraint> = Box::new(NoRowDuplicates::new());
 
-        constraint.process(&QueueItem{row:1, column: 1}, &mut puzzle);
+        constraint.process(&QueueItem::new(1, 1), &mut puzzle).unwrap();
+    }
+
+    #[test]
+    fn all_cannot_be_eliminated() {
+        let mut puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&".".repeat(81)).unwrap()).unwrap();
+        (1..=9).for_each(|v| puzzle.get_square(2,3).eliminate(v));
+
+        let mut constraint = AllCannotBeEliminated::new();
+        assert_eq!(constraint.process(&QueueItem::new(1, 1), &mut puzzle), Ok(()));
+        assert_eq!(constraint.process(&QueueItem::new(2, 3), &mut puzzle),
+                   Err(ConstraintViolation::NoCandidates { row: 2, column: 3 }));
+    }
+
+    fn empty_puzzle() -> SudokuPuzzle {
+        SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&".".repeat(81)).unwrap()).unwrap()
+    }
+
+    // leaves only the given candidates in the square
+    fn keep(puzzle: &mut SudokuPuzzle, row: usize, column: usize, values: &[usize]) {
+        let size = puzzle.size();
+        (1..=size).filter(|v| !values.contains(v)).for_each(|v| puzzle.get_square(row, column).eliminate(v));
+    }
+
+    #[test]
+    fn naked_triple() {
+        // (12) (23) (13) in row 1: 1, 2 and 3 go nowhere else in the row
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 5, &[2,3]);
+        keep(&mut puzzle, 1, 9, &[1,3]);
+
+        let mut constraint = NakedSubsets::new();
+        constraint.set_recording(true);
+        assert_eq!(constraint.process(&QueueItem::new(1, 5), &mut puzzle), Ok(()));
+        assert_eq!(puzzle.get_square(1,2).values_remaining(), vec![4,5,6,7,8,9]);
+        assert_eq!(puzzle.get_square(1,5).values_remaining(), vec![2,3]);
+        // the column is untouched
+        assert_eq!(puzzle.get_square(2,5).count_remaining(), 9);
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::NakedTriple);
+        assert_eq!(steps[0].unit, Some(Unit::Row(1)));
+        assert_eq!(steps[0].cells, vec![(1,1), (1,5), (1,9)]);
+        assert_eq!(steps[0].digits, vec![1,2,3]);
+
+        // only pairs: nothing to do
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 5, &[2,3]);
+        keep(&mut puzzle, 1, 9, &[1,3]);
+        NakedSubsets::new().with_size(2).process(&QueueItem::new(1, 5), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(1,2).count_remaining(), 9);
+    }
+
+    #[test]
+    fn hidden_pair() {
+        // 8 and 9 only fit in (4,1) and (6,2) of box 4
+        let mut puzzle = empty_puzzle();
+        (4..=6).flat_map(|r| (1..=3).map(move |c| (r,c)))
+            .filter(|cell| *cell != (4,1) && *cell != (6,2))
+            .for_each(|(r,c)| puzzle.get_square(r,c).eliminate_list(&[8,9]));
+
+        let mut constraint = HiddenSubsets::new();
+        constraint.set_recording(true);
+        assert_eq!(constraint.process(&QueueItem::new(5, 3), &mut puzzle), Ok(()));
+        assert_eq!(puzzle.get_square(4,1).values_remaining(), vec![8,9]);
+        assert_eq!(puzzle.get_square(6,2).values_remaining(), vec![8,9]);
+        assert_eq!(puzzle.get_square(5,1).count_remaining(), 7);
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::HiddenPair);
+        assert_eq!(steps[0].unit, Some(Unit::Box(4)));
+        assert_eq!(steps[0].cells, vec![(4,1), (6,2)]);
+        assert_eq!(steps[0].digits, vec![8,9]);
+    }
+
+    #[test]
+    fn locked_candidates() {
+        // pointing: in box 1, 7 only fits in row 2, so not in (2,4)..(2,9)
+        let mut puzzle = empty_puzzle();
+        [(1,1),(1,2),(1,3),(3,1),(3,2),(3,3)].iter().for_each(|(r,c)| puzzle.get_square(*r,*c).eliminate(7));
+        let mut constraint = LockedCandidates::new();
+        constraint.set_recording(true);
+        assert_eq!(constraint.process(&QueueItem::new(1, 1), &mut puzzle), Ok(()));
+        assert!(puzzle.get_square(2,2).is_possible(7));
+        assert!(!puzzle.get_square(2,4).is_possible(7));
+        assert!(!puzzle.get_square(2,9).is_possible(7));
+        assert!(puzzle.get_square(5,2).is_possible(7));
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::LockedCandidates);
+        assert_eq!(steps[0].unit, Some(Unit::Box(1)));
+        assert_eq!(steps[0].cells, vec![(2,1),(2,2),(2,3)]);
+        assert_eq!(steps[0].eliminated.len(), 6);
+
+        // claiming: in column 5, 3 only fits in box 8, so not elsewhere in box 8
+        let mut puzzle = empty_puzzle();
+        (1..=6).for_each(|r| puzzle.get_square(r,5).eliminate(3));
+        LockedCandidates::new().process(&QueueItem::new(2, 5), &mut puzzle).unwrap();
+        assert!(puzzle.get_square(8,5).is_possible(3));
+        assert!(!puzzle.get_square(8,4).is_possible(3));
+        assert!(!puzzle.get_square(9,6).is_possible(3));
+        assert!(puzzle.get_square(9,7).is_possible(3));
+    }
+
+    #[test]
+    fn locked_candidates_only_look_at_removed_digits() {
+        // 3 is claimed by box 8 from column 5, and 7 points from box 1 along row 2
+        let mut puzzle = empty_puzzle();
+        (1..=6).for_each(|r| puzzle.get_square(r,5).eliminate(3));
+        [(1,1),(1,2),(1,3),(3,1),(3,2),(3,3)].iter().for_each(|(r,c)| puzzle.get_square(*r,*c).eliminate(7));
+        let mut constraint = LockedCandidates::new();
+        assert!(constraint.is_unit_based());
+
+        // neither digit was removed here, so neither is looked at
+        constraint.process_unit(Unit::Column(5), squares::bit(4) | squares::bit(7), &mut puzzle).unwrap();
+        constraint.process_unit(Unit::Box(1), squares::bit(3) | squares::bit(4), &mut puzzle).unwrap();
+        assert!(puzzle.get_square(8,4).is_possible(3));
+        assert!(puzzle.get_square(2,4).is_possible(7));
+
+        // each unit acts on its own digit once it is among those removed
+        constraint.process_unit(Unit::Column(5), squares::bit(3) | squares::bit(4), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(8,4).is_possible(3));
+        assert!(puzzle.get_square(2,4).is_possible(7));
+        constraint.process_unit(Unit::Box(1), squares::bit(7), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(2,4).is_possible(7));
+    }
+
+    #[test]
+    fn x_wing() {
+        // in rows 2 and 7, 5 only fits in columns 3 and 8
+        let mut puzzle = empty_puzzle();
+        for r in [2, 7] {
+            (1..=9).filter(|c| *c != 3 && *c != 8).for_each(|c| puzzle.get_square(r,c).eliminate(5));
+        }
+        let mut constraint = Fish::new();
+        constraint.set_recording(true);
+        // (2,3) still has 5, so it cannot have formed a fish for it
+        constraint.process(&QueueItem::new(2, 3), &mut puzzle).unwrap();
+        assert!(puzzle.get_square(1,3).is_possible(5));
+        assert!(constraint.take_steps().is_empty());
+
+        constraint.process(&QueueItem::new(2, 1), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(1,3).is_possible(5));
+        assert!(!puzzle.get_square(9,8).is_possible(5));
+        assert!(puzzle.get_square(2,3).is_possible(5));
+        assert!(puzzle.get_square(7,8).is_possible(5));
+        assert!(puzzle.get_square(1,1).is_possible(5));
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::XWing);
+        assert_eq!(steps[0].cells, vec![(2,3),(2,8),(7,3),(7,8)]);
+        assert_eq!(steps[0].eliminated.len(), 14);
+
+        assert_eq!(Fish::new().name(), "Fish");
+        assert_eq!(Fish::new().with_size(3).name(), "FishSwordfish");
+    }
+
+    #[test]
+    fn xy_wing() {
+        // pivot (12) at (1,1), pincers (13) at (1,5) and (23) at (5,1): one of
+        // the pincers is 3, and (5,5) sees both
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 5, &[1,3]);
+        keep(&mut puzzle, 5, 1, &[2,3]);
+        let mut constraint = XYWing::new();
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(5,5).is_possible(3));
+        assert!(puzzle.get_square(1,9).is_possible(3));
+        assert!(puzzle.get_square(9,1).is_possible(3));
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::XYWing);
+        assert_eq!(steps[0].cells, vec![(1,5), (1,1), (5,1)]);
+        assert_eq!(steps[0].digits, vec![3]);
+        assert_eq!(steps[0].eliminated, vec![(5,5,3)]);
+    }
+
+    #[test]
+    fn xyz_wing() {
+        // pivot (123) at (1,1), pincers (13) at (1,5) and (23) at (2,2): 3 goes
+        // from the squares that see all three
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2,3]);
+        keep(&mut puzzle, 1, 5, &[1,3]);
+        keep(&mut puzzle, 2, 2, &[2,3]);
+        let mut constraint = XYZWing::new();
+        constraint.process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(1,2).is_possible(3));
+        assert!(!puzzle.get_square(1,3).is_possible(3));
+        assert!(puzzle.get_square(1,4).is_possible(3));
+        assert!(puzzle.get_square(2,1).is_possible(3));
+        assert!(puzzle.get_square(1,1).is_possible(3));
+    }
+
+    #[test]
+    fn x_chain() {
+        // 5 only fits twice in columns 1 and 5, linked through row 8: either
+        // (2,1) or (3,5) is 5
+        let mut puzzle = empty_puzzle();
+        (1..=9).filter(|r| *r != 2 && *r != 8).for_each(|r| puzzle.get_square(r,1).eliminate(5));
+        (1..=9).filter(|r| *r != 3 && *r != 8).for_each(|r| puzzle.get_square(r,5).eliminate(5));
+        let mut constraint = XChain::new();
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(1, 1), &mut puzzle).unwrap();
+        for (r,c) in [(2,4), (2,6), (3,2), (3,3)].iter() {
+            assert!(!puzzle.get_square(*r,*c).is_possible(5));
+        }
+        assert!(puzzle.get_square(2,1).is_possible(5));
+        assert!(puzzle.get_square(3,5).is_possible(5));
+        assert!(puzzle.get_square(2,2).is_possible(5));
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::XChain);
+        assert_eq!(steps[0].cells, vec![(2,1), (8,1), (8,5), (3,5)]);
+        assert_eq!(steps[0].eliminated.len(), 4);
+    }
+
+    #[test]
+    fn unique_rectangle_type_1() {
+        // (12) in three corners of a rectangle over boxes 1 and 4: the fourth
+        // corner cannot be 1 or 2, or 1 and 2 could swap
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 2, &[1,2]);
+        keep(&mut puzzle, 4, 1, &[1,2]);
+        keep(&mut puzzle, 4, 2, &[1,2,3,4]);
+        // only when the puzzle is declared unique
+        UniqueRectangle::new().process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert!(puzzle.get_square(4,2).is_possible(1));
+
+        let mut constraint = UniqueRectangle::new().with_enabled(true);
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(4,2).values_remaining(), vec![3,4]);
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::UniqueRectangle);
+        assert_eq!(steps[0].cells, vec![(1,1), (1,2), (4,1), (4,2)]);
+        assert_eq!(steps[0].digits, vec![1,2]);
+        assert_eq!(steps[0].eliminated, vec![(4,2,1), (4,2,2)]);
+    }
+
+    #[test]
+    fn unique_rectangle_type_2() {
+        // both roof squares are (123): one of them is 3, so 3 goes from the
+        // squares that see both
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 2, &[1,2]);
+        keep(&mut puzzle, 4, 1, &[1,2,3]);
+        keep(&mut puzzle, 4, 2, &[1,2,3]);
+        UniqueRectangle::new().with_enabled(true).process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(4,9).is_possible(3));
+        assert!(!puzzle.get_square(6,3).is_possible(3));
+        assert!(puzzle.get_square(7,1).is_possible(3));
+        assert!(puzzle.get_square(4,1).is_possible(3));
+    }
+
+    #[test]
+    fn unique_rectangle_type_3() {
+        // the roof's extras (34) and (34) at (4,9) form a naked pair in row 4
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 2, &[1,2]);
+        keep(&mut puzzle, 4, 1, &[1,2,3]);
+        keep(&mut puzzle, 4, 2, &[1,2,4]);
+        keep(&mut puzzle, 4, 9, &[3,4]);
+        UniqueRectangle::new().with_enabled(true).process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert!(!puzzle.get_square(4,5).is_possible(3));
+        assert!(!puzzle.get_square(4,5).is_possible(4));
+        assert!(puzzle.get_square(4,9).is_possible(3));
+        assert!(puzzle.get_square(5,5).is_possible(3));
+    }
+
+    #[test]
+    fn unique_rectangle_type_4() {
+        // 1 only fits in the roof within row 4, so the roof cannot be 2
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 2, &[1,2]);
+        keep(&mut puzzle, 4, 1, &[1,2,5]);
+        keep(&mut puzzle, 4, 2, &[1,2,6]);
+        (3..=9).for_each(|c| puzzle.get_square(4,c).eliminate(1));
+        UniqueRectangle::new().with_enabled(true).process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(4,1).values_remaining(), vec![1,5]);
+        assert_eq!(puzzle.get_square(4,2).values_remaining(), vec![1,6]);
+    }
+
+    // 4x4 with every square bivalue and each digit twice in every unit, but
+    // with the given candidates at (1,1)
+    fn bivalue_grave(corner: &[usize]) -> SudokuPuzzle {
+        let mut puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&".".repeat(16)).unwrap()).unwrap();
+        let pairs = [[1,2], [3,4]];
+        for (r,c) in puzzle.coordinates() {
+            keep(&mut puzzle, r, c, &pairs[((r-1)/2 + (c-1)/2 + (r-1)%2) % 2]);
+        }
+        puzzle.restore(0, squares::full_bitmap(4));
+        keep(&mut puzzle, 1, 1, corner);
+        puzzle
+    }
+
+    #[test]
+    fn bug_plus_one() {
+        // (123) at (1,1): 3 appears three times in its row, column and box, and
+        // placing it there is the only way out of the deadly pattern
+        let mut puzzle = bivalue_grave(&[1,2,3]);
+        BugPlusOne::new().process(&QueueItem::new(4, 4), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(1,1).count_remaining(), 3);
+
+        let mut constraint = BugPlusOne::new().with_enabled(true);
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(4, 4), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(1,1).values_remaining(), vec![3]);
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::BugPlusOne);
+        assert_eq!(steps[0].cells, vec![(1,1)]);
+        assert_eq!(steps[0].digits, vec![3]);
+
+        // a plain bivalue grave has no +1 square to resolve
+        let mut puzzle = bivalue_grave(&[1,2]);
+        BugPlusOne::new().with_enabled(true).process(&QueueItem::new(4, 4), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(1,1).count_remaining(), 2);
+    }
+
+    #[test]
+    fn forcing_chain_contradiction() {
+        // r1c1=1 leaves 3 for both r1c2 and r1c3, so r1c1 is 2
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 2, &[1,3]);
+        keep(&mut puzzle, 1, 3, &[1,3]);
+        let mut constraint = ForcingChains::new();
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(1,1).values_remaining(), vec![2]);
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].technique, Technique::ForcingChain);
+        assert_eq!(steps[0].cells, vec![(1,1)]);
+        assert_eq!(steps[0].digits, vec![1]);
+        assert_eq!(steps[0].placed, vec![(1,1,2)]);
+        assert_eq!(steps[0].chain, vec!["r1c1=1 -> r1c2=3 -> r1c3=3 -> square (1,3) has no candidates left"]);
+    }
+
+    #[test]
+    fn forcing_chain_both_ways() {
+        // r1c1=1 makes r1c5 3, r1c1=2 makes r5c1 3; either way r5c5 is 4
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 5, &[1,3]);
+        keep(&mut puzzle, 5, 1, &[2,3]);
+        keep(&mut puzzle, 5, 5, &[3,4]);
+        let mut constraint = ForcingChains::new();
+        constraint.set_recording(true);
+        constraint.process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(5,5).values_remaining(), vec![4]);
+        assert_eq!(puzzle.get_square(1,1).count_remaining(), 2);
+
+        let steps = constraint.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].cells, vec![(1,1), (5,5)]);
+        assert_eq!(steps[0].digits, vec![1,2]);
+        assert_eq!(steps[0].chain, vec!["r1c1=1 -> r1c5=3 -> r5c5=4", "r1c1=2 -> r5c1=3 -> r5c5=4"]);
+
+        // with a single round the lookahead stops before reaching r5c5
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        keep(&mut puzzle, 1, 5, &[1,3]);
+        keep(&mut puzzle, 5, 1, &[2,3]);
+        keep(&mut puzzle, 5, 5, &[3,4]);
+        ForcingChains::new().with_max_rounds(1).process(&QueueItem::new(9, 9), &mut puzzle).unwrap();
+        assert_eq!(puzzle.get_square(5,5).count_remaining(), 2);
+    }
+
+    // notes the squares it is given, with what they lost, and every forget
+    struct Watcher {
+        seen: std::sync::Arc<std::sync::Mutex<Vec<(usize, usize, usize)>>>,
+        forgotten: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    }
+    impl Constraint for Watcher {
+        fn process(&mut self, queue_item: &QueueItem, _puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+            self.seen.lock().unwrap().push((queue_item.row, queue_item.column, queue_item.removed));
+            Ok(())
+        }
+        fn name(&self) -> String {
+            "Watcher".to_string()
+        }
+        fn elapsed_millis(&self) -> u128 {
+            0
+        }
+        fn forget(&mut self) {
+            self.forgotten.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn forcing_chain_lookahead_only_revisits_changes() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let forgotten = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut constraints: Vec<Box<dyn Constraint>> = vec![
+            Box::new(NoRowDuplicates::new()),
+            Box::new(Watcher { seen: seen.clone(), forgotten: forgotten.clone() }),
+        ];
+        let mut puzzle = empty_puzzle();
+        keep(&mut puzzle, 1, 1, &[1,2]);
+        let lookahead = ForcingChains::new().look_ahead(1, 1, 1, &puzzle, &mut constraints);
+        assert_eq!(lookahead.chain, vec![(1,1,1)]);
+        assert!(lookahead.contradiction.is_none());
+
+        // the placement, then the rest of row 1, which lost the 1; then
+        // nothing changed, so the lookahead stops
+        let mut expected = vec![(1, 1, squares::bit(2))];
+        expected.extend((2..=9).map(|c| (1, c, squares::bit(1))));
+        assert_eq!(*seen.lock().unwrap(), expected);
+        assert_eq!(forgotten.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // the same constraints for the other branch, having forgotten the first
+        seen.lock().unwrap().clear();
+        ForcingChains::new().look_ahead(1, 1, 2, &puzzle, &mut constraints);
+        assert_eq!(seen.lock().unwrap()[0], (1, 1, squares::bit(1)));
+        assert_eq!(forgotten.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn combinations() {
+        assert_eq!(super::combinations(&[1,2,3,4], 2).len(), 6);
+        assert_eq!(super::combinations(&[1,2,3], 3), vec![vec![1,2,3]]);
+        assert!(super::combinations(&[1,2], 3).is_empty());
+    }
+
+    #[test]
+    fn units() {
+        assert_eq!(Unit::all(3).len(), 27);
+        assert_eq!(Unit::containing_box(3,5,7), Unit::Box(6));
+        assert_eq!(Unit::Box(6).cells(3)[0], (4,7));
+        assert_eq!(Unit::Column(2).cells(3)[8], (9,2));
+        Unit::all(3).iter().enumerate().for_each(|(index, unit)| assert_eq!(unit.index(3), index));
+        assert_eq!(format!("{}", ConstraintViolation::DigitMissing { unit: Unit::Row(4), digit: 7 }),
+                   "row 4 has no square left for 7");
     }
 
     #[test]
@@ -0,0 +1,449 @@
+use crate::sudoku_puzzle::SudokuPuzzle;
+use crate::constraint::Unit;
+use crate::squares;
+use std::fmt;
+
+// ordered from easiest to hardest, so the hardest technique needed is the max
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Technique {
+    // a square with one candidate left (NoRow/Column/BoxDuplicates)
+    NakedSingle,
+    // the only square in a unit that can hold a digit (ProcessOfElimination)
+    HiddenSingle,
+    // a digit confined to one line within a box, or to one box within a
+    // line (LockedCandidates)
+    LockedCandidates,
+    // two squares in a unit with the same two candidates (DoubleDouble*,
+    // NakedSubsets)
+    NakedPair,
+    // two digits that only fit in the same two squares of a unit (HiddenSubsets)
+    HiddenPair,
+    // the same for three and four squares
+    NakedTriple,
+    HiddenTriple,
+    NakedQuad,
+    HiddenQuad,
+    // a digit confined to the same 2, 3 or 4 columns in as many rows, or the
+    // other way around (Fish)
+    XWing,
+    Swordfish,
+    Jellyfish,
+    // a rectangle of squares whose candidates would let two digits swap,
+    // giving a second solution (UniqueRectangle); only for puzzles declared
+    // unique
+    UniqueRectangle,
+    // all squares bivalue but one, which has to take its extra digit
+    // (BugPlusOne); also needs a unique puzzle
+    BugPlusOne,
+    // wings: a pivot square and two pincers, one of which must hold the
+    // digit (XYWing, XYZWing)
+    XYWing,
+    XYZWing,
+    // chains of strong links for one digit, split into two colors
+    // (SimpleColoring)
+    SimpleColoring,
+    // chains of alternating strong and weak links for one digit (XChain), or
+    // through bivalue squares (XYChain); either end holds the digit
+    XChain,
+    XYChain,
+    // assuming either candidate of a bivalue square and propagating: one
+    // leads to a contradiction, or both place the same digit (ForcingChains)
+    ForcingChain,
+    // nothing above makes progress, so a search is needed
+    Guessing,
+}
+impl Technique {
+    // on the newspapers' 1-4 scale, see sample_puzzles; where hidden singles
+    // fall on it depends on how often they are needed, see Grade::difficulty
+    pub fn difficulty(&self) -> usize {
+        match self {
+            Technique::NakedSingle |
+            Technique::HiddenSingle => 1,
+            Technique::LockedCandidates |
+            Technique::NakedPair |
+            Technique::HiddenPair |
+            Technique::NakedTriple |
+            Technique::HiddenTriple |
+            Technique::NakedQuad |
+            Technique::HiddenQuad |
+            Technique::XWing |
+            Technique::Swordfish |
+            Technique::Jellyfish |
+            Technique::UniqueRectangle |
+            Technique::BugPlusOne => 3,
+            Technique::XYWing |
+            Technique::XYZWing |
+            Technique::SimpleColoring |
+            Technique::XChain |
+            Technique::XYChain |
+            Technique::ForcingChain |
+            Technique::Guessing => 4,
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        difficulty_label(self.difficulty())
+    }
+}
+
+// the name of a rating on the 1-4 scale
+pub fn difficulty_label(difficulty: usize) -> &'static str {
+    match difficulty {
+        1 => "Easy",
+        2 => "Medium",
+        3 => "Hard",
+        _ => "Expert",
+    }
+}
+impl fmt::Display for Technique {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Technique::NakedSingle => write!(f, "naked single"),
+            Technique::HiddenSingle => write!(f, "hidden single"),
+            Technique::LockedCandidates => write!(f, "locked candidates"),
+            Technique::NakedPair => write!(f, "naked pair"),
+            Technique::HiddenPair => write!(f, "hidden pair"),
+            Technique::NakedTriple => write!(f, "naked triple"),
+            Technique::HiddenTriple => write!(f, "hidden triple"),
+            Technique::NakedQuad => write!(f, "naked quad"),
+            Technique::HiddenQuad => write!(f, "hidden quad"),
+            Technique::XWing => write!(f, "x-wing"),
+            Technique::Swordfish => write!(f, "swordfish"),
+            Technique::Jellyfish => write!(f, "jellyfish"),
+            Technique::XYWing => write!(f, "xy-wing"),
+            Technique::XYZWing => write!(f, "xyz-wing"),
+            Technique::UniqueRectangle => write!(f, "unique rectangle"),
+            Technique::BugPlusOne => write!(f, "bug+1"),
+            Technique::SimpleColoring => write!(f, "simple coloring"),
+            Technique::XChain => write!(f, "x-chain"),
+            Technique::XYChain => write!(f, "xy-chain"),
+            Technique::ForcingChain => write!(f, "forcing chain"),
+            Technique::Guessing => write!(f, "guessing"),
+        }
+    }
+}
+
+// one deduction: the technique, the squares and digits that justify it, and
+// what it changed; squares are (row, column), 1-based
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub technique: Technique,
+    pub unit: Option<Unit>,
+    pub cells: Vec<(usize, usize)>,
+    pub digits: Vec<usize>,
+    // (row, column, digit)
+    pub eliminated: Vec<(usize, usize, usize)>,
+    pub placed: Vec<(usize, usize, usize)>,
+    // for lookahead techniques, how each assumption played out, e.g.
+    // "r1c1=3 -> r1c5=7 -> row 2 has no square left for 4"
+    pub chain: Vec<String>,
+}
+
+fn cell_name(row: usize, column: usize) -> String {
+    format!("r{}c{}", row, column)
+}
+
+fn join<T, F: Fn(&T) -> String>(list: &[T], f: F) -> String {
+    list.iter().map(f).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} in {}", self.technique,
+               join(&self.digits, |d| d.to_string()),
+               join(&self.cells, |(r,c)| cell_name(*r,*c)))?;
+        if let Some(unit) = self.unit {
+            write!(f, " ({})", unit)?;
+        }
+        if !self.placed.is_empty() {
+            write!(f, "; places {}", join(&self.placed, |(r,c,d)| format!("{}={}", cell_name(*r,*c), d)))?;
+        }
+        if !self.eliminated.is_empty() {
+            write!(f, "; eliminates {}", join(&self.eliminated, |(r,c,d)| format!("{} from {}", d, cell_name(*r,*c))))?;
+        }
+        if !self.chain.is_empty() {
+            write!(f, "; because {}", self.chain.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+// a JSON string literal, quotes included; chains quote constraint messages,
+// so anything may turn up in them
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl Step {
+    // the search placing a digit to see where it leads, see Solver::get_steps
+    pub fn guess(row: usize, column: usize, digit: usize) -> Self {
+        Step {
+            technique: Technique::Guessing,
+            unit: None,
+            cells: vec![(row, column)],
+            digits: vec![digit],
+            eliminated: vec![],
+            placed: vec![(row, column, digit)],
+            chain: vec![],
+        }
+    }
+    // the search taking a digit out again once guessing it failed
+    pub fn refuted(row: usize, column: usize, digit: usize, reason: &str) -> Self {
+        Step {
+            technique: Technique::Guessing,
+            unit: None,
+            cells: vec![(row, column)],
+            digits: vec![digit],
+            eliminated: vec![(row, column, digit)],
+            placed: vec![],
+            chain: vec![format!("{}={} -> {}", cell_name(row, column), digit, reason)],
+        }
+    }
+    // hand-rolled to keep the crate free of dependencies besides rand
+    pub fn to_json(&self) -> String {
+        let unit = match self.unit {
+            Some(unit) => json_string(&unit.to_string()),
+            None => "null".to_string(),
+        };
+        // only lookahead steps have a chain, the others leave it out
+        let chain = match self.chain.is_empty() {
+            true => "".to_string(),
+            false => format!(",\"chain\":[{}]", self.chain.iter().map(|link| json_string(link)).collect::<Vec<String>>().join(",")),
+        };
+        format!("{{\"technique\":{},\"unit\":{},\"cells\":[{}],\"digits\":[{}],\"eliminated\":[{}],\"placed\":[{}]{}}}",
+                json_string(&self.technique.to_string()),
+                unit,
+                self.cells.iter().map(|(r,c)| format!("[{},{}]", r, c)).collect::<Vec<String>>().join(","),
+                self.digits.iter().map(|d| d.to_string()).collect::<Vec<String>>().join(","),
+                self.eliminated.iter().map(|(r,c,d)| format!("[{},{},{}]", r, c, d)).collect::<Vec<String>>().join(","),
+                self.placed.iter().map(|(r,c,d)| format!("[{},{},{}]", r, c, d)).collect::<Vec<String>>().join(","),
+                chain)
+    }
+}
+
+pub fn steps_to_json(steps: &[Step]) -> String {
+    let steps: Vec<String> = steps.iter().map(|step| format!("  {}", step.to_json())).collect();
+    match steps.is_empty() {
+        true => "[]".to_string(),
+        false => format!("[\n{}\n]", steps.join(",\n")),
+    }
+}
+
+// candidates of the squares a deduction may change, taken before it runs
+pub struct Snapshot {
+    affected: Vec<(usize, usize)>,
+    bitmaps: Vec<usize>,
+}
+
+// the single simplest deduction available, see Solver::next_hint
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hint {
+    // step.placed holds exactly the one digit to place
+    Place(Step),
+    // no digit can be placed yet, but the step removes candidates
+    Eliminate(Step),
+    GuessNeeded,
+    Solved,
+}
+impl fmt::Display for Hint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Hint::Place(step) | Hint::Eliminate(step) => write!(f, "{}", step),
+            Hint::GuessNeeded => write!(f, "no logical move left, a guess is needed"),
+            Hint::Solved => write!(f, "the puzzle is already solved"),
+        }
+    }
+}
+impl Hint {
+    pub fn to_json(&self) -> String {
+        match self {
+            Hint::Place(step) => format!("{{\"hint\":\"place\",\"step\":{}}}", step.to_json()),
+            Hint::Eliminate(step) => format!("{{\"hint\":\"eliminate\",\"step\":{}}}", step.to_json()),
+            Hint::GuessNeeded => "{\"hint\":\"guess\"}".to_string(),
+            Hint::Solved => "{\"hint\":\"solved\"}".to_string(),
+        }
+    }
+}
+
+// kept by each constraint; recording is off unless asked for (see
+// Grader::with_recording), so the solver's hot path only pays for a bool check
+#[derive(Default)]
+pub struct Recorder {
+    is_recording: bool,
+    steps: Vec<Step>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Recorder::default()
+    }
+    pub fn set_recording(&mut self, is_recording: bool) {
+        self.is_recording = is_recording;
+    }
+    // None when not recording; affected is only called when recording
+    pub fn snapshot<F>(&self, puzzle: &mut SudokuPuzzle, affected: F) -> Option<Snapshot>
+        where F: FnOnce() -> Vec<(usize, usize)>
+    {
+        if !self.is_recording {
+            return None;
+        }
+        let affected = affected();
+        let bitmaps = affected.iter().map(|(r,c)| puzzle.get_square(*r,*c).get_bitmap()).collect();
+        Some(Snapshot { affected, bitmaps })
+    }
+    // compares the affected squares against the snapshot and keeps a step if
+    // anything changed
+    pub fn record(
+        &mut self,
+        technique: Technique,
+        unit: Option<Unit>,
+        cells: Vec<(usize, usize)>,
+        digits: Vec<usize>,
+        puzzle: &mut SudokuPuzzle,
+        snapshot: Option<Snapshot>,
+    ) {
+        self.record_with_chain(technique, unit, cells, digits, vec![], puzzle, snapshot);
+    }
+    // record, keeping how the lookahead got there, see Step::chain
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_with_chain(
+        &mut self,
+        technique: Technique,
+        unit: Option<Unit>,
+        cells: Vec<(usize, usize)>,
+        digits: Vec<usize>,
+        chain: Vec<String>,
+        puzzle: &mut SudokuPuzzle,
+        snapshot: Option<Snapshot>,
+    ) {
+        let Snapshot { affected, bitmaps: before } = match snapshot {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+        let mut eliminated = vec![];
+        let mut placed = vec![];
+        for (k, (r,c)) in affected.iter().enumerate() {
+            let after = puzzle.get_square(*r,*c).get_bitmap();
+            if after == before[k] {
+                continue;
+            }
+            // a placement implies its eliminations, so only list one or the other
+            match puzzle.get_square(*r,*c).has_single_value() {
+                true => placed.push((*r, *c, puzzle.get_square(*r,*c).get_single_value())),
+                false => squares::bitmap_values(before[k] & !after)
+                    .into_iter()
+                    .for_each(|d| eliminated.push((*r, *c, d))),
+            }
+        }
+        if eliminated.is_empty() && placed.is_empty() {
+            return;
+        }
+        self.steps.push(Step { technique, unit, cells, digits, eliminated, placed, chain });
+    }
+    pub fn take_steps(&mut self) -> Vec<Step> {
+        std::mem::take(&mut self.steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_puzzle() -> SudokuPuzzle {
+        SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&".".repeat(81)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn recorder_keeps_only_changes() {
+        let mut puzzle = empty_puzzle();
+        let mut recorder = Recorder::new();
+        let affected = || vec![(1,2), (1,3)];
+
+        // not recording: nothing is kept
+        let snapshot = recorder.snapshot(&mut puzzle, affected);
+        assert!(snapshot.is_none());
+        puzzle.get_square(1,2).eliminate(5);
+        recorder.record(Technique::NakedSingle, None, vec![(1,1)], vec![5], &mut puzzle, snapshot);
+        assert!(recorder.take_steps().is_empty());
+
+        recorder.set_recording(true);
+        let snapshot = recorder.snapshot(&mut puzzle, affected);
+        recorder.record(Technique::NakedSingle, None, vec![(1,1)], vec![5], &mut puzzle, snapshot);
+        assert!(recorder.take_steps().is_empty());
+
+        let snapshot = recorder.snapshot(&mut puzzle, affected);
+        puzzle.get_square(1,3).eliminate(5);
+        recorder.record(Technique::NakedSingle, Some(Unit::Row(1)), vec![(1,1)], vec![5], &mut puzzle, snapshot);
+        let steps = recorder.take_steps();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].eliminated, vec![(1,3,5)]);
+        assert!(steps[0].placed.is_empty());
+        assert!(recorder.take_steps().is_empty());
+    }
+
+    #[test]
+    fn text_and_json() {
+        let step = Step {
+            technique: Technique::HiddenSingle,
+            unit: Some(Unit::Box(2)),
+            cells: vec![(3,5)],
+            digits: vec![7],
+            eliminated: vec![(3,6,7), (3,8,7)],
+            placed: vec![(3,5,7)],
+            chain: vec![],
+        };
+        assert_eq!(format!("{}", step),
+                   "hidden single: 7 in r3c5 (box 2); places r3c5=7; eliminates 7 from r3c6, 7 from r3c8");
+        assert_eq!(step.to_json(),
+                   "{\"technique\":\"hidden single\",\"unit\":\"box 2\",\"cells\":[[3,5]],\"digits\":[7],\"eliminated\":[[3,6,7],[3,8,7]],\"placed\":[[3,5,7]]}");
+        assert_eq!(steps_to_json(&[]), "[]");
+        assert!(Hint::Place(step.clone()).to_json().starts_with("{\"hint\":\"place\",\"step\":{\"technique\""));
+        assert_eq!(Hint::GuessNeeded.to_json(), "{\"hint\":\"guess\"}");
+        assert_eq!(steps_to_json(&[step.clone(), step]).lines().count(), 4);
+    }
+
+    #[test]
+    fn chain_text_and_json() {
+        let step = Step {
+            technique: Technique::ForcingChain,
+            unit: None,
+            cells: vec![(1,1)],
+            digits: vec![1],
+            eliminated: vec![],
+            placed: vec![(1,1,2)],
+            chain: vec!["r1c1=1 -> r1c2=3 -> row 1 has no square left for 4".to_string()],
+        };
+        assert_eq!(format!("{}", step),
+                   "forcing chain: 1 in r1c1; places r1c1=2; because r1c1=1 -> r1c2=3 -> row 1 has no square left for 4");
+        assert!(step.to_json().ends_with(",\"chain\":[\"r1c1=1 -> r1c2=3 -> row 1 has no square left for 4\"]}"));
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("say \"no\" \\ then\nstop\u{1}"), "\"say \\\"no\\\" \\\\ then\\nstop\\u0001\"");
+        let step = Step {
+            technique: Technique::ForcingChain,
+            unit: None,
+            cells: vec![(1,1)],
+            digits: vec![1],
+            eliminated: vec![],
+            placed: vec![],
+            chain: vec!["a \"quoted\" \\ link".to_string()],
+        };
+        assert!(step.to_json().ends_with(",\"chain\":[\"a \\\"quoted\\\" \\\\ link\"]}"));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::guess_ordering::SimpleGuessOrderingByTuple;
+use crate::guess_ordering::seeded_rng;
+use crate::guess_ordering::random_seed;
+use crate::solver::Solver;
+use crate::config::Settings;
+use crate::grid::Grid;
+use crate::grader::Grader;
+use crate::sudoku_puzzle::SudokuPuzzle;
+use rand::Rng;
+use rand::rngs::StdRng;
+use std::fmt;
+
+// the generator only makes 9x9 puzzles: on 16x16 and larger boards the
+// uniqueness check after every removed given takes far too long
+const BOX_SIZE: usize = 3;
+const SIZE: usize = BOX_SIZE * BOX_SIZE;
+// so at most this many givens
+pub const SQUARES: usize = SIZE * SIZE;
+// when aiming for a difficulty band, give up after this many grids
+const MAX_ATTEMPTS: usize = 100;
+const ORDERING: (usize, usize, usize, usize, usize, usize, usize, usize) = (2,3,4,5,6,7,8,9);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symmetry {
+    None,
+    // (r,c) and (8-r,8-c) are removed together, 0-based
+    Rotational,
+    // (r,c) and (r,8-c) are removed together, 0-based
+    Mirror,
+}
+impl Symmetry {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(Symmetry::None),
+            "rotational" => Some(Symmetry::Rotational),
+            "mirror" => Some(Symmetry::Mirror),
+            _ => None,
+        }
+    }
+    // indexes are 0-80, row major
+    fn partners(&self, index: usize) -> Vec<usize> {
+        let (row, col) = (index / SIZE, index % SIZE);
+        let last = SIZE - 1;
+        let partner = match self {
+            Symmetry::None => index,
+            Symmetry::Rotational => (last - row) * SIZE + (last - col),
+            Symmetry::Mirror => row * SIZE + (last - col),
+        };
+        match partner == index {
+            true => vec![index],
+            false => vec![index, partner],
+        }
+    }
+}
+
+// difficulty is the grader's, on the 1-4 scale of Grade::difficulty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difficulty {
+    // singles only, hidden singles in one round at most
+    Easy,
+    // hidden singles, round after round
+    Medium,
+    // techniques rated 3, e.g. naked pairs
+    Hard,
+    // techniques rated 4, or no logic gets there and a search is needed
+    Fiendish,
+}
+impl Difficulty {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "easy" => Some(Difficulty::Easy),
+            "medium" => Some(Difficulty::Medium),
+            "hard" => Some(Difficulty::Hard),
+            "fiendish" => Some(Difficulty::Fiendish),
+            _ => None,
+        }
+    }
+    fn from_rating(difficulty: usize) -> Self {
+        match difficulty {
+            1 => Difficulty::Easy,
+            2 => Difficulty::Medium,
+            3 => Difficulty::Hard,
+            _ => Difficulty::Fiendish,
+        }
+    }
+    // generated puzzles have one solution, so the uniqueness techniques count
+    pub fn measure(grid: &Grid) -> Self {
+        let mut puzzle = SudokuPuzzle::from_grid(grid);
+        match Grader::new().with_uniqueness(true).grade(&mut puzzle) {
+            Ok(grade) => Difficulty::from_rating(grade.difficulty()),
+            Err(_) => Difficulty::Fiendish,
+        }
+    }
+}
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+            Difficulty::Fiendish => write!(f, "fiendish"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GeneratedPuzzle {
+    // 81-character lines, see SudokuPuzzle::parse_line
+    pub puzzle: String,
+    pub solution: String,
+    pub givens: usize,
+    pub difficulty: Difficulty,
+    // the generator's seed, see Generator::with_seed
+    pub seed: u64,
+}
+
+// makes 9x9 puzzles only, see SQUARES
+pub struct Generator {
+    symmetry: Symmetry,
+    target_givens: Option<usize>,
+    difficulty: Option<Difficulty>,
+    seed: Option<u64>,
+}
+
+// seed: a randomized search seeded with it, None: not randomized
+fn new_solver(grid: &Grid, seed: Option<u64>) -> Solver {
+    let mut ordering = SimpleGuessOrderingByTuple::new(ORDERING);
+    ordering.with_randomization(seed.is_some());
+    let mut settings = Settings::new();
+    settings.seed = seed;
+    let mut s = Solver::from_grid(grid, Box::new(ordering));
+    s.with_settings(settings);
+    s
+}
+
+fn is_unique(grid: &Grid) -> bool {
+    // running out of depth means uniqueness is unknown, so keep the given
+    matches!(new_solver(grid, None).count_solutions(2), Ok(1))
+}
+
+impl Generator {
+    pub fn new() -> Self {
+        Generator {
+            symmetry: Symmetry::None,
+            target_givens: None,
+            difficulty: None,
+            seed: None,
+        }
+    }
+    pub fn with_symmetry(&mut self, symmetry: Symmetry) -> &mut Self {
+        self.symmetry = symmetry;
+        self
+    }
+    // givens are removed until this many are left, or no more can be
+    // removed without losing uniqueness; never fewer. With symmetry, one
+    // more may be left when only pairs of givens are left to remove
+    pub fn with_target_givens(&mut self, target_givens: usize) -> &mut Self {
+        self.target_givens = Some(target_givens);
+        self
+    }
+    pub fn with_difficulty(&mut self, difficulty: Difficulty) -> &mut Self {
+        self.difficulty = Some(difficulty);
+        self
+    }
+    // the same seed generates the same puzzle; without one a random seed is
+    // picked, see GeneratedPuzzle::seed
+    pub fn with_seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // None only when a target difficulty was not reached within MAX_ATTEMPTS grids
+    pub fn generate(&self) -> Option<GeneratedPuzzle> {
+        let seed = self.seed.unwrap_or_else(random_seed);
+        let mut rng = seeded_rng(seed);
+        for _attempt in 0..MAX_ATTEMPTS {
+            let solution = Generator::random_solution(&mut rng);
+            let puzzle = self.remove_givens(&solution, &mut rng);
+            let difficulty = Difficulty::measure(&puzzle);
+            match self.difficulty {
+                Some(target) if target != difficulty => continue,
+                _ => (),
+            }
+            let puzzle = puzzle.givens_line();
+            return Some(GeneratedPuzzle {
+                givens: puzzle.chars().filter(|c| *c != '.').count(),
+                puzzle,
+                solution: solution.to_line(),
+                difficulty,
+                seed,
+            });
+        }
+        None
+    }
+
+    // boxes 1, 5 and 9 share no row or column, so any three permutations of
+    // 1-9 can seed them; the randomized search fills in the rest
+    fn random_solution(rng: &mut StdRng) -> Grid {
+        let mut grid = Grid::new(SIZE);
+        let digits: Vec<usize> = (1..=SIZE).collect();
+        for diagonal in 0..BOX_SIZE {
+            // top left square of the box
+            let first = diagonal * BOX_SIZE * (SIZE + 1);
+            let values = SimpleGuessOrderingByTuple::randomize_values(&digits, rng);
+            for (k, value) in values.iter().enumerate() {
+                grid.give(first + (k / BOX_SIZE) * SIZE + k % BOX_SIZE, *value);
+            }
+        }
+
+        let mut s = new_solver(&grid, Some(rng.gen()));
+        s.solve().expect("seeded boxes 1, 5 and 9 always have a solution");
+        let solved = s.get_grid();
+        let mut solution = Grid::new(SIZE);
+        (0..SQUARES).for_each(|index| solution.give(index, solved.get_single_value(index).unwrap()));
+        solution
+    }
+
+    fn remove_givens(&self, solution: &Grid, rng: &mut StdRng) -> Grid {
+        let mut grid = solution.clone();
+        let mut givens = SQUARES;
+        let indexes: Vec<usize> = (0..SQUARES).collect();
+        for index in SimpleGuessOrderingByTuple::randomize_values(&indexes, rng) {
+            if self.target_givens.is_some_and(|target| givens <= target) {
+                break;
+            }
+            if !grid.is_given(index) {
+                continue;
+            }
+            let partners = self.symmetry.partners(index);
+            if self.target_givens.is_some_and(|target| givens - partners.len() < target) {
+                continue;
+            }
+            partners.iter().for_each(|i| grid.clear(*i));
+            match is_unique(&grid) {
+                true => givens -= partners.len(),
+                false => partners.iter().for_each(|i| grid.give(*i, solution.get_single_value(*i).unwrap())),
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::explain::Technique;
+
+    fn grid(line: &str) -> Grid {
+        SudokuPuzzle::new(SudokuPuzzle::parse_line(line).unwrap()).grid().clone()
+    }
+
+    #[test]
+    fn symmetry_partners() {
+        assert_eq!(Symmetry::None.partners(3), vec![3]);
+        assert_eq!(Symmetry::Rotational.partners(0), vec![0, 80]);
+        assert_eq!(Symmetry::Rotational.partners(40), vec![40]);
+        assert_eq!(Symmetry::Mirror.partners(9), vec![9, 17]);
+        assert_eq!(Symmetry::Mirror.partners(13), vec![13]);
+    }
+
+    #[test]
+    fn difficulty_bands() {
+        assert_eq!(Difficulty::from_rating(Technique::HiddenSingle.difficulty()), Difficulty::Easy);
+        assert_eq!(Difficulty::from_rating(2), Difficulty::Medium);
+        assert_eq!(Difficulty::from_rating(Technique::NakedPair.difficulty()), Difficulty::Hard);
+        assert_eq!(Difficulty::from_rating(Technique::Guessing.difficulty()), Difficulty::Fiendish);
+        // the same on every run, whatever the guess ordering
+        let hardest = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle).grid().clone();
+        assert_eq!(Difficulty::measure(&hardest), Difficulty::Fiendish);
+        // sjm20200425, rated 2/4: hidden singles in more than one round
+        let mercury = grid("7...12.89..8.57.....1.6....8......91......6..12......4....2.7.....64.2..43.17...6");
+        assert_eq!(Difficulty::measure(&mercury), Difficulty::Medium);
+        assert_eq!(Difficulty::parse("fiendish"), Some(Difficulty::Fiendish));
+        assert_eq!(Difficulty::parse("trivial"), None);
+    }
+
+    #[test]
+    fn generate_symmetric_unique_puzzle() {
+        let mut generator = Generator::new();
+        generator.with_symmetry(Symmetry::Rotational).with_target_givens(40).with_seed(1);
+        let generated = generator.generate().unwrap();
+
+        assert_eq!(generated.givens, 40);
+        assert!(!generated.solution.contains('.'));
+        assert!(is_unique(&grid(&generated.puzzle)));
+        let puzzle: Vec<char> = generated.puzzle.chars().collect();
+        let solution: Vec<char> = generated.solution.chars().collect();
+        for i in 0..81 {
+            // rotational symmetry, and every given agrees with the solution
+            assert_eq!(puzzle[i] == '.', puzzle[80 - i] == '.');
+            assert!(puzzle[i] == '.' || puzzle[i] == solution[i]);
+        }
+    }
+
+    #[test]
+    fn target_givens_are_never_undershot() {
+        // the center could not go, and taking away a pair from 41 would
+        // leave 39, so rotational symmetry stops one short of the target
+        let generated = Generator::new().with_symmetry(Symmetry::Rotational).with_target_givens(40).with_seed(0).generate().unwrap();
+        assert_eq!(generated.givens, 41);
+        assert_ne!(generated.puzzle.chars().nth(40), Some('.'));
+
+        // without symmetry givens go one at a time, so the target is exact
+        let generated = Generator::new().with_target_givens(31).with_seed(0).generate().unwrap();
+        assert_eq!(generated.givens, 31);
+    }
+
+    #[test]
+    fn same_seed_same_puzzle() {
+        let mut generator = Generator::new();
+        generator.with_target_givens(45).with_seed(2024);
+        let first = generator.generate().unwrap();
+        let second = generator.generate().unwrap();
+        assert_eq!(first.seed, 2024);
+        assert_eq!(first.puzzle, second.puzzle);
+        assert_eq!(first.solution, second.solution);
+
+        let other = Generator::new().with_target_givens(45).with_seed(2025).generate().unwrap();
+        assert_ne!(other.solution, first.solution);
+    }
+}
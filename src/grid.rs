@@ -0,0 +1,405 @@
+use crate::squares;
+use crate::squares::MAX_SIZE;
+use crate::sudoku_puzzle::value_to_char;
+use std::fmt;
+use std::hash::Hash;
+
+// a candidate mask: value 1 is bit 0, see squares::bit. u16 holds every
+// value up to 16x16, only 25x25 needs u32
+pub trait Mask: Copy + Eq + Hash + fmt::Debug + Send + Sync {
+    fn from_bits(bits: usize) -> Self;
+    fn bits(self) -> usize;
+}
+impl Mask for u16 {
+    fn from_bits(bits: usize) -> Self {
+        bits as u16
+    }
+    fn bits(self) -> usize {
+        self as usize
+    }
+}
+impl Mask for u32 {
+    fn from_bits(bits: usize) -> Self {
+        bits as u32
+    }
+    fn bits(self) -> usize {
+        self as usize
+    }
+}
+
+// one board size as plain values: the candidates of every square as a
+// mask, row by row, plus which squares are givens (bit i % 64 of word
+// i / 64 for square i). Sized exactly to the board, so it is Copy and
+// cloning one is a memcpy of just that board: 184 bytes for 9x9
+//
+// Givens keep the value they were given (eliminating from or selecting in
+// one does nothing); squares are numbered 0 to SQUARES - 1, row major
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SizedGrid<M: Mask, const SQUARES: usize, const WORDS: usize> {
+    masks: [M; SQUARES],
+    givens: [u64; WORDS],
+}
+pub type Grid4 = SizedGrid<u16, 16, 1>;
+pub type Grid9 = SizedGrid<u16, 81, 2>;
+pub type Grid16 = SizedGrid<u16, 256, 4>;
+pub type Grid25 = SizedGrid<u32, 625, 10>;
+
+impl<M: Mask, const SQUARES: usize, const WORDS: usize> SizedGrid<M, SQUARES, WORDS> {
+    // squares per row, column and box
+    pub const SIZE: usize = SQUARES.isqrt();
+
+    // every square open, no givens
+    pub fn new() -> Self {
+        SizedGrid {
+            masks: [M::from_bits(squares::full_bitmap(Self::SIZE)); SQUARES],
+            givens: [0; WORDS],
+        }
+    }
+    pub fn get_bitmap(&self, index: usize) -> usize {
+        self.masks[index].bits()
+    }
+    pub fn is_given(&self, index: usize) -> bool {
+        self.givens[index / 64] & (1 << (index % 64)) != 0
+    }
+    pub fn count_remaining(&self, index: usize) -> usize {
+        self.get_bitmap(index).count_ones() as usize
+    }
+    // the value once only one is left, given or not
+    pub fn get_single_value(&self, index: usize) -> Option<usize> {
+        match self.count_remaining(index) {
+            1 => Some(self.get_bitmap(index).trailing_zeros() as usize + 1),
+            _ => None,
+        }
+    }
+    // makes the square a given; replaces whatever was there
+    pub fn give(&mut self, index: usize, value: usize) {
+        assert!((1..=Self::SIZE).contains(&value));
+        self.masks[index] = M::from_bits(squares::bit(value));
+        self.givens[index / 64] |= 1 << (index % 64);
+    }
+    // the square goes back to open with every value possible, given or not
+    pub fn clear(&mut self, index: usize) {
+        self.masks[index] = M::from_bits(squares::full_bitmap(Self::SIZE));
+        self.givens[index / 64] &= !(1 << (index % 64));
+    }
+    // takes the values in bitmap away from an open square; true if it lost any
+    pub fn bitmap_elimination(&mut self, index: usize, bitmap: usize) -> bool {
+        if self.is_given(index) {
+            return false;
+        }
+        let previous = self.get_bitmap(index);
+        self.masks[index] = M::from_bits(previous & !bitmap);
+        previous != self.get_bitmap(index)
+    }
+    // leaves an open square with value only, which it must still have; true
+    // if that changed it
+    pub fn select(&mut self, index: usize, value: usize) -> bool {
+        if self.is_given(index) {
+            return false;
+        }
+        let previous = self.get_bitmap(index);
+        if !(1..=Self::SIZE).contains(&value) || previous & squares::bit(value) == 0 {
+            panic!("cannot select");
+        }
+        self.masks[index] = M::from_bits(squares::bit(value));
+        previous != squares::bit(value)
+    }
+    // puts an open square's candidates back, when the solver backtracks
+    pub fn restore(&mut self, index: usize, bitmap: usize) {
+        if !self.is_given(index) {
+            self.masks[index] = M::from_bits(bitmap);
+        }
+    }
+}
+
+// a board of any supported size: the SizedGrid for it. 4x4 and 9x9 are
+// held inline, so cloning them never allocates; 16x16 and 25x25 are boxed,
+// so the rest of the boards do not pay for their size
+//
+// SudokuPuzzle keeps its squares in one, so this is what the constraints
+// and the solver's search work on, see squares::Square. Equal boards
+// compare and hash equal; boards of different sizes never compare equal
+#[derive(PartialEq, Eq, Hash)]
+pub enum Grid {
+    Four(Grid4),
+    Nine(Grid9),
+    Sixteen(Box<Grid16>),
+    TwentyFive(Box<Grid25>),
+}
+
+// runs the same code on whichever SizedGrid the Grid holds
+macro_rules! each_size {
+    ($grid:expr, $sized:ident => $body:expr) => {
+        match $grid {
+            Grid::Four($sized) => $body,
+            Grid::Nine($sized) => $body,
+            Grid::Sixteen($sized) => $body,
+            Grid::TwentyFive($sized) => $body,
+        }
+    };
+}
+
+impl Grid {
+    // every square open, no givens
+    pub fn new(size: usize) -> Self {
+        match size {
+            4 => Grid::Four(Grid4::new()),
+            9 => Grid::Nine(Grid9::new()),
+            16 => Grid::Sixteen(Box::new(Grid16::new())),
+            25 => Grid::TwentyFive(Box::new(Grid25::new())),
+            _ => panic!("no {}x{} boards, the sizes go up to {}", size, size, MAX_SIZE),
+        }
+    }
+    pub fn size(&self) -> usize {
+        match self {
+            Grid::Four(_) => Grid4::SIZE,
+            Grid::Nine(_) => Grid9::SIZE,
+            Grid::Sixteen(_) => Grid16::SIZE,
+            Grid::TwentyFive(_) => Grid25::SIZE,
+        }
+    }
+    pub fn get_bitmap(&self, index: usize) -> usize {
+        each_size!(self, grid => grid.get_bitmap(index))
+    }
+    pub fn is_given(&self, index: usize) -> bool {
+        each_size!(self, grid => grid.is_given(index))
+    }
+    pub fn count_remaining(&self, index: usize) -> usize {
+        each_size!(self, grid => grid.count_remaining(index))
+    }
+    // see SizedGrid for these
+    pub fn get_single_value(&self, index: usize) -> Option<usize> {
+        each_size!(self, grid => grid.get_single_value(index))
+    }
+    pub fn give(&mut self, index: usize, value: usize) {
+        each_size!(self, grid => grid.give(index, value))
+    }
+    pub fn clear(&mut self, index: usize) {
+        each_size!(self, grid => grid.clear(index))
+    }
+    pub fn bitmap_elimination(&mut self, index: usize, bitmap: usize) -> bool {
+        each_size!(self, grid => grid.bitmap_elimination(index, bitmap))
+    }
+    pub fn select(&mut self, index: usize, value: usize) -> bool {
+        each_size!(self, grid => grid.select(index, value))
+    }
+    pub fn restore(&mut self, index: usize, bitmap: usize) {
+        each_size!(self, grid => grid.restore(index, bitmap))
+    }
+    // as SudokuPuzzle::to_line
+    pub fn to_line(&self) -> String {
+        let size = self.size();
+        (0..size * size)
+            .map(|index| match self.get_single_value(index) {
+                Some(value) => value_to_char(value, size),
+                None => '.',
+            })
+            .collect()
+    }
+    // just the givens, as the line a puzzle would be written down as
+    pub fn givens_line(&self) -> String {
+        let size = self.size();
+        (0..size * size)
+            .map(|index| match self.is_given(index) {
+                true => value_to_char(self.get_single_value(index).unwrap(), size),
+                false => '.',
+            })
+            .collect()
+    }
+}
+
+impl Clone for Grid {
+    fn clone(&self) -> Self {
+        match self {
+            Grid::Four(grid) => Grid::Four(*grid),
+            Grid::Nine(grid) => Grid::Nine(*grid),
+            Grid::Sixteen(grid) => Grid::Sixteen(grid.clone()),
+            Grid::TwentyFive(grid) => Grid::TwentyFive(grid.clone()),
+        }
+    }
+    // copies into the boxed board already there, if it is the same size
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (Grid::Sixteen(grid), Grid::Sixteen(from)) => **grid = **from,
+            (Grid::TwentyFive(grid), Grid::TwentyFive(from)) => **grid = **from,
+            (grid, from) => *grid = from.clone(),
+        }
+    }
+}
+impl fmt::Debug for Grid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = self.size();
+        let masks: Vec<usize> = (0..size * size).map(|index| self.get_bitmap(index)).collect();
+        f.debug_struct("Grid")
+            .field("size", &size)
+            .field("givens", &self.givens_line())
+            .field("masks", &masks)
+            .finish()
+    }
+}
+
+// one flag per square, for boards of any size, without allocating
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SquareSet {
+    words: [u64; (MAX_SIZE * MAX_SIZE).div_ceil(64)],
+}
+impl SquareSet {
+    pub fn new() -> Self {
+        SquareSet::default()
+    }
+    pub fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+    pub fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+    pub fn remove(&mut self, index: usize) {
+        self.words[index / 64] &= !(1 << (index % 64));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample_puzzles;
+    use crate::sudoku_puzzle::SudokuPuzzle;
+    use crate::solver::Solver;
+    use crate::guess_ordering::SimpleGuessOrderingByTuple;
+    use std::collections::HashSet;
+
+    fn sample() -> SudokuPuzzle {
+        SudokuPuzzle::new(sample_puzzles::get_by_id("worldshardest").unwrap().puzzle)
+    }
+
+    #[test]
+    fn lines() {
+        let puzzle = sample();
+        let grid = puzzle.grid().clone();
+        let line = puzzle.to_line();
+        assert_eq!(grid.to_line(), line);
+        assert_eq!(grid.givens_line(), line);
+        let given = line.find(|c| c != '.').unwrap();
+        assert!(grid.is_given(given));
+        assert_eq!(grid.count_remaining(line.find('.').unwrap()), 9);
+    }
+
+    #[test]
+    fn square_semantics() {
+        let mut grid = Grid::new(9);
+        let (given, open) = (0, 80);
+        grid.give(given, 5);
+        // givens ignore eliminate, select and restore
+        assert!(!grid.bitmap_elimination(given, squares::bit(5)));
+        assert!(!grid.select(given, 3));
+        grid.restore(given, 0);
+        assert_eq!(grid.get_single_value(given), Some(5));
+        assert_eq!(grid.get_bitmap(given), squares::bit(5));
+
+        assert!(grid.bitmap_elimination(open, squares::bit(2)));
+        assert!(!grid.bitmap_elimination(open, squares::bit(2)));
+        assert_eq!(grid.count_remaining(open), 8);
+        assert!(!grid.is_given(open));
+        assert!(grid.select(open, 7));
+        assert!(!grid.select(open, 7));
+        assert_eq!(grid.get_single_value(open), Some(7));
+        grid.restore(open, squares::full_bitmap(9));
+        assert_eq!(grid.count_remaining(open), 9);
+        assert_eq!(grid.givens_line().chars().filter(|c| *c != '.').count(), 1);
+
+        grid.clear(given);
+        assert!(!grid.is_given(given));
+        assert_eq!(grid.count_remaining(given), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot select")]
+    fn select_eliminated() {
+        let mut grid = Grid::new(9);
+        grid.bitmap_elimination(0, squares::bit(4));
+        grid.select(0, 4);
+    }
+
+    #[test]
+    fn every_size() {
+        // the last square of a 25x25 board, and its last value
+        let mut grid = Grid::new(25);
+        grid.give(624, 25);
+        assert!(grid.is_given(624));
+        assert_eq!(grid.get_single_value(624), Some(25));
+        assert_eq!(grid.count_remaining(623), 25);
+        assert_eq!(grid.to_line(), format!("{}Y", ".".repeat(624)));
+
+        let line = format!("0F.A{}", ".".repeat(252));
+        let puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line(&line).unwrap());
+        assert_eq!(puzzle.grid().size(), 16);
+        assert_eq!(puzzle.grid().givens_line(), line);
+        assert_eq!(SudokuPuzzle::from_grid(puzzle.grid()).to_line(), line);
+    }
+
+    #[test]
+    fn copies_compare_and_hash() {
+        let mut puzzle = sample();
+        let before = puzzle.grid().clone();
+        puzzle.get_square(1,2).eliminate(3);
+        let after = puzzle.grid().clone();
+        assert_ne!(after, before);
+        assert_eq!(after.get_bitmap(1), puzzle.get_square(1,2).get_bitmap());
+        assert_eq!(SudokuPuzzle::from_grid(&after).grid(), &after);
+
+        // equal boards hash equal; boards of other sizes never compare equal
+        let states: HashSet<Grid> = vec![before.clone(), after, before, Grid::new(4)].into_iter().collect();
+        assert_eq!(states.len(), 3);
+        assert_ne!(Grid::new(4), Grid::new(9));
+    }
+
+    #[test]
+    fn sized_to_the_board() {
+        fn copy<T: Copy>(grid: T) -> T {
+            grid
+        }
+        // 81 u16 masks and two words of givens, and no bigger inline
+        assert_eq!(std::mem::size_of::<Grid9>(), 184);
+        assert!(std::mem::size_of::<Grid>() <= 192);
+        assert_eq!(std::mem::size_of::<Grid4>(), 40);
+        assert_eq!(Grid9::SIZE, 9);
+        assert_eq!(Grid25::SIZE, 25);
+        let mut nine = Grid9::new();
+        nine.give(80, 9);
+        assert_eq!(copy(nine), nine);
+        assert_eq!(Grid::Nine(nine).to_line(), format!("{}9", ".".repeat(80)));
+
+        // clone_from reuses the box of a board the same size
+        let mut sixteen = Grid::new(16);
+        let mut other = Grid::new(16);
+        other.give(255, 16);
+        let boxed = match &sixteen {
+            Grid::Sixteen(grid) => &**grid as *const Grid16,
+            _ => unreachable!(),
+        };
+        sixteen.clone_from(&other);
+        assert_eq!(sixteen, other);
+        assert!(matches!(&sixteen, Grid::Sixteen(grid) if std::ptr::eq(&**grid, boxed)));
+        sixteen.clone_from(&Grid::new(4));
+        assert_eq!(sixteen.size(), 4);
+    }
+
+    #[test]
+    fn square_sets() {
+        let mut set = SquareSet::new();
+        set.insert(0);
+        set.insert(624);
+        assert!(set.contains(0) && set.contains(624) && !set.contains(64));
+        set.remove(624);
+        assert_eq!(set, { let mut only = SquareSet::new(); only.insert(0); only });
+    }
+
+    #[test]
+    fn solver_runs_on_grids() {
+        let grid = sample().grid().clone();
+        let mut s = Solver::from_grid(&grid, Box::new(SimpleGuessOrderingByTuple::new((2,3,4,5,6,7,8,9))));
+        s.solve().unwrap();
+        let solved = s.get_grid();
+        assert!(!solved.to_line().contains('.'));
+        assert_eq!(solved.givens_line(), grid.givens_line());
+    }
+}
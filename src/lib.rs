@@ -0,0 +1,38 @@
+#![cfg_attr(test, allow(clippy::assertions_on_constants))]
+// the types here are built with new() and the with_* builders, never
+// Default; squares' clone() hands back a boxed Square, not Self
+#![allow(clippy::new_without_default, clippy::should_implement_trait)]
+
+// the solver as a library; main.rs is the command line on top of it. A
+// custom constraint implements Constraint and goes into a Pipeline, which
+// Settings hands to the Solver:
+//
+//     let mut settings = Settings::new();
+//     settings.pipeline = Pipeline::standard().with_constraint(|_| Box::new(MyConstraint::new()));
+//     solver.with_settings(settings);
+
+pub mod squares;
+pub mod guess_ordering;
+pub mod solver;
+pub mod sudoku_puzzle;
+pub mod constraint;
+mod test;
+pub mod debug;
+pub mod sample_puzzles;
+pub mod config;
+pub mod stopwatch;
+pub mod cli;
+pub mod batch;
+pub mod generator;
+pub mod grader;
+pub mod explain;
+pub mod pipeline;
+pub mod grid;
+
+pub use crate::constraint::Constraint;
+pub use crate::constraint::ConstraintViolation;
+pub use crate::pipeline::Pipeline;
+pub use crate::config::Settings;
+pub use crate::solver::Solver;
+pub use crate::solver::QueueItem;
+pub use crate::sudoku_puzzle::SudokuPuzzle;
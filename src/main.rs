@@ -1,19 +1,26 @@
-mod squares;
-mod guess_ordering;
-mod solver;
-mod sudoku_puzzle;
-mod constraint;
-mod test;
-mod debug;
-mod sample_puzzles;
-mod config;
-mod stopwatch;
+use sudoku_solver::guess_ordering;
+use sudoku_solver::solver;
+use sudoku_solver::sudoku_puzzle;
+use sudoku_solver::debug;
+use sudoku_solver::sample_puzzles;
+use sudoku_solver::config;
+use sudoku_solver::stopwatch;
+use sudoku_solver::cli;
+use sudoku_solver::batch;
+use sudoku_solver::generator;
+use sudoku_solver::grader;
+use sudoku_solver::explain;
 
 use std::collections::HashMap;
 use std::io;
+use std::io::Read;
 use sample_puzzles::Puzzle;
 use std::io::Write;
 use stopwatch::Stopwatch;
+use cli::Command;
+use cli::Options;
+use cli::PuzzleSource;
+use guess_ordering::GuessOrdering;
 
 #[derive(Clone)]
 enum Mode {
@@ -54,7 +61,7 @@ object model. Basically Square is done, perhaps Constraint should be next?
 
 */
 
-fn profile_strategy() {
+fn profile_strategy(options: &Options) -> bool {
     //
     // ProcessOfElimination takes a TON of time (but reduces guesses):
     //   e.g. 480ms w/ 100 guesses vs 229ms w/ 365 guesses
@@ -103,16 +110,56 @@ fn profile_strategy() {
     // 2,3,4,... w/ POE+++, 100x: 263ms, 94 guesses, 10 depth
     // 2,4,3,... w/ POE+++, 100x: 182ms, 91 guesses, 10 depth
     //
+    // subsets, locked candidates and fish are not in the default constraints;
+    // they run with --constraints advanced (POE off)
+    // 2,3,4,... default, 100x: 17ms, 293 guesses, 13 depth
+    // 2,4,3,... default, 100x: 17ms, 293 guesses, 13 depth
+    // 2,3,4,... w/ fish, 100x: 46ms, 52 guesses, 8 depth
+    // 2,4,3,... w/ fish, 100x: 41ms, 52 guesses, 8 depth
+    //
+    // wings, coloring and chains on top, tried in the solver queue; too slow
+    // for the guesses saved, so they stay in the grader
+    // 2,3,4,... w/ chains, 20x: 505ms, 34 guesses, 7 depth
+    // 2,3,4,... w/ wings+coloring, 20x: 255ms, 53 guesses, 8 depth
+    //
+    // unit-based queue: subsets and locked candidates once per changed unit
+    // and round, done_yet once per round (--constraints advanced)
+    // 2,3,4,... w/ units, 100x: 57ms, 50 guesses, 8 depth
+    // 2,4,3,... w/ units, 100x: 53ms, 53 guesses, 8 depth
+    //
     debug::debug("entering profile_strategy".to_string());
 
-    let number_of_trials: usize = 100;
-    let ordering_strategies = vec![
-        guess_ordering::SimpleGuessOrderingByTuple::new((2,3,4,5,6,7,8,9)),
-        guess_ordering::SimpleGuessOrderingByTuple::new((2,4,3,5,6,7,8,9)),
-        //guess_ordering::SimpleGuessOrderingByTuple::new((3,2,4,5,6,7,8,9)),
-        //guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9)),
-        //guess_ordering::SimpleGuessOrderingByTuple::new((5,4,3,2,6,7,8,9)),
-    ];
+    let number_of_trials: usize = options.number_of_trials;
+    let tuple = |ordering_tuple| {
+        let mut ordering_strategy = guess_ordering::SimpleGuessOrderingByTuple::new(ordering_tuple);
+        ordering_strategy.with_randomization(options.is_randomized);
+        Box::new(ordering_strategy) as Box<dyn GuessOrdering>
+    };
+    // any GuessOrdering will do; --strategy picks them by name
+    let ordering_strategies: Vec<Box<dyn GuessOrdering>> = match options.ordering {
+        _ if !options.strategies.is_empty() => options.guess_orderings(),
+        Some(ordering_tuple) => vec![tuple(ordering_tuple)],
+        None => vec![
+            tuple((2,3,4,5,6,7,8,9)),
+            tuple((2,4,3,5,6,7,8,9)),
+            //tuple((3,2,4,5,6,7,8,9)),
+            //tuple((4,3,2,5,6,7,8,9)),
+            //tuple((5,4,3,2,6,7,8,9)),
+        ],
+    };
+
+    let p = match sample_puzzles::get_by_id(&options.puzzle_id) {
+        Some(puzzle) => puzzle,
+        None => {
+            eprintln!("unknown sample puzzle id: {}", options.puzzle_id);
+            std::process::exit(2);
+        }
+    };
+    // trial t runs with seed + t, for every strategy alike; any trial can
+    // be replayed with solve --seed
+    let seed = options.settings.seed.unwrap_or_else(guess_ordering::random_seed);
+    println!("profiling {} ({} trials, seed {})", p.name, number_of_trials, seed);
+    let mut is_every_trial_solved = true;
 
     for ordering_strategy in ordering_strategies {
         // let ordering_strategy = guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
@@ -121,15 +168,15 @@ fn profile_strategy() {
         let mut elapsed_time_per_trial: Vec<u128> = vec![];
         let mut max_depth_per_trial: Vec<usize> = vec![];
         let mut constraint_times_map: HashMap<String, u128> = HashMap::new();
-
-        let p = match sample_puzzles::get_by_id("worldshardest") {
-            Some(puzzle) => puzzle,
-            None => panic!("invalid id!"),
-        };
+        // e.g. --max-depth cut the search short; reported after the trials
+        let mut failed_trials: Vec<String> = vec![];
 
         for trial in 0..number_of_trials {
             let sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(p.puzzle.clone());
             let mut s = solver::Solver::new(sudoku_puzzle, ordering_strategy.clone());
+            let mut settings = options.settings.clone();
+            settings.seed = Some(seed.wrapping_add(trial as u64));
+            s.with_settings(settings);
             // let time_start = SystemTime::now().duration_since(UNIX_EPOCH).expect("time went backwards");
             let mut stopwatch = Stopwatch::new();
             stopwatch.start();
@@ -155,12 +202,12 @@ fn profile_strategy() {
                             constraint_times_map.insert(key.to_string(), new_value_sum);
                         }
                     }
-                    ()
+
                 }
-                Err(e) => panic!("puzzle should not have errored: {}", e.msg),
+                Err(e) => failed_trials.push(format!("trial {} (seed {}): {}", trial, seed.wrapping_add(trial as u64), e)),
             }
             if trial == number_of_trials-1 {
-                println!("");
+                println!();
                 std::io::stdout().flush().unwrap();
             } else {
                 print!(".");
@@ -172,11 +219,15 @@ fn profile_strategy() {
         }
 
         println!("ordering strategy: {}", ordering_strategy.name());
-        let elapsed_time_total: u128 = elapsed_time_per_trial.iter().fold(0u128, |sum, val| sum + val);
+        failed_trials.iter().for_each(|failure| println!("failed {}", failure));
+        is_every_trial_solved &= failed_trials.is_empty();
+        // guesses and depth only count the trials that were solved
+        let solved_trials = guesses_per_trial.len().max(1);
+        let elapsed_time_total: u128 = elapsed_time_per_trial.iter().sum();
         let elapsed_time_avg: u128 = elapsed_time_total / number_of_trials as u128;
-        let guesses_avg: usize = guesses_per_trial.iter().fold(0usize, |sum, val| sum + val) / number_of_trials;
+        let guesses_avg: usize = guesses_per_trial.iter().sum::<usize>() / solved_trials;
         let max_depth_reached_avg: usize =
-            max_depth_per_trial.iter().fold(0usize, |sum, val| sum + val) / number_of_trials;
+            max_depth_per_trial.iter().sum::<usize>() / solved_trials;
         let mut elapsed_time_total_constraints = 0u128;
         for (k,v) in constraint_times_map {
             elapsed_time_total_constraints += v;
@@ -185,15 +236,15 @@ fn profile_strategy() {
         println!("total elapsed times: {} (per clock), {} (per constraints)",
                  elapsed_time_total,
                  elapsed_time_total_constraints);
-    
-        println!("avg time: {}ms, avg guesses: {}, avg max depth: {} ({} trials)", elapsed_time_avg, guesses_avg, max_depth_reached_avg, number_of_trials);
+
+        println!("avg time: {}ms, avg guesses: {}, avg max depth: {} ({} trials, {} failed)", elapsed_time_avg, guesses_avg, max_depth_reached_avg, number_of_trials, failed_trials.len());
         // println!("number of trials: {}", number_of_trials);
         // println!("total elapsed time: {}", elapsed_time_subtotal);
-
     }
+    is_every_trial_solved
 }
 
-fn run_one_puzzle_with_search(puzzle: &Puzzle) {
+fn run_one_puzzle_with_search(puzzle: &Puzzle, options: &Options) -> bool {
     debug::debug("entering run_one_puzzle_with_search".to_string());
 
     let sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(puzzle.puzzle.clone());
@@ -202,8 +253,9 @@ fn run_one_puzzle_with_search(puzzle: &Puzzle) {
 
     sudoku_puzzle.debug_print_puzzle();
 
-    let ordering_strategy = guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+    let ordering_strategy = options.guess_ordering();
     let mut s = solver::Solver::new(sudoku_puzzle, ordering_strategy);
+    s.with_settings(options.settings.clone());
     // s.with_progress(vec![]).with_progress(vec![]);
 
     //let time_reference = SystemTime::now();
@@ -211,24 +263,284 @@ fn run_one_puzzle_with_search(puzzle: &Puzzle) {
     let mut stopwatch = Stopwatch::new();
     stopwatch.start();
 
-    let result = s.solve();
+    let result = match options.check_uniqueness {
+        true => s.uniqueness().map(|uniqueness| {
+            println!("solutions: {}", uniqueness);
+            uniqueness != solver::Uniqueness::NoSolution
+        }),
+        false => s.solve().map(|_| true),
+    };
 
-    match result {
-        Ok(_) => println!("puzzle returned OK"),
-        Err(e) => println!("puzzle returned with error: {}", e.msg),
-    }
+    let is_solved = match result {
+        Ok(false) => {
+            println!("puzzle has no solution");
+            false
+        },
+        Ok(true) => {
+            println!("puzzle returned OK");
+            true
+        },
+        Err(e) => {
+            println!("puzzle returned with error: {}", e);
+            false
+        },
+    };
 
     // let time_end = SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards again");
     stopwatch.stop();
     // let elapsed_time = (time_end - time_start).as_millis();
     let elapsed_time = stopwatch.elapsed_millis();
-    println!("Profiling info: total elapsed time={}ms", elapsed_time);
+    println!("Profiling info: total elapsed time={}ms, guesses={}, max depth={}, seed={}",
+             elapsed_time, s.get_total_guesses(), s.get_max_depth_reached(), s.get_seed());
+
+    if options.settings.is_recording {
+        match options.is_json {
+            true => println!("{}", explain::steps_to_json(s.get_steps())),
+            false => s.get_steps()
+                .iter()
+                .enumerate()
+                .for_each(|(i, step)| println!("{:>3}. {}", i+1, step)),
+        }
+    }
 
     let solved_puzzle = s.get_puzzle();
     solved_puzzle.print_puzzle();
+    println!("{}", solved_puzzle.to_line());
+
+    is_solved
+}
+
+// propagation only, with the solver's constraints; prints the solution, or
+// the stuck grid with every square's candidates
+fn run_one_puzzle_without_search(puzzle: &Puzzle, options: &Options) -> bool {
+    debug::debug("entering run_one_puzzle_without_search".to_string());
+
+    let sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(puzzle.puzzle.clone());
+
+    println!("{}", puzzle.name);
+
+    let mut s = solver::Solver::new(sudoku_puzzle, options.guess_ordering());
+    s.with_settings(options.settings.clone());
+
+    match s.solve_without_search() {
+        Ok(outcome) => {
+            println!("{}", outcome);
+            match outcome.is_solved {
+                true => s.get_puzzle().print_puzzle(),
+                false => println!("{}", s.get_puzzle().candidates_text()),
+            }
+            println!("{}", s.get_puzzle().to_line());
+            true
+        },
+        Err(e) => {
+            println!("puzzle has no solution: {}", e);
+            false
+        },
+    }
+}
+
+// either the single-line format or one line per row, on any supported board
+// size, see SudokuPuzzle::parse_text
+fn read_puzzle(source: &PuzzleSource) -> Result<Puzzle, String> {
+    let (name, text) = match source {
+        PuzzleSource::Stdin => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|e| format!("cannot read stdin: {}", e))?;
+            ("stdin".to_string(), text)
+        },
+        PuzzleSource::File(path) => {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+            (path.clone(), text)
+        },
+    };
+
+    let rows = sudoku_puzzle::SudokuPuzzle::parse_text(&text).map_err(|e| format!("{}: {}", name, e))?;
+
+    Ok(Puzzle {
+        id: name.clone(),
+        name,
+        puzzle: rows,
+    })
+}
+
+fn read_puzzle_or_exit(source: &PuzzleSource) -> Puzzle {
+    match read_puzzle(source) {
+        Ok(puzzle) => puzzle,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn grade(puzzle: &Puzzle, options: &Options) -> bool {
+    let mut sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(puzzle.puzzle.clone());
+    match grader::Grader::new().with_uniqueness(options.settings.is_unique).grade(&mut sudoku_puzzle) {
+        Ok(grade) => {
+            println!("{}", grade);
+            println!("{}", grade.progress);
+            true
+        },
+        Err(e) => {
+            println!("puzzle has no solution: {}", e);
+            false
+        },
+    }
+}
+
+fn explain(puzzle: &Puzzle, options: &Options) -> bool {
+    let mut sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(puzzle.puzzle.clone());
+    let grade = match grader::Grader::new()
+        .with_recording(true)
+        .with_uniqueness(options.settings.is_unique)
+        .grade(&mut sudoku_puzzle) {
+        Ok(grade) => grade,
+        Err(e) => {
+            println!("puzzle has no solution: {}", e);
+            return false;
+        },
+    };
+    match options.is_json {
+        true => println!("{}", explain::steps_to_json(&grade.steps)),
+        false => {
+            grade.steps
+                .iter()
+                .enumerate()
+                .for_each(|(i, step)| println!("{:>3}. {}", i+1, step));
+            if grade.hardest == explain::Technique::Guessing {
+                println!("stuck: logic alone gets no further, a guess is needed");
+            }
+            println!("{}", grade);
+        },
+    }
+    true
+}
+
+fn hint(puzzle: &Puzzle, options: &Options) -> bool {
+    let sudoku_puzzle = sudoku_puzzle::SudokuPuzzle::new(puzzle.puzzle.clone());
+    match solver::Solver::next_hint(&sudoku_puzzle, &options.settings) {
+        Ok(hint) => {
+            match options.is_json {
+                true => println!("{}", hint.to_json()),
+                false => println!("{}", hint),
+            }
+            true
+        },
+        Err(e) => {
+            println!("the grid contains a mistake: {}", e);
+            false
+        },
+    }
+}
+
+fn run_batch(source: &PuzzleSource, options: &Options) {
+    let stdout = io::stdout();
+    let mut output = io::BufWriter::new(stdout.lock());
+    let result = match source {
+        PuzzleSource::Stdin => {
+            let stdin = io::stdin();
+            let input = stdin.lock();
+            batch::solve_batch(input, &mut output, options.guess_ordering().as_ref(), &options.settings, options.check_uniqueness)
+        },
+        PuzzleSource::File(path) => match std::fs::File::open(path) {
+            Ok(file) => batch::solve_batch(io::BufReader::new(file), &mut output, options.guess_ordering().as_ref(), &options.settings, options.check_uniqueness),
+            Err(e) => {
+                eprintln!("error: cannot read {}: {}", path, e);
+                std::process::exit(2);
+            }
+        },
+    };
+    match result.and_then(|summary| output.flush().map(|_| summary)) {
+        Ok(summary) => eprintln!("{}", summary),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn generate(options: &Options) {
+    let mut generator = generator::Generator::new();
+    generator.with_symmetry(options.symmetry);
+    if let Some(target_givens) = options.target_givens {
+        generator.with_target_givens(target_givens);
+    }
+    if let Some(difficulty) = options.difficulty {
+        generator.with_difficulty(difficulty);
+    }
+    if let Some(seed) = options.settings.seed {
+        generator.with_seed(seed);
+    }
+    match generator.generate() {
+        Some(generated) => {
+            println!("{}", generated.puzzle);
+            eprintln!("givens: {}, difficulty: {}, seed: {}", generated.givens, generated.difficulty, generated.seed);
+            eprintln!("solution: {}", generated.solution);
+        },
+        None => {
+            eprintln!("error: no puzzle of the requested difficulty was found, try again or relax --givens");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn list_samples() {
+    sample_puzzles::get_all()
+        .iter()
+        .for_each(|puzzle| println!("{:<24} {}", puzzle.id, puzzle.name));
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(msg) => {
+            eprintln!("error: {}", msg);
+            eprint!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Interactive => run_interactive(),
+        Command::Help => print!("{}", cli::USAGE),
+        Command::ListSamples => list_samples(),
+        Command::Profile(options) => {
+            if !profile_strategy(&options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Solve(source, options) => {
+            if !run_one_puzzle_with_search(&read_puzzle_or_exit(&source), &options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Logic(source, options) => {
+            if !run_one_puzzle_without_search(&read_puzzle_or_exit(&source), &options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Grade(source, options) => {
+            if !grade(&read_puzzle_or_exit(&source), &options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Hint(source, options) => {
+            if !hint(&read_puzzle_or_exit(&source), &options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Explain(source, options) => {
+            if !explain(&read_puzzle_or_exit(&source), &options) {
+                std::process::exit(1);
+            }
+        },
+        Command::Batch(source, options) => run_batch(&source, &options),
+        Command::Generate(options) => generate(&options),
+    }
+}
+
+fn run_interactive() {
     println!("Welcome to SudokuSolver! Please make mode selection");
 
     let sample_puzzle = match sample_puzzles::get_by_id(config::SAMPLE_PUZZLE_ID) {
@@ -238,10 +550,10 @@ fn main() {
 
     let puzzle_name = &sample_puzzle.name;
 
-    println!("");
+    println!();
     println!("selected sudoku: {}", puzzle_name);
     println!("  1) solve sudoku");
-    println!("  2) run solver with search disabled (under construction)");
+    println!("  2) run solver with search disabled");
     println!(r##"  3) run profiler against World's hardest Sudoku (under construction)"##);
 
     loop {
@@ -269,9 +581,9 @@ fn main() {
         match choice_map.get(&choice) {
             Some(mode) => {
                 match mode {
-                    Mode::Search => run_one_puzzle_with_search(&sample_puzzle),
-                    Mode::RunWithoutSearch => println!("run without search not implemented"),
-                    Mode::ProfileWorldsHardest => profile_strategy(),
+                    Mode::Search => { run_one_puzzle_with_search(&sample_puzzle, &Options::new()); },
+                    Mode::RunWithoutSearch => { run_one_puzzle_without_search(&sample_puzzle, &Options::new()); },
+                    Mode::ProfileWorldsHardest => { profile_strategy(&Options::new()); },
                 }
             },
             None => continue,
@@ -0,0 +1,254 @@
+use crate::config::Settings;
+use crate::constraint::Constraint;
+use crate::constraint::NoRowDuplicates;
+use crate::constraint::NoColumnDuplicates;
+use crate::constraint::NoBoxDuplicates;
+use crate::constraint::ProcessOfElimination;
+use crate::constraint::AllCannotBeEliminated;
+use crate::constraint::DoubleDoubleRow;
+use crate::constraint::DoubleDoubleColumn;
+use crate::constraint::DoubleDoubleBox;
+use crate::constraint::LockedCandidates;
+use crate::constraint::NakedSubsets;
+use crate::constraint::HiddenSubsets;
+use crate::constraint::Fish;
+use crate::constraint::XYWing;
+use crate::constraint::XYZWing;
+use crate::constraint::SimpleColoring;
+use crate::constraint::XChain;
+use crate::constraint::XYChain;
+use crate::constraint::UniqueRectangle;
+use crate::constraint::BugPlusOne;
+use crate::constraint::ForcingChains;
+
+use std::fmt;
+use std::sync::Arc;
+
+// makes a fresh constraint; constraints keep timings and memos, so every
+// solver builds its own
+pub type ConstraintFactory = Arc<dyn Fn(&Settings) -> Box<dyn Constraint> + Send + Sync>;
+
+// the constraints a solver runs on every queue item, in order; lives in
+// Settings so whatever makes a solver passes it along with the rest
+#[derive(Clone)]
+pub struct Pipeline {
+    factories: Vec<ConstraintFactory>,
+}
+
+// the constraints that can be picked by name (case-insensitive), e.g. with
+// --constraints on the command line
+pub const CONSTRAINT_NAMES: [&str; 22] = [
+    "NoRowDuplicates",
+    "NoColumnDuplicates",
+    "NoBoxDuplicates",
+    "ProcessOfElimination",
+    "AllCannotBeEliminated",
+    "DoubleDoubleRow",
+    "DoubleDoubleColumn",
+    "DoubleDoubleBox",
+    "LockedCandidates",
+    "NakedSubsets",
+    "HiddenSubsets",
+    "Fish",
+    "XYWing",
+    "XYZWing",
+    "SimpleColoring",
+    "XChain",
+    "XYChain",
+    "UniqueRectangle",
+    "BugPlusOne",
+    "ForcingChains",
+    "Standard",
+    "Advanced",
+];
+
+impl Pipeline {
+    // an empty pipeline, see with_constraint
+    pub fn new() -> Self {
+        Pipeline { factories: vec![] }
+    }
+    // what Solver::new runs; POE follows Settings::is_poe_enabled, the
+    // uniqueness constraints Settings::is_unique, so by default it does what
+    // the solver always did
+    pub fn standard() -> Self {
+        Pipeline::new()
+            .with_constraint(|_| Box::new(NoRowDuplicates::new()))
+            .with_constraint(|_| Box::new(NoColumnDuplicates::new()))
+            .with_constraint(|_| Box::new(NoBoxDuplicates::new()))
+            .with_constraint(|settings| Box::new(ProcessOfElimination::new().with_enabled(settings.is_poe_enabled)))
+            .with_constraint(|_| Box::new(AllCannotBeEliminated::new()))
+            .with_constraint(|_| Box::new(DoubleDoubleRow::new()))
+            .with_constraint(|_| Box::new(DoubleDoubleColumn::new()))
+            .with_constraint(|_| Box::new(DoubleDoubleBox::new()))
+            .with_constraint(|settings| Box::new(UniqueRectangle::new().with_enabled(settings.is_unique)))
+            .with_constraint(|settings| Box::new(BugPlusOne::new().with_enabled(settings.is_unique)))
+    }
+    // standard plus locked candidates, subsets and fish: fewer guesses for
+    // more time per square, e.g. --constraints advanced
+    pub fn advanced() -> Self {
+        Pipeline::standard()
+            .with_constraint(|_| Box::new(LockedCandidates::new()))
+            .with_constraint(|_| Box::new(NakedSubsets::new()))
+            .with_constraint(|_| Box::new(HiddenSubsets::new()))
+            .with_constraint(|_| Box::new(Fish::new()))
+    }
+    // naked and hidden singles only; what ForcingChains looks ahead with
+    pub fn singles() -> Self {
+        Pipeline::new()
+            .with_constraint(|_| Box::new(NoRowDuplicates::new()))
+            .with_constraint(|_| Box::new(NoColumnDuplicates::new()))
+            .with_constraint(|_| Box::new(NoBoxDuplicates::new()))
+            .with_constraint(|_| Box::new(ProcessOfElimination::new().with_enabled(true)))
+            .with_constraint(|_| Box::new(AllCannotBeEliminated::new()))
+    }
+    // appends a constraint; any Constraint implementation will do
+    pub fn with_constraint<F>(mut self, factory: F) -> Self
+        where F: Fn(&Settings) -> Box<dyn Constraint> + Send + Sync + 'static
+    {
+        self.factories.push(Arc::new(factory));
+        self
+    }
+    // appends every constraint of other, in order
+    pub fn with_pipeline(mut self, other: &Pipeline) -> Self {
+        self.factories.extend(other.factories.iter().cloned());
+        self
+    }
+    // appends the constraint called name (see CONSTRAINT_NAMES); picking POE
+    // by name enables it, "standard" and "advanced" append those pipelines;
+    // the uniqueness constraints still need Settings::is_unique
+    pub fn with_named(self, name: &str) -> Result<Self, String> {
+        let pipeline = match name.to_lowercase().as_str() {
+            "norowduplicates" => self.with_constraint(|_| Box::new(NoRowDuplicates::new())),
+            "nocolumnduplicates" => self.with_constraint(|_| Box::new(NoColumnDuplicates::new())),
+            "noboxduplicates" => self.with_constraint(|_| Box::new(NoBoxDuplicates::new())),
+            "processofelimination" => self.with_constraint(|_| Box::new(ProcessOfElimination::new().with_enabled(true))),
+            "allcannotbeeliminated" => self.with_constraint(|_| Box::new(AllCannotBeEliminated::new())),
+            "doubledoublerow" => self.with_constraint(|_| Box::new(DoubleDoubleRow::new())),
+            "doubledoublecolumn" => self.with_constraint(|_| Box::new(DoubleDoubleColumn::new())),
+            "doubledoublebox" => self.with_constraint(|_| Box::new(DoubleDoubleBox::new())),
+            "lockedcandidates" => self.with_constraint(|_| Box::new(LockedCandidates::new())),
+            "nakedsubsets" => self.with_constraint(|_| Box::new(NakedSubsets::new())),
+            "hiddensubsets" => self.with_constraint(|_| Box::new(HiddenSubsets::new())),
+            "fish" => self.with_constraint(|_| Box::new(Fish::new())),
+            "xywing" => self.with_constraint(|_| Box::new(XYWing::new())),
+            "xyzwing" => self.with_constraint(|_| Box::new(XYZWing::new())),
+            "simplecoloring" => self.with_constraint(|_| Box::new(SimpleColoring::new())),
+            "xchain" => self.with_constraint(|_| Box::new(XChain::new())),
+            "xychain" => self.with_constraint(|_| Box::new(XYChain::new())),
+            "uniquerectangle" => self.with_constraint(|settings| Box::new(UniqueRectangle::new().with_enabled(settings.is_unique))),
+            "bugplusone" => self.with_constraint(|settings| Box::new(BugPlusOne::new().with_enabled(settings.is_unique))),
+            "forcingchains" => self.with_constraint(|_| Box::new(ForcingChains::new())),
+            "standard" => self.with_pipeline(&Pipeline::standard()),
+            "advanced" => self.with_pipeline(&Pipeline::advanced()),
+            _ => return Err(format!("unknown constraint '{}', expected one of: {}", name, CONSTRAINT_NAMES.join(", "))),
+        };
+        Ok(pipeline)
+    }
+    // comma-separated names, e.g. "standard,XYWing,XChain"
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut pipeline = Pipeline::new();
+        for name in list.split(',').map(|name| name.trim()) {
+            pipeline = pipeline.with_named(name)?;
+        }
+        Ok(pipeline)
+    }
+    pub fn build(&self, settings: &Settings) -> Vec<Box<dyn Constraint>> {
+        self.factories.iter().map(|factory| factory(settings)).collect()
+    }
+}
+
+impl fmt::Debug for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pipeline({} constraints)", self.factories.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::QueueItem;
+    use crate::constraint::ConstraintViolation;
+    use crate::sudoku_puzzle::SudokuPuzzle;
+    use crate::solver::Solver;
+    use crate::sample_puzzles;
+    use crate::guess_ordering::SimpleGuessOrderingByTuple;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    fn names(pipeline: &Pipeline) -> Vec<String> {
+        pipeline.build(&Settings::new()).iter().map(|constraint| constraint.name()).collect()
+    }
+
+    #[test]
+    fn standard() {
+        let names = names(&Pipeline::standard());
+        assert_eq!(names.len(), 10);
+        assert_eq!(names[0], "NoRowDuplicates");
+        assert_eq!(names[3], "ProcessOfEliminationWithMemoization");
+        assert_eq!(names[7], "DoubleDoubleBox");
+        assert_eq!(names[9], "BugPlusOne");
+        // the newer techniques are opt-in
+        assert!(!names.iter().any(|name| name == "LockedCandidates" || name == "Fish"));
+    }
+
+    #[test]
+    fn advanced() {
+        let advanced = names(&Pipeline::advanced());
+        assert_eq!(advanced.len(), 14);
+        assert_eq!(advanced[..10], names(&Pipeline::standard())[..]);
+        assert_eq!(advanced[10..], ["LockedCandidates", "NakedSubsets", "HiddenSubsets", "Fish"]);
+        assert_eq!(names(&Pipeline::parse("Advanced").unwrap()), advanced);
+    }
+
+    #[test]
+    fn parse() {
+        let pipeline = Pipeline::parse("norowduplicates, XYWing").unwrap();
+        assert_eq!(names(&pipeline), vec!["NoRowDuplicates", "XYWing"]);
+        assert_eq!(names(&Pipeline::parse("standard,XChain").unwrap()).len(), 11);
+        assert_eq!(names(&Pipeline::parse("ProcessOfElimination").unwrap()), vec!["ProcessOfEliminationWithMemoization"]);
+
+        let error = Pipeline::parse("NoRowDuplicates,Magic").unwrap_err();
+        assert!(error.starts_with("unknown constraint 'Magic'"));
+        assert!(error.contains("XYChain"));
+    }
+
+    // counts how often it runs
+    struct Counting {
+        count: Arc<AtomicUsize>,
+    }
+    impl Constraint for Counting {
+        fn process(&mut self, _queue_item: &QueueItem, _puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+            self.count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        fn name(&self) -> String {
+            "Counting".to_string()
+        }
+        fn elapsed_millis(&self) -> u128 {
+            0
+        }
+    }
+
+    #[test]
+    fn custom_constraints_run_during_search() {
+        let built = Arc::new(AtomicUsize::new(0));
+        let processed = Arc::new(AtomicUsize::new(0));
+        let (built_by_factory, processed_by_factory) = (built.clone(), processed.clone());
+        let pipeline = Pipeline::standard().with_constraint(move |_| {
+            built_by_factory.fetch_add(1, Ordering::SeqCst);
+            Box::new(Counting { count: processed_by_factory.clone() })
+        });
+        let mut settings = Settings::new();
+        settings.pipeline = pipeline;
+
+        // needs guessing
+        let puzzle = SudokuPuzzle::new(sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut s = Solver::new(puzzle, Box::new(SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9))));
+        s.with_settings(settings);
+        s.solve().unwrap();
+        assert!(s.get_total_guesses() > 0);
+        // built once, the search backtracks in the same solver
+        assert_eq!(built.load(Ordering::SeqCst), 1);
+        assert!(processed.load(Ordering::SeqCst) > 0);
+    }
+}
@@ -9,8 +9,18 @@ pub struct Puzzle {
 #[derive(Clone)]
 struct PuzzleTuple(String, String, Vec<String>);
 
-pub fn get_by_id(id: &str) -> Option<Puzzle> {
-    let sample_puzzles: Vec<PuzzleTuple> =
+pub fn get_all() -> Vec<Puzzle> {
+    sample_puzzle_tuples()
+        .iter()
+        .map(|pt| Puzzle {
+            id: pt.0.clone(),
+            name: pt.1.clone(),
+            puzzle: pt.2.clone(),
+        })
+        .collect()
+}
+
+fn sample_puzzle_tuples() -> Vec<PuzzleTuple> {
         [
             PuzzleTuple(
                 "sjm20200425".to_string(),
@@ -222,15 +232,18 @@ pub fn get_by_id(id: &str) -> Option<Puzzle> {
                     "5    7  6",
                 ].iter().cloned().map(|s| s.to_string()).collect(),
             ),
-        ]
-        .iter().cloned().collect();
+        ].to_vec()
+}
+
+pub fn get_by_id(id: &str) -> Option<Puzzle> {
+    let sample_puzzles: Vec<PuzzleTuple> = sample_puzzle_tuples();
 
     let puzzle_tuples_by_id: HashMap<String, PuzzleTuple> = sample_puzzles
         .iter()
         .cloned()
         .map(|pt| (pt.0.clone(), pt))
         .collect();
-        
+
 
     /*
     sample_puzzle_map.insert(
@@ -249,16 +262,11 @@ pub fn get_by_id(id: &str) -> Option<Puzzle> {
         ].iter().cloned()//.map(|(k,v)| (*k,v)).collect();
         .collect();
 */
-    match puzzle_tuples_by_id.get(id) {
-        Some(pt) => {
-            Some(Puzzle {
-                id: pt.0.clone(),
-                name: pt.1.clone(),
-                puzzle: pt.2.clone(),
-            })
-        }
-        None => None
-    }
+    puzzle_tuples_by_id.get(id).map(|pt| Puzzle {
+        id: pt.0.clone(),
+        name: pt.1.clone(),
+        puzzle: pt.2.clone(),
+    })
 
 }
 
@@ -325,15 +333,14 @@ pub fn _get_by_id_test(id: &str) -> Option<Puzzle> {
                     "         ",
                 ].iter().cloned().map(|s| s.to_string()).collect(),
             ),
-        ]
-        .iter().cloned().collect();
+        ].to_vec();
 
     let puzzle_tuples_by_id: HashMap<String, PuzzleTuple> = test_puzzles
         .iter()
         .cloned()
         .map(|pt| (pt.0.clone(), pt))
         .collect();
-        
+
 
     /*
     sample_puzzle_map.insert(
@@ -352,16 +359,11 @@ pub fn _get_by_id_test(id: &str) -> Option<Puzzle> {
         ].iter().cloned()//.map(|(k,v)| (*k,v)).collect();
         .collect();
 */
-    match puzzle_tuples_by_id.get(id) {
-        Some(pt) => {
-            Some(Puzzle {
-                id: pt.0.clone(),
-                name: pt.1.clone(),
-                puzzle: pt.2.clone(),
-            })
-        }
-        None => None
-    }
+    puzzle_tuples_by_id.get(id).map(|pt| Puzzle {
+        id: pt.0.clone(),
+        name: pt.1.clone(),
+        puzzle: pt.2.clone(),
+    })
 
 }
 
@@ -385,9 +387,17 @@ mod tests {
             Some(_) => (),
             None => panic!(),
         }
-        match get_by_id("should_not_be_there") {
-            Some(_) => panic!(),
-            None => (),
-        }
+        if get_by_id("should_not_be_there").is_some() { panic!() }
+    }
+
+    #[test]
+    fn test_get_all() {
+        let all = get_all();
+        assert_eq!(all[0].id, "sjm20200425");
+        assert!(all.iter().any(|p| p.id == "worldshardest"));
+        all.iter().for_each(|p| {
+            assert!(get_by_id(&p.id).is_some());
+            assert_eq!(p.puzzle.len(), 9);
+        });
     }
 }
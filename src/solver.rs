@@ -1,25 +1,42 @@
 use crate::sudoku_puzzle::SudokuPuzzle;
-use crate::guess_ordering::SimpleGuessOrderingByTuple;
-use crate::constraint::box_util;
+use crate::guess_ordering::GuessOrdering;
+use crate::guess_ordering::seeded_rng;
+use crate::guess_ordering::random_seed;
 use crate::constraint::Constraint;
 use crate::constraint::ConstraintViolation;
-use crate::constraint::NoRowDuplicates;
-use crate::constraint::NoColumnDuplicates;
-use crate::constraint::NoBoxDuplicates;
-use crate::constraint::ProcessOfElimination;
-use crate::constraint::AllCannotBeEliminated;
-use crate::constraint::DoubleDoubleRow;
-use crate::constraint::DoubleDoubleColumn;
-use crate::constraint::DoubleDoubleBox;
+use crate::constraint::Unit;
+use crate::constraint::units_of;
 use crate::debug;
-use crate::config;
+use crate::config::Settings;
+use crate::config::default_max_search_depth;
+use crate::explain::Step;
+use crate::explain::Hint;
+use crate::explain::Technique;
+use crate::grid::Grid;
+
+use rand::Rng;
+use rand::rngs::StdRng;
 
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::thread;
 
 pub struct QueueItem {
     pub row: usize,
     pub column: usize,
+    // the candidate bits the square lost since it was last queued
+    pub removed: usize,
+}
+impl QueueItem {
+    // when it is not known what changed, everything might have
+    pub fn new(row: usize, column: usize) -> Self {
+        QueueItem { row, column, removed: !0 }
+    }
 }
 
 #[derive(Debug)]
@@ -49,16 +66,81 @@ impl Progress {
 pub struct Solver {
     // puzzle: &'a mut SudokuPuzzle,
     puzzle: SudokuPuzzle,
-    guess_ordering: SimpleGuessOrderingByTuple,
+    guess_ordering: Box<dyn GuessOrdering>,
     progress_list: Vec<Progress>,
     // queue_item_list: Vec<QueueItem>,
     queue_item_list: VecDeque<QueueItem>,
+    // per unit (see Unit::index), the candidate bits its squares lost since
+    // the unit-based constraints last looked at it; 0 when unchanged
+    pending_units: Vec<usize>,
+    // every square's bitmap as of when it was last queued
+    seen_bitmaps: Vec<usize>,
+    // (square index, bitmap before) for every change to seen_bitmaps since
+    // the search started; rolling back pops it, see rollback
+    trail: Vec<(usize, usize)>,
     constraint_queue: Vec<Box<dyn Constraint>>,
-    guess: Option<(usize, usize)>, // during search, enqueue only the guess
     constraint_times: HashMap<String, u128>,
+    settings: Settings,
+    solution_limit: Option<usize>, // None: stop at the first solution
+    solutions: Vec<SudokuPuzzle>,
+    // Settings::is_recording: the deductions and guesses on the way to the
+    // board as it stands; a failed guess leaves only the step refuting it
+    steps: Vec<Step>,
+    is_search_enabled: bool, // false: stop when propagation gets stuck
+    fired: Vec<usize>, // per constraint, queue items it made progress on (logic only)
+    // set once a worker of the parallel search this solver belongs to is
+    // done, see search_in_parallel
+    cancelled: Option<Arc<AtomicBool>>,
+    // what the parts of the last parallel search did, the splitting on this
+    // solver first, then each worker's branch; this solver's progress and
+    // constraint times are their totals, see search_in_parallel
+    parts: Vec<WorkerOutcome>,
+    // what rng was seeded with, see Settings::seed
+    seed: u64,
+    rng: StdRng,
+}
+
+// what propagation alone achieved, see Solver::solve_without_search; the
+// puzzle itself (solved, or stuck with its candidates) is in get_puzzle
+#[derive(Debug)]
+pub struct LogicOutcome {
+    pub is_solved: bool,
+    // constraint name and how many queue items it made progress on, in
+    // pipeline order, leaving out the ones that never did
+    pub fired: Vec<(String, usize)>,
+}
+impl fmt::Display for LogicOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_solved {
+            true => writeln!(f, "solved by logic alone")?,
+            false => writeln!(f, "stuck: logic alone gets no further, a guess is needed")?,
+        }
+        let fired: Vec<String> = self.fired
+            .iter()
+            .map(|(name, count)| format!("{} x{}", name, count))
+            .collect();
+        write!(f, "constraints fired: {}", fired.join(", "))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Uniqueness {
+    Unique,
+    Multiple,
+    NoSolution,
+}
+impl fmt::Display for Uniqueness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Uniqueness::Unique => write!(f, "unique"),
+            Uniqueness::Multiple => write!(f, "multiple"),
+            Uniqueness::NoSolution => write!(f, "none"),
+        }
+    }
 }
 
 impl Solver {
+    #[allow(dead_code)]
     pub fn get_progress_list(&self) -> &Vec<Progress> {
         &self.progress_list
     }
@@ -77,6 +159,10 @@ impl Solver {
         let last = self.progress_list.len()-1;
         self.progress_list[last].max_depth_reached
     }
+    // only filled by count_solutions
+    pub fn get_solutions(&self) -> &Vec<SudokuPuzzle> {
+        &self.solutions
+    }
     pub fn get_constraint_times_map(&self) -> HashMap<String, u128> {
         let mut map: HashMap<String, u128> = HashMap::new();
         for i in 0..self.constraint_queue.len() {
@@ -85,173 +171,375 @@ impl Solver {
             let value = constraint.elapsed_millis() +
                 self.constraint_times.get(&name).unwrap();
             map.insert(name, value);
-            match constraint.elapsed_sub_millis() {
-                Some(millis) => {
-                    let name = "POESubMeasurement".to_string();
-                    let value = millis + self.constraint_times.get(&name).unwrap();
-                    map.insert(name, value);
-                },
-                None => (),
+            if let Some(millis) = constraint.elapsed_sub_millis() {
+                let name = "POESubMeasurement".to_string();
+                let value = millis + self.constraint_times.get(&name).unwrap();
+                map.insert(name, value);
             };
         }
         // &self.constraint_times
         map
     }
-    pub fn new(puzzle: SudokuPuzzle, guess_ordering: SimpleGuessOrderingByTuple) -> Self {
-        let mut constraint_queue: Vec<Box<dyn Constraint>> = vec![];
-        constraint_queue.push(Box::new(NoRowDuplicates::new()));
-        constraint_queue.push(Box::new(NoColumnDuplicates::new()));
-        constraint_queue.push(Box::new(NoBoxDuplicates::new()));
-        constraint_queue.push(Box::new(ProcessOfElimination::new()));
-        constraint_queue.push(Box::new(AllCannotBeEliminated::new()));
-        constraint_queue.push(Box::new(DoubleDoubleRow::new()));
-        constraint_queue.push(Box::new(DoubleDoubleColumn::new()));
-        constraint_queue.push(Box::new(DoubleDoubleBox::new()));
-
+    fn build_constraint_queue(settings: &Settings) -> Vec<Box<dyn Constraint>> {
+        settings.pipeline.build(settings)
+    }
+    fn build_constraint_times(constraint_queue: &[Box<dyn Constraint>]) -> HashMap<String, u128> {
         let mut child_map: HashMap<String, u128> = HashMap::new();
         constraint_queue.iter().for_each(|constraint| {
             child_map.insert(constraint.name(), 0);
-            match constraint.elapsed_sub_millis() {
-                Some(_) => {
-                    child_map.insert("POESubMeasurement".to_string(), 0);
-                    ()
-                }
-                None => (),
+            if constraint.elapsed_sub_millis().is_some() {
+                child_map.insert("POESubMeasurement".to_string(), 0);
             };
-            ()
         });
+        child_map
+    }
+    pub fn new(puzzle: SudokuPuzzle, guess_ordering: Box<dyn GuessOrdering>) -> Self {
+        let settings = Settings::new();
+        let constraint_queue = Solver::build_constraint_queue(&settings);
+        let constraint_times = Solver::build_constraint_times(&constraint_queue);
+        let seed = random_seed();
 
         Solver {
-            puzzle: puzzle,
+            puzzle,
             guess_ordering,
             progress_list: vec![Progress::new(0)],
             queue_item_list: VecDeque::new(),
+            pending_units: vec![],
+            seen_bitmaps: vec![],
             constraint_queue,
-            guess: None,
-            constraint_times: child_map,
+            trail: vec![],
+            constraint_times,
+            settings,
+            solution_limit: None,
+            solutions: vec![],
+            steps: vec![],
+            is_search_enabled: true,
+            fired: vec![],
+            cancelled: None,
+            parts: vec![],
+            seed,
+            rng: seeded_rng(seed),
         }
     }
-    pub fn with_guess(&mut self, row: usize, col: usize) {
-        self.guess = Some((row, col));
-    }
-    pub fn with_progress<'a>(&'a mut self, progress_list: &Vec<Progress>) -> &'a mut Self {
+    // starts the solver below the given levels, as if it were searching
+    // from a guess made at the deepest of them
+    pub fn with_progress<'a>(&'a mut self, progress_list: &[Progress]) -> &'a mut Self {
         // chose not to build this out, as progress_list is not an Option but has 2 different values
         let mut cloned_progress_list = vec![];
         progress_list
             .iter()
             .for_each(|progress| { cloned_progress_list.push((*progress).clone())});
         let depth = progress_list.len();
-        if depth > config::MAX_SEARCH_DEPTH {
-            panic!("max depth reached");
-        }
         cloned_progress_list.push(Progress::new(depth));
 
         self.progress_list = cloned_progress_list;
-        
+
         self
     }
-    
-    pub fn get_puzzle(&mut self) -> &mut SudokuPuzzle {
-        &mut self.puzzle
+    // the search runs on the SudokuPuzzle the grid converts to, see Grid
+    pub fn from_grid(grid: &Grid, guess_ordering: Box<dyn GuessOrdering>) -> Self {
+        Solver::new(SudokuPuzzle::from_grid(grid), guess_ordering)
     }
-    fn enqueue(&mut self, qitem: QueueItem) {
-        self.queue_item_list.push_back(qitem);
+    // Settings::max_search_depth, or the default for this board
+    fn max_search_depth(&self) -> usize {
+        self.settings.max_search_depth.unwrap_or_else(|| default_max_search_depth(self.puzzle.size()))
     }
-    fn clean_all(&mut self) {
-        (1..=0).for_each(|r| (1..=9).for_each(|c| self.puzzle.get_square(r,c).clean()))
-    }
-    fn copy_from_solution(&mut self, possible_solution: &mut SudokuPuzzle) {
-        debug::debug(format!("(depth={}) entering copy_from_solution!!!", self.get_depth()));
-        for i in 1..=9 {
-            for j in 1..=9 {
-                let correct_value = possible_solution.get_square(i,j).get_single_value();
-                self.puzzle.get_square(i,j).select(correct_value);
-            }
-        }
-        match self.validate_solution() {
-            Ok(_) => (),
-            Err(e) => panic!("did not expect the possible solution to fail validation! (e={})", e.msg),
+    // empty unless Settings::is_recording
+    pub fn get_steps(&self) -> &[Step] {
+        &self.steps
+    }
+    // the seed to put in Settings::seed to replay this solver's search
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+    // the board as it stands
+    pub fn get_grid(&self) -> Grid {
+        self.puzzle.grid().clone()
+    }
+    pub fn with_settings(&mut self, settings: Settings) -> &mut Self {
+        // the constraint queue comes from the settings' pipeline, so rebuild it
+        self.constraint_queue = Solver::build_constraint_queue(&settings);
+        self.constraint_queue.iter_mut().for_each(|constraint| constraint.set_recording(settings.is_recording));
+        self.constraint_times = Solver::build_constraint_times(&self.constraint_queue);
+        if let Some(seed) = settings.seed {
+            self.seed = seed;
+            self.rng = seeded_rng(seed);
         }
+        self.settings = settings;
+        self
     }
-    fn validate_solution(&mut self) -> Result<bool, ConstraintViolation> {
-        // validate all rows contain all values exactly once
-        for r in 1..=9 {
-            let mut seen = [0 as usize; 9];
-            for c in 1..=9 {
-                if self.get_puzzle().get_square(r,c).has_single_value() {
-                    let value = self.get_puzzle().get_square(r,c).get_single_value();
-                    seen[value-1] = seen[value-1] + 1;
+
+    // the simplest next deduction from the puzzle's current state, using the
+    // constraints settings gives the solver, in the solver's order, so a hint
+    // only ever names a technique the solver itself would use; the puzzle
+    // itself is not changed
+    //
+    // removing placed digits from their peers (naked singles that only
+    // eliminate) is bookkeeping rather than a hint, so it is applied and the
+    // constraints start over; otherwise the first constraint that places a
+    // digit or, failing that, eliminates one gives the hint
+    pub fn next_hint(puzzle: &SudokuPuzzle, settings: &Settings) -> Result<Hint, ConstraintViolation> {
+        let mut scratch = SudokuPuzzle::clone_from(puzzle);
+        if scratch.coordinates().into_iter().all(|(r,c)| scratch.get_square(r,c).has_single_value()) {
+            return Ok(Hint::Solved);
+        }
+        let mut constraints = Solver::build_constraint_queue(settings);
+        constraints.iter_mut().for_each(|constraint| constraint.set_recording(true));
+
+        'constraints: loop {
+            for constraint in constraints.iter_mut() {
+                let steps = Solver::run_over_board(constraint, &mut scratch)?;
+                if let Some(step) = steps.iter().find(|step| !step.placed.is_empty()) {
+                    return Ok(Hint::Place(Solver::placement(step)));
                 }
-            }
-            for i in 0.. seen.len() {
-                if seen[i] != 1 {
-                    return Err(ConstraintViolation{msg: "rows must contain all values once".to_string()});
+                match steps.into_iter().find(|step| !step.eliminated.is_empty()) {
+                    Some(step) if step.technique == Technique::NakedSingle => continue 'constraints,
+                    Some(step) => return Ok(Hint::Eliminate(step)),
+                    None => (),
                 }
             }
+            return Ok(Hint::GuessNeeded);
         }
-        // validate all columns contain all values exactly once
-        for c in 1..=9 {
-            let mut seen = [0 as usize; 9];
-            for r in 1..=9 {
-                if self.get_puzzle().get_square(r,c).has_single_value() {
-                    let value = self.get_puzzle().get_square(r,c).get_single_value();
-                    seen[value-1] = seen[value-1] + 1;
+    }
+    // one pass of a constraint over every square, or every unit for the
+    // unit-based ones; the steps it recorded
+    fn run_over_board(constraint: &mut Box<dyn Constraint>, puzzle: &mut SudokuPuzzle) -> Result<Vec<Step>, ConstraintViolation> {
+        let box_size = puzzle.box_size();
+        match constraint.is_unit_based() {
+            true => {
+                let every_value = crate::squares::full_bitmap(puzzle.size());
+                for unit in Unit::all(box_size) {
+                    constraint.process_unit(unit, every_value, puzzle)?;
                 }
-            }
-            for i in 0.. seen.len() {
-                if seen[i] != 1 {
-                    return Err(ConstraintViolation{msg: "columns must contain all values once".to_string()});
+            },
+            false => {
+                for (r,c) in puzzle.coordinates() {
+                    constraint.process(&QueueItem::new(r, c), puzzle)?;
                 }
-            }
+            },
         }
-        // validate all boxes contain all values exactly once
-        let mut error_seen: Option<Result<bool, ConstraintViolation>> = None;
-        [(1,1),(1,4),(1,7),(4,1),(4,4),(4,7),(7,1),(7,4),(7,7),]
+        Ok(constraint.take_steps())
+    }
+    // the first digit a step places, as a step of its own; a naked single
+    // is named by the square it lands in rather than the one it came from
+    fn placement(step: &Step) -> Step {
+        let (r, c, d) = step.placed[0];
+        match step.technique {
+            Technique::NakedSingle => Step {
+                technique: Technique::NakedSingle,
+                unit: None,
+                cells: vec![(r, c)],
+                digits: vec![d],
+                eliminated: vec![],
+                placed: vec![(r, c, d)],
+                chain: vec![],
+            },
+            _ => {
+                let mut step = step.clone();
+                step.placed.truncate(1);
+                step.eliminated.clear();
+                step
+            },
+        }
+    }
+    // propagates without ever guessing; a contradiction is still an error
+    pub fn solve_without_search(&mut self) -> Result<LogicOutcome, ConstraintViolation> {
+        self.is_search_enabled = false;
+        self.fired = vec![0; self.constraint_queue.len()];
+        self.solve()?;
+        let is_solved = self.puzzle.coordinates().into_iter().all(|(r,c)| self.puzzle.get_square(r,c).has_single_value()) &&
+            self.validate_solution().is_ok();
+        let fired = self.constraint_queue
             .iter()
-            .for_each(|(r,c)| {
-                let mut seen = [0 as usize; 9];
-                box_util::box9(*r,*c).iter().for_each(|(i,j)| {
-                    if self.get_puzzle().get_square(*i,*j).has_single_value() {
-                        let value = self.get_puzzle().get_square(*i,*j).get_single_value();
-                        seen[value-1] = seen[value-1] + 1;
-                    }
-                });
-                for i in 0.. seen.len() {
-                    if seen[i] != 1 {
-                        error_seen = Some(Err(ConstraintViolation{msg: "boxes must contain all values once".to_string()}));
-                    }
-                }
-            });
-
-        match error_seen {
-            Some(e) => e,
-            None => Ok(true),
+            .zip(self.fired.iter())
+            .filter(|(_, count)| **count > 0)
+            .map(|(constraint, count)| (constraint.name(), *count))
+            .collect();
+        Ok(LogicOutcome { is_solved, fired })
+    }
+    // searches until limit solutions have been found or the search space is
+    // exhausted; contradictions count as zero solutions, running out of depth
+    // is an error since the count is then unknown
+    pub fn count_solutions(&mut self, limit: usize) -> Result<usize, ConstraintViolation> {
+        if limit == 0 {
+            return Ok(0);
+        }
+        self.solution_limit = Some(limit);
+        // the uniqueness constraints take one solution for granted, which is
+        // what is being counted
+        if self.settings.is_unique {
+            let mut settings = self.settings.clone();
+            settings.is_unique = false;
+            self.with_settings(settings);
+        }
+        match self.solve() {
+            Ok(_) => (),
+            Err(e @ ConstraintViolation::DepthLimitExceeded { .. }) => return Err(e),
+            Err(e @ ConstraintViolation::InvalidInput(_)) => return Err(e),
+            Err(_) => (),
+        }
+        // leave the first solution in the puzzle, as solve would; counting
+        // eliminates values that led to solutions, so replace rather than copy
+        if !self.solutions.is_empty() {
+            self.puzzle = SudokuPuzzle::clone_from(&self.solutions[0]);
         }
+        Ok(self.solutions.len())
     }
-    fn done_yet(&mut self) -> Result<bool, ConstraintViolation> {
-        for i in 1..=9 {
-            for j in 1..=9 {
-                if !self.get_puzzle().get_square(i,j).has_single_value() {
-                    return Ok(false);
+    pub fn uniqueness(&mut self) -> Result<Uniqueness, ConstraintViolation> {
+        match self.count_solutions(2)? {
+            0 => Ok(Uniqueness::NoSolution),
+            1 => Ok(Uniqueness::Unique),
+            _ => Ok(Uniqueness::Multiple),
+        }
+    }
+    fn record_solution(&mut self) {
+        if self.solution_limit.is_some() {
+            self.solutions.push(SudokuPuzzle::clone_from(&self.puzzle));
+        }
+    }
+    pub fn get_puzzle(&mut self) -> &mut SudokuPuzzle {
+        &mut self.puzzle
+    }
+    fn enqueue(&mut self, qitem: QueueItem) {
+        self.queue_item_list.push_back(qitem);
+    }
+    fn clean_all(&mut self) {
+        self.puzzle.coordinates().iter().for_each(|(r,c)| self.puzzle.get_square(*r,*c).clean())
+    }
+    // checks that no digit is placed twice in the unit and, if require_complete,
+    // that every digit is placed
+    fn check_unit(&mut self, unit: Unit, require_complete: bool) -> Result<(), ConstraintViolation> {
+        let box_size = self.puzzle.box_size();
+        let mut seen = vec![0_usize; box_size * box_size];
+        for (r,c) in unit.cells(box_size) {
+            if self.get_puzzle().get_square(r,c).has_single_value() {
+                let digit = self.get_puzzle().get_square(r,c).get_single_value();
+                seen[digit-1] += 1;
+                if seen[digit-1] > 1 {
+                    return Err(ConstraintViolation::DuplicateDigit { unit, digit, row: r, column: c });
                 }
             }
         }
+        if require_complete {
+            if let Some(index) = seen.iter().position(|count| *count == 0) {
+                return Err(ConstraintViolation::DigitMissing { unit, digit: index+1 });
+            }
+        }
+        Ok(())
+    }
+    fn validate_solution(&mut self) -> Result<bool, ConstraintViolation> {
+        // validate all rows, columns and boxes contain all values exactly once
+        for unit in Unit::all(self.puzzle.box_size()) {
+            self.check_unit(unit, true)?;
+        }
+        Ok(true)
+    }
+    fn validate_givens(&mut self) -> Result<(), ConstraintViolation> {
+        for unit in Unit::all(self.puzzle.box_size()) {
+            self.check_unit(unit, false)?;
+        }
+        Ok(())
+    }
+    fn done_yet(&mut self) -> Result<bool, ConstraintViolation> {
+        let grid = self.puzzle.grid();
+        let is_complete = (0..grid.size() * grid.size()).all(|index| grid.count_remaining(index) == 1);
+        if !is_complete {
+            return Ok(false);
+        }
 
         self.validate_solution()
     }
-    fn enqueue_all_dirty_and_mark_clean(&mut self) -> () {
-        (1..=9).for_each(|r| (1..=9).for_each(|c| {
-            if self.puzzle.get_square(r,c).is_dirty() {
-                self.enqueue(QueueItem{row: r, column: c});
-                self.puzzle.get_square(r,c).clean();
+    fn remember_bitmaps(&mut self) {
+        let grid = self.puzzle.grid();
+        self.seen_bitmaps = (0..grid.size() * grid.size()).map(|index| grid.get_bitmap(index)).collect();
+        self.pending_units = vec![0; 3 * self.puzzle.size()];
+        self.puzzle.take_touched();
+    }
+    // queues every square that changed since it was last queued, with the
+    // candidate bits it lost
+    fn enqueue_all_dirty_and_mark_clean(&mut self) {
+        let size = self.puzzle.size();
+        // only squares handed out by get_square can be dirty; sorted so they
+        // are queued in board order, as a scan of the whole board would
+        let mut touched = self.puzzle.take_touched();
+        touched.sort_unstable();
+        for index in touched {
+            let (r, c) = (index / size + 1, index % size + 1);
+            if !self.puzzle.is_dirty(index) {
+                continue;
+            }
+            let bitmap = self.puzzle.grid().get_bitmap(index);
+            let removed = self.seen_bitmaps[index] & !bitmap;
+            if bitmap != self.seen_bitmaps[index] {
+                self.trail.push((index, self.seen_bitmaps[index]));
+            }
+            self.seen_bitmaps[index] = bitmap;
+            self.puzzle.clean(index);
+            if removed != 0 {
+                self.enqueue(QueueItem { row: r, column: c, removed });
             }
-        }));
+        }
+    }
+    // the square's row, column and box need another look from the unit-based
+    // constraints
+    fn schedule_units(&mut self, qitem: &QueueItem) {
+        let box_size = self.puzzle.box_size();
+        for unit in units_of(qitem, box_size).iter() {
+            self.pending_units[unit.index(box_size)] |= qitem.removed;
+        }
+    }
+    // candidates only ever shrink, so a constraint made progress exactly when
+    // this went down
+    fn candidates_left(&self) -> usize {
+        let grid = self.puzzle.grid();
+        (0..grid.size() * grid.size()).map(|index| grid.count_remaining(index)).sum()
+    }
+    // runs constraint i on a square (Some) or a unit (None, with the unit)
+    fn run_constraint(&mut self, i: usize, qitem: Option<&QueueItem>, unit: Option<(Unit, usize)>)
+                      -> Result<(), ConstraintViolation> {
+        // only counted in logic-only mode, it costs a pass over the board
+        let before = match self.fired.is_empty() {
+            true => 0,
+            false => self.candidates_left(),
+        };
+        let constraint = &mut self.constraint_queue[i];
+        let result = match (qitem, unit) {
+            (Some(qitem), _) => constraint.process(qitem, &mut self.puzzle),
+            (None, Some((unit, removed))) => constraint.process_unit(unit, removed, &mut self.puzzle),
+            (None, None) => Ok(()),
+        };
+        if self.settings.is_recording {
+            self.steps.append(&mut constraint.take_steps());
+        }
+        result?;
+        if !self.fired.is_empty() && self.candidates_left() < before {
+            self.fired[i] += 1;
+        }
+        Ok(())
     }
     fn process(&mut self, qitem: QueueItem) -> Result<(), ConstraintViolation> {
         for i in 0..self.constraint_queue.len() {
-            // self.constraint_queue[i].process(&qitem, &mut self.puzzle)?;
-            let constraint = &mut self.constraint_queue[i];
-            constraint.process(&qitem, &mut self.puzzle)?;
+            if !self.constraint_queue[i].is_unit_based() {
+                self.run_constraint(i, Some(&qitem), None)?;
+            }
+        }
+        self.schedule_units(&qitem);
+        Ok(())
+    }
+    // once per round, each changed unit through the unit-based constraints
+    fn process_units(&mut self) -> Result<(), ConstraintViolation> {
+        let box_size = self.puzzle.box_size();
+        for unit in Unit::all(box_size) {
+            let removed = self.pending_units[unit.index(box_size)];
+            if removed == 0 {
+                continue;
+            }
+            self.pending_units[unit.index(box_size)] = 0;
+            for i in 0..self.constraint_queue.len() {
+                if self.constraint_queue[i].is_unit_based() {
+                    self.run_constraint(i, None, Some((unit, removed)))?;
+                }
+            }
         }
         Ok(())
     }
@@ -265,163 +553,398 @@ impl Solver {
         //   3. given a new solver, only enqueue the recently selected node
         //
 
-        match self.guess {
-            Some((row, col)) => {
-                self.enqueue(QueueItem{row: row, column: col});
-            },
-            None => {
-                // enqueue all InitialSquare objects
-                (1..=9).for_each(|r| (1..=9).for_each(|c| {
-                    if self.puzzle.get_square(r,c).has_single_value() {
-                        self.enqueue(QueueItem{row: r, column: c})
-                    }
-                }));
+        // duplicate givens are never caught by propagation, which only
+        // eliminates from open squares
+        self.validate_givens()?;
+        // enqueue every square that already has its value
+        self.puzzle.coordinates().into_iter().for_each(|(r,c)| {
+            if self.puzzle.get_square(r,c).has_single_value() {
+                self.enqueue(QueueItem::new(r, c))
             }
-        };
+        });
 
         self.clean_all();
+        self.remember_bitmaps();
+        self.trail.clear();
 
-        let max_runs = 30;
-        'outer: for _run_number in 0..max_runs {
-            if self.queue_item_list.len() > 0 {
-                debug::debug(format!("(depth={}) running through queue of length {}", self.get_depth(), self.queue_item_list.len()));
-                // run through queue completely
-                'inner: while !self.queue_item_list.is_empty() {
-                    let qitem = self.queue_item_list.pop_front().unwrap();
-                    let result = self.process(qitem);
-                    match result {
-                        Ok(_) => (),
-                        Err(e) => {
-                            return Err(e);
-                        }
-                    };
-                    // check if finished after every constraint has processed
-                    match self.done_yet() {
-                        Ok(is_done) => {
-                            if is_done {
-                                break 'outer;
-                            }
-                        }
-                        Err(constraint_violation) => {
-                            return Err(constraint_violation);
-                        }
+        // the search is a loop rather than a recursion: every guess pushes a
+        // frame, and backtracking rolls the board back along the trail to
+        // where the frame started, so one solver and one board do for all of it
+        let mut frames: Vec<Frame> = vec![];
+        let mut propagated = self.propagate();
+        loop {
+            // what the board at the deepest level comes to: Ok once solved,
+            // or a frame to guess from when propagation is stuck
+            let mut result = match propagated {
+                Err(e) => Err(e),
+                Ok(true) => {
+                    self.record_solution();
+                    Ok(())
+                },
+                Ok(false) => {
+                    // the fixpoint; solve_without_search reports what is left
+                    if !self.is_search_enabled {
+                        debug::debug(format!("(depth={}) stuck without search", self.get_depth()));
+                        return Ok(());
                     }
-                }
-                debug::debug(format!("(depth={}) finished running through queue!", self.get_depth()));
-                self.enqueue_all_dirty_and_mark_clean();
-            } else {
-                // search
-                let guess_list = self.guess_ordering.clone().guess_list(self.get_puzzle());
-                debug::debug(format!("(depth={}) guess list: {:?}", self.get_depth(), guess_list));
-                // note there should be many correct guesses - one per node!
-
-                // not sure, but we might not need this given that contradictions are searched for
-                let correct_guess: Option<(usize, usize, usize)> = None;
-
-                for index in 0..guess_list.len() {
-                    // preparation
-                    let guess: &(usize, usize, usize) = &guess_list[index];
-                    let i = guess.0;
-                    let j = guess.1;
-                    let value = guess.2;
-                    debug::debug(format!("(depth={}) guess: (i,j,value)=({},{},{})", self.get_depth(), i, j, value));
-                    // clone the puzzle -- this also copies square state
-                    let mut puzzle_clone = SudokuPuzzle::clone_from(self.get_puzzle());
-                    // apply the guess
-                    puzzle_clone.get_square(i,j).select(value);
-                    self.get_progress().total_guesses = self.get_progress().total_guesses + 1;
-                    // create the new solver
-                    let mut new_solver: Solver = Solver::new(puzzle_clone, self.guess_ordering.clone());
-                    new_solver.with_progress(self.get_progress_list());
-                    new_solver.with_guess(i,j);
-                    // and solve
-                    match new_solver.solve() {
-                        Ok(_) => {
-                            debug::debug(format!("(depth={}) guess ({},{},{}) was correct!", self.get_depth(), i, j, value));
-                            // the guess was correct
-                            // copy state from that puzzle to this puzzle
-                            // (much faster than applying the correct guess)
-                            self.copy_from_solution(new_solver.get_puzzle());
-
-                            // bookkeeping
-                            self.get_progress().total_guesses = self.get_progress().total_guesses +
-                                new_solver.get_total_guesses();
-                            let new_max_depth = new_solver.get_max_depth_reached();
-                            let current_max_depth = self.get_progress().max_depth_reached;
-                            if new_max_depth > current_max_depth {
-                                self.get_progress().max_depth_reached = new_max_depth;
-                            }
-                            let delta_constraint_times = new_solver.get_constraint_times_map();
-                            for i in 0..self.constraint_queue.len() {
-                                let constraint = &self.constraint_queue[i];
-                                let name = constraint.name();
-                                let new_value = delta_constraint_times.get(&name).unwrap() +
-                                    self.constraint_times.get(&name).unwrap();
-                                self.constraint_times.insert(name, new_value);
-                                match constraint.elapsed_sub_millis() {
-                                    Some(_) => {
-                                        let name = "POESubMeasurement".to_string();
-                                        let value = delta_constraint_times.get(&name).unwrap() +
-                                            self.constraint_times.get(&name).unwrap();
-                                        self.constraint_times.insert(name, value);
-                                    },
-                                    None => (),
-                                }
-                            }
-
-                            return Ok(())
-                        }
-                        Err(e) => {
-                            debug::debug(format!("(depth={}) guess ({},{},{}) was incorrect, eliminating {} (e={})", self.get_depth(), i,j,value,value, e.msg));
-                            self.puzzle.get_square(i,j).eliminate(value);
-
-                            // bookkeeping
-                            self.get_progress().total_guesses = self.get_progress().total_guesses +
-                                new_solver.get_total_guesses();
-                            let new_max_depth = new_solver.get_max_depth_reached();
-                            let current_max_depth = self.get_progress().max_depth_reached;
-                            if new_max_depth > current_max_depth {
-                                self.get_progress().max_depth_reached = new_max_depth;
-                            }
-                            let delta_constraint_times = new_solver.get_constraint_times_map();
-                            for i in 0..self.constraint_queue.len() {
-                                let constraint = &self.constraint_queue[i];
-                                let name = constraint.name();
-                                let new_value = delta_constraint_times.get(&name).unwrap() +
-                                    self.constraint_times.get(&name).unwrap();
-                                self.constraint_times.insert(name, new_value);
-                                match constraint.elapsed_sub_millis() {
-                                    Some(_) => {
-                                        let name = "POESubMeasurement".to_string();
-                                        let value = delta_constraint_times.get(&name).unwrap() +
-                                            self.constraint_times.get(&name).unwrap();
-                                        self.constraint_times.insert(name, value);
-                                    },
-                                    None => (),
-                                }
-                            }
-
-                            // if a contradiction has been reached, do not keep guessing blindly
-                            if self.puzzle.get_square(i,j).count_remaining() == 0 {
-                                debug::debug(format!("(depth={}) all values eliminated for ({},{})", self.get_depth(), i, j));
-                                let msg = format!("all values eliminated for a square, ({},{}), depth={}", i,j, self.get_depth());
-                                return Err(ConstraintViolation{msg: msg});
-                            }
+                    // counting needs every branch, so it stays on one thread
+                    // and so does recording, the steps are of one search
+                    if frames.is_empty() && self.settings.search_threads > 1 && self.solution_limit.is_none() && !self.settings.is_recording {
+                        return self.search_in_parallel();
+                    }
+                    let guess_list = self.guess_ordering.guess_list(&mut self.puzzle, &mut self.rng);
+                    debug::debug(format!("(depth={}) guess list: {:?}", self.get_depth(), guess_list));
+                    match guess_list.is_empty() {
+                        true => self.check_complete(),
+                        false => {
+                            frames.push(Frame {
+                                guesses: guess_list,
+                                next: 0,
+                                mark: self.trail.len(),
+                                steps_mark: self.steps.len(),
+                                solutions_before: self.solutions.len(),
+                            });
+                            propagated = self.try_next_guess(&mut frames);
                             continue;
-                        }
+                        },
+                    }
+                },
+            };
+
+            // hand the result up until a level has another value to try
+            loop {
+                let level = frames.len();
+                let frame = match frames.last_mut() {
+                    Some(frame) => frame,
+                    None => return result,
+                };
+                let (i, j, value) = frame.guesses[frame.next - 1];
+                match &result {
+                    // the guess was correct and the board holds the solution
+                    Ok(_) if self.solution_limit.is_none() => {
+                        debug::debug(format!("(depth={}) guess ({},{},{}) was correct!", level, i, j, value));
+                        return result;
+                    },
+                    // counting: every solution with this value is known, so
+                    // move on to the other values unless there are enough
+                    Ok(_) if self.solutions.len() >= self.solution_limit.unwrap() => return result,
+                    Ok(_) => (),
+                    // running out of depth says nothing about the guess itself
+                    Err(e @ ConstraintViolation::DepthLimitExceeded { .. }) => return Err(e.clone()),
+                    Err(ConstraintViolation::Cancelled) => return result,
+                    Err(e) => {
+                        debug::debug(format!("(depth={}) guess ({},{},{}) was incorrect, eliminating {} (e={})", level, i, j, value, value, e));
+                    },
+                }
+                let (mark, steps_mark) = (frame.mark, frame.steps_mark);
+                self.rollback(mark);
+                self.puzzle.get_square(i,j).eliminate(value);
+                self.commit(i, j);
+                if self.settings.is_recording {
+                    self.steps.truncate(steps_mark);
+                    if let Err(e) = &result {
+                        self.steps.push(Step::refuted(i, j, value, &e.to_string()));
                     }
                 }
+                let frame = frames.last_mut().unwrap();
+                frame.mark = self.trail.len();
+                frame.steps_mark = self.steps.len();
+                if frame.next < frame.guesses.len() {
+                    break;
+                }
+                // if a contradiction has been reached, do not keep guessing
+                // blindly; when counting, earlier values may have had solutions
+                debug::debug(format!("(depth={}) all guesses eliminated, the last at ({},{})", level, i, j));
+                let solutions_before = frame.solutions_before;
+                result = match result.is_ok() || self.solutions.len() > solutions_before {
+                    true => Ok(()),
+                    false => Err(self.exhausted(i, j, value)),
+                };
+                frames.pop();
+            }
+            propagated = self.try_next_guess(&mut frames);
+        }
+    }
+    // Settings::search_threads: the board is split into the branches of the next
+    // split_depth levels of guesses, and the workers take branches off a
+    // shared queue, searching each as solve would; the first solution found
+    // cancels the rest. Branches that fail while splitting are not searched
+    fn search_in_parallel(&mut self) -> Result<(), ConstraintViolation> {
+        let mut settings = self.settings.clone();
+        settings.search_threads = 1;
+        let ordering = self.guess_ordering.clone();
+        // the guesses and the propagation done here while splitting
+        let mut split = WorkerOutcome {
+            result: Ok(()),
+            total_guesses: 0,
+            max_depth_reached: 0,
+            constraint_times: HashMap::new(),
+        };
+        let mut errors: Vec<ConstraintViolation> = vec![];
+
+        // as in the single-threaded search, nothing to guess means the grid
+        // is complete or a square has no candidates left
+        let mut root_guesses = ordering.guess_list(&mut self.puzzle, &mut self.rng);
+        let (row, column, _) = match root_guesses.first() {
+            Some(guess) => *guess,
+            None => return self.check_complete(),
+        };
+        // the square of the first guess, reported if no branch has a solution
+        let first_guess = (row, column);
 
-                if correct_guess == None {
-                    return Err(ConstraintViolation{msg: "no correct guesses".to_string()});
+        let mut branches = vec![Branch { puzzle: SudokuPuzzle::clone_from(&self.puzzle), depth: self.get_depth(), seed: 0 }];
+        for level in 0..self.settings.split_depth.max(1) {
+            let mut next = vec![];
+            for mut branch in branches {
+                let guesses = match level {
+                    0 => std::mem::take(&mut root_guesses),
+                    _ => ordering.guess_list(&mut branch.puzzle, &mut self.rng),
+                };
+                for (i, j, value) in guesses {
+                    let depth = branch.depth + 1;
+                    split.total_guesses += 1;
+                    split.max_depth_reached = split.max_depth_reached.max(depth);
+                    if depth > self.max_search_depth() {
+                        errors.push(ConstraintViolation::DepthLimitExceeded { row: i, column: j, digit: value, limit: self.max_search_depth() });
+                        continue;
+                    }
+                    // propagate the guess in a solver of its own, to see
+                    // whether the branch is worth handing out
+                    let mut puzzle = SudokuPuzzle::clone_from(&branch.puzzle);
+                    puzzle.get_square(i,j).select(value);
+                    let mut s = Solver::new(puzzle, ordering.clone());
+                    s.with_settings(settings.clone());
+                    let outcome = s.solve_without_search();
+                    for (name, millis) in s.get_constraint_times_map() {
+                        *split.constraint_times.entry(name).or_insert(0) += millis;
+                    }
+                    match outcome {
+                        Err(e) => errors.push(e),
+                        Ok(outcome) if outcome.is_solved => {
+                            self.puzzle = s.puzzle;
+                            self.merge_parts(vec![split]);
+                            return Ok(());
+                        },
+                        // each branch gets a seed of its own up front, so
+                        // the seed of this solver decides them all
+                        Ok(_) => next.push(Branch { puzzle: s.puzzle, depth, seed: self.rng.gen() }),
+                    }
                 }
+            }
+            branches = next;
+        }
 
+        let threads = self.settings.search_threads.min(branches.len());
+        debug::debug(format!("(depth={}) searching {} branches on {} threads", self.get_depth(), branches.len(), threads));
+        let queue = Mutex::new(VecDeque::from(branches));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let solution: Mutex<Option<SudokuPuzzle>> = Mutex::new(None);
+        let outcomes: Mutex<Vec<WorkerOutcome>> = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let branch = match queue.lock().unwrap().pop_front() {
+                        Some(branch) => branch,
+                        None => break,
+                    };
+                    if cancelled.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let levels: Vec<Progress> = (0..branch.depth).map(Progress::new).collect();
+                    let mut settings = settings.clone();
+                    settings.seed = Some(branch.seed);
+                    let mut s = Solver::new(branch.puzzle, ordering.clone());
+                    s.with_settings(settings).with_progress(&levels);
+                    s.cancelled = Some(cancelled.clone());
+                    let result = s.solve();
+                    let outcome = WorkerOutcome {
+                        result: result.clone(),
+                        total_guesses: s.get_total_guesses(),
+                        max_depth_reached: s.get_max_depth_reached(),
+                        constraint_times: s.get_constraint_times_map(),
+                    };
+                    // only the first solution counts, later ones were racing it
+                    if result.is_ok() && !cancelled.swap(true, Ordering::SeqCst) {
+                        *solution.lock().unwrap() = Some(s.puzzle);
+                    }
+                    outcomes.lock().unwrap().push(outcome);
+                });
             }
+        });
 
-        };
+        let mut parts = vec![split];
+        parts.append(&mut outcomes.into_inner().unwrap());
+        for part in parts.iter() {
+            match &part.result {
+                Ok(_) | Err(ConstraintViolation::Cancelled) => (),
+                Err(e) => errors.push(e.clone()),
+            }
+        }
+        self.merge_parts(parts);
+        if let Some(puzzle) = solution.into_inner().unwrap() {
+            self.puzzle = puzzle;
+            return Ok(());
+        }
+        // as in the single-threaded search: running out of depth leaves the
+        // question open, otherwise every value of the first square failed
+        match errors.into_iter().find(|e| matches!(e, ConstraintViolation::DepthLimitExceeded { .. })) {
+            Some(e) => Err(e),
+            None => Err(ConstraintViolation::NoCandidates { row: first_guess.0, column: first_guess.1 }),
+        }
+    }
+    // adds up the guesses and constraint times of the parts of a parallel
+    // search on this solver's, and keeps the parts
+    fn merge_parts(&mut self, parts: Vec<WorkerOutcome>) {
+        for part in parts.iter() {
+            self.get_progress().total_guesses += part.total_guesses;
+            if part.max_depth_reached > self.get_progress().max_depth_reached {
+                self.get_progress().max_depth_reached = part.max_depth_reached;
+            }
+            for (name, millis) in part.constraint_times.iter() {
+                *self.constraint_times.entry(name.to_string()).or_insert(0) += millis;
+            }
+        }
+        self.parts = parts;
+    }
+    // runs the constraints until nothing changes: the queue holds the squares
+    // that changed, and processing them can dirty more squares, which are
+    // queued in turn; true once the puzzle is solved
+    fn propagate(&mut self) -> Result<bool, ConstraintViolation> {
+        while !self.queue_item_list.is_empty() {
+            debug::debug(format!("(depth={}) running through queue of length {}", self.get_depth(), self.queue_item_list.len()));
+            // run through queue completely
+            while let Some(qitem) = self.queue_item_list.pop_front() {
+                self.process(qitem)?;
+            }
+            // then the units those squares are in
+            self.process_units()?;
+            debug::debug(format!("(depth={}) finished running through queue!", self.get_depth()));
+            // check if finished once per round rather than per square; done_yet
+            // only says so once validate_solution passed
+            if self.done_yet()? {
+                return Ok(true);
+            }
+            self.enqueue_all_dirty_and_mark_clean();
+        }
+        Ok(false)
+    }
+    // nothing to guess means no square has two or more candidates left, so
+    // either one has none or the grid is complete
+    fn check_complete(&mut self) -> Result<(), ConstraintViolation> {
+        for (r,c) in self.puzzle.coordinates() {
+            if self.puzzle.get_square(r,c).count_remaining() == 0 {
+                return Err(ConstraintViolation::NoCandidates { row: r, column: c });
+            }
+        }
+        self.validate_solution()?;
+        self.record_solution();
         Ok(())
     }
+    // what went wrong once every guess of a frame failed, the last being
+    // value at (row, column): the square has no values left, or (with a
+    // digit-centric GuessOrdering) the value has no square left in a unit
+    fn exhausted(&mut self, row: usize, column: usize, value: usize) -> ConstraintViolation {
+        if self.puzzle.get_square(row, column).count_remaining() == 0 {
+            return ConstraintViolation::NoCandidates { row, column };
+        }
+        let box_size = self.puzzle.box_size();
+        for unit in units_of(&QueueItem::new(row, column), box_size) {
+            if unit.cells(box_size).into_iter().all(|(r,c)| !self.puzzle.get_square(r,c).is_possible(value)) {
+                return ConstraintViolation::DigitMissing { unit, digit: value };
+            }
+        }
+        ConstraintViolation::NoCandidates { row, column }
+    }
+    // places the deepest frame's next value and propagates it, one level down
+    fn try_next_guess(&mut self, frames: &mut [Frame]) -> Result<bool, ConstraintViolation> {
+        let depth = self.get_depth() + frames.len();
+        let frame = frames.last_mut().unwrap();
+        let (i, j, value) = frame.guesses[frame.next];
+        frame.next += 1;
+        if self.cancelled.as_ref().is_some_and(|cancelled| cancelled.load(Ordering::Relaxed)) {
+            return Err(ConstraintViolation::Cancelled);
+        }
+        debug::debug(format!("(depth={}) guess: (i,j,value)=({},{},{})", depth - 1, i, j, value));
+        self.get_progress().total_guesses += 1;
+        if depth > self.get_progress().max_depth_reached {
+            self.get_progress().max_depth_reached = depth;
+        }
+        if depth > self.max_search_depth() {
+            return Err(ConstraintViolation::DepthLimitExceeded {
+                row: i,
+                column: j,
+                digit: value,
+                limit: self.max_search_depth(),
+            });
+        }
+        // apply the guess
+        if self.settings.is_recording {
+            self.steps.push(Step::guess(i, j, value));
+        }
+        self.puzzle.get_square(i,j).select(value);
+        self.commit(i, j);
+        self.constraint_queue.iter_mut().for_each(|constraint| constraint.forget());
+        // only the guessed square has changed
+        self.enqueue(QueueItem::new(i, j));
+        self.propagate()
+    }
+    // puts the square's change on the trail, so it can be rolled back
+    fn commit(&mut self, row: usize, column: usize) {
+        let index = (row-1)*self.puzzle.size() + (column-1);
+        let bitmap = self.puzzle.get_square(row, column).get_bitmap();
+        if bitmap != self.seen_bitmaps[index] {
+            self.trail.push((index, self.seen_bitmaps[index]));
+            self.seen_bitmaps[index] = bitmap;
+        }
+        self.puzzle.get_square(row, column).clean();
+    }
+    // undoes every change since the trail was mark long; changes not on the
+    // trail yet are put there first, so only the squares touched since the
+    // last commit are looked at, never the whole board
+    fn rollback(&mut self, mark: usize) {
+        for index in self.puzzle.take_touched() {
+            let bitmap = self.puzzle.grid().get_bitmap(index);
+            if bitmap != self.seen_bitmaps[index] {
+                self.trail.push((index, self.seen_bitmaps[index]));
+                self.seen_bitmaps[index] = bitmap;
+            }
+        }
+        while self.trail.len() > mark {
+            let (index, bitmap) = self.trail.pop().unwrap();
+            self.seen_bitmaps[index] = bitmap;
+            self.puzzle.restore(index, bitmap);
+        }
+        self.queue_item_list.clear();
+        self.pending_units.iter_mut().for_each(|removed| *removed = 0);
+    }
+}
+
+// a board for a worker of the parallel search, depth guesses down
+struct Branch {
+    puzzle: SudokuPuzzle,
+    depth: usize,
+    seed: u64,
+}
 
+// what a worker's search of one branch came to
+struct WorkerOutcome {
+    result: Result<(), ConstraintViolation>,
+    total_guesses: usize,
+    max_depth_reached: usize,
+    constraint_times: HashMap<String, u128>,
+}
+
+// one level of the search: the values to try for one square
+struct Frame {
+    guesses: Vec<(usize, usize, usize)>,
+    // the guess to try next; the one being tried is next - 1
+    next: usize,
+    // the trail length to roll back to before trying another value
+    mark: usize,
+    // as mark, for Solver::steps
+    steps_mark: usize,
+    // solutions found before this level, see count_solutions
+    solutions_before: usize,
 }
 
 #[cfg(test)]
@@ -475,7 +998,6 @@ mod tests {
               .iter().for_each(|(i,expected_depth)| {
                   assert_eq!(s3.get_progress_list()[*i].depth, *expected_depth);
               });
-          
       }
 
     #[test]
@@ -491,17 +1013,29 @@ mod tests {
         p.total_guesses = 1;
         assert_eq!(p.total_guesses, 1);
         assert_eq!(p2.total_guesses, 0);
-        
     }
 
     #[test]
     fn create_solver() {
+        let _s = Solver::new(get_sample_puzzle(), get_ordering());
+    }
+
+    #[test]
+    fn clean_all_cleans_every_square() {
+        // the loop over rows used to be (1..=0), which cleaned nothing
         let mut s = Solver::new(get_sample_puzzle(), get_ordering());
+        let squares: Vec<(usize, usize)> = (1..=9).flat_map(|r| (1..=9).map(move |c| (r,c))).collect();
+        let (r, c) = *squares.iter().find(|(r,c)| s.puzzle.get_square(*r,*c).count_remaining() > 1).unwrap();
+        let value = s.puzzle.get_square(r,c).values_remaining()[0];
+        s.puzzle.get_square(r,c).eliminate(value);
+        assert!(s.puzzle.get_square(r,c).is_dirty());
+        s.clean_all();
+        assert!(squares.iter().all(|(r,c)| !s.puzzle.get_square(*r,*c).is_dirty()));
     }
 
     #[test]
     fn test_vectors() {
-        let v = vec![1,2,3,4,5];
+        let v = [1,2,3,4,5];
         assert_eq!(v[1], 2);
     }
 
@@ -510,8 +1044,520 @@ mod tests {
         assert!(true);
     }
 
-    fn get_ordering() -> guess_ordering::SimpleGuessOrderingByTuple {
-        guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9))
+    #[test]
+    fn duplicate_given() {
+        let mut line = ".".repeat(81);
+        line.replace_range(0..1, "5");
+        line.replace_range(20..21, "5");
+        let puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&line).unwrap()).unwrap();
+        let mut s = Solver::new(puzzle, get_ordering());
+        assert_eq!(s.solve(), Err(ConstraintViolation::DuplicateDigit { unit: Unit::Box(1), digit: 5, row: 3, column: 3 }));
+    }
+
+    #[test]
+    fn recorded_steps_lead_to_the_solution() {
+        let puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut settings = Settings::new();
+        settings.is_recording = true;
+        // recording keeps the search on one thread
+        settings.search_threads = 4;
+        let mut s = Solver::new(puzzle, get_ordering());
+        s.with_settings(settings);
+        s.solve().unwrap();
+        assert!(s.get_total_guesses() > 0);
+        let solution: Vec<char> = s.get_puzzle().to_line().chars().collect();
+        let digit_at = |r: usize, c: usize| solution[(r-1)*9 + (c-1)].to_digit(10).unwrap() as usize;
+
+        let steps = s.get_steps();
+        let guesses: Vec<&Step> = steps.iter().filter(|step| step.technique == crate::explain::Technique::Guessing).collect();
+        assert!(guesses.iter().any(|step| !step.placed.is_empty()));
+        for step in steps {
+            // only the branch that held is left: every placement is right,
+            // and no elimination takes out a digit of the solution
+            assert!(step.placed.iter().all(|(r,c,d)| digit_at(*r,*c) == *d), "{}", step);
+            assert!(step.eliminated.iter().all(|(r,c,d)| digit_at(*r,*c) != *d), "{}", step);
+        }
+
+        // off by default
+        let puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut s = Solver::new(puzzle, get_ordering());
+        s.solve().unwrap();
+        assert!(s.get_steps().is_empty());
+    }
+
+    fn solver_for_line(line: &str) -> Solver {
+        let puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(line).unwrap()).unwrap();
+        Solver::new(puzzle, get_ordering())
+    }
+
+    #[test]
+    fn hints() {
+        // the first hint for this puzzle is a placement, and it is correct
+        let solution = "765312489348957162291864375874236591953481627126795834619523748587649213432178956";
+        let puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("sjm20200425").unwrap().puzzle);
+        match Solver::next_hint(&puzzle, &Settings::new()).unwrap() {
+            Hint::Place(step) => {
+                assert_eq!(step.placed.len(), 1);
+                let (r,c,d) = step.placed[0];
+                assert_eq!(solution.as_bytes()[(r-1)*9 + (c-1)], b'0' + d as u8);
+            },
+            other => panic!("unexpected: {:?}", other),
+        }
+        // the puzzle is left alone
+        assert_eq!(puzzle.to_line(), SudokuPuzzle::new(crate::sample_puzzles::get_by_id("sjm20200425").unwrap().puzzle).to_line());
+
+        // once logic is exhausted, only guessing remains
+        let mut puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        crate::grader::Grader::new().grade(&mut puzzle).unwrap();
+        let mut settings = Settings::new();
+        settings.is_poe_enabled = true;
+        assert_eq!(Solver::next_hint(&puzzle, &settings).unwrap(), Hint::GuessNeeded);
+
+        let puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(solution).unwrap()).unwrap();
+        assert_eq!(Solver::next_hint(&puzzle, &Settings::new()).unwrap(), Hint::Solved);
+    }
+
+    #[test]
+    fn hints_follow_the_settings() {
+        // stuck without ProcessOfElimination, which is off by default
+        let mut settings = Settings::new();
+        settings.max_search_depth = Some(0);
+        let mut s = Solver::new(SudokuPuzzle::new(crate::sample_puzzles::get_by_id("sjm20160302").unwrap().puzzle), get_ordering());
+        s.with_settings(settings.clone());
+        assert!(s.solve().is_err());
+        assert_eq!(Solver::next_hint(s.get_puzzle(), &settings).unwrap(), Hint::GuessNeeded);
+
+        // with it, the next move is a hidden single
+        settings.is_poe_enabled = true;
+        match Solver::next_hint(s.get_puzzle(), &settings).unwrap() {
+            Hint::Place(step) => assert_eq!(step.technique, Technique::HiddenSingle),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn count_solutions() {
+        // unique
+        let mut s = solver_for_line("7...12.89..8.57.....1.6....8......91......6..12......4....2.7.....64.2..43.17...6");
+        assert_eq!(s.count_solutions(10), Ok(1));
+        assert_eq!(s.get_puzzle().to_line(), "765312489348957162291864375874236591953481627126795834619523748587649213432178956");
+
+        // with rows 1 to 3 blank, any order of those rows is a solution
+        let mut s = solver_for_line(&format!("{}874236591953481627126795834619523748587649213432178956", ".".repeat(27)));
+        assert_eq!(s.count_solutions(5), Ok(5));
+        let lines: Vec<String> = s.get_solutions().iter().map(|p| p.to_line()).collect();
+        (0..5).for_each(|i| (i+1..5).for_each(|j| assert_ne!(lines[i], lines[j])));
+
+        // with rows 1 and 2 blank, swapping them gives a second solution
+        let mut s = solver_for_line(&format!("{}291864375874236591953481627126795834619523748587649213432178956", ".".repeat(18)));
+        assert_eq!(s.uniqueness(), Ok(Uniqueness::Multiple));
+
+        // declaring it unique does not change the count, even though the
+        // uniqueness constraints alone would rule out all but one solution
+        let mut s = solver_for_line("....5.....8.6....3..4....8.....2....1..76.4.....84.159....8....2......7.5.7..9...");
+        let mut settings = Settings::new();
+        settings.is_unique = true;
+        s.with_settings(settings);
+        assert_eq!(s.uniqueness(), Ok(Uniqueness::Multiple));
+    }
+
+    #[test]
+    fn no_solution() {
+        // square (1,9) has no candidates left
+        let mut s = solver_for_line(&format!("12345678.........9{}", ".".repeat(63)));
+        assert_eq!(s.uniqueness(), Ok(Uniqueness::NoSolution));
+        assert!(s.get_solutions().is_empty());
+    }
+
+    #[test]
+    fn solve_without_search() {
+        // sjm20200425, Mercury News, difficulty: 2/4
+        let mut s = solver_for_line("7...12.89..8.57.....1.6....8......91......6..12......4....2.7.....64.2..43.17...6");
+        let outcome = s.solve_without_search().unwrap();
+        assert!(outcome.is_solved);
+        assert_eq!(s.get_puzzle().to_line(), "765312489348957162291864375874236591953481627126795834619523748587649213432178956");
+        assert_eq!(s.get_total_guesses(), 0);
+        assert_eq!(outcome.fired[0].0, "NoRowDuplicates");
+        // POE is off by default, so it never fires
+        assert!(outcome.fired.iter().all(|(name, _)| !name.starts_with("ProcessOfElimination")));
+
+        // needs guessing: stuck, with candidates left
+        let puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut s = Solver::new(puzzle, get_ordering());
+        let outcome = s.solve_without_search().unwrap();
+        assert!(!outcome.is_solved);
+        assert_eq!(s.get_total_guesses(), 0);
+        assert!(s.get_puzzle().to_line().contains('.'));
+        assert!(s.get_puzzle().coordinates().into_iter().any(|(r,c)| s.get_puzzle().get_square(r,c).count_remaining() > 1));
+
+        // contradictions are still errors
+        let mut s = solver_for_line(&format!("12345678.........9{}", ".".repeat(63)));
+        assert!(s.solve_without_search().is_err());
+    }
+
+    #[test]
+    fn depth_limit_is_an_error() {
+        let puzzle = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut settings = Settings::new();
+        settings.max_search_depth = Some(1);
+        let mut s = Solver::new(puzzle, get_ordering());
+        s.with_settings(settings);
+        match s.solve() {
+            Err(ConstraintViolation::DepthLimitExceeded { limit, .. }) => assert_eq!(limit, 1),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn same_seed_same_search() {
+        let worldshardest = || SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        // randomized, as get_ordering is
+        let run = |seed: Option<u64>, threads: usize| {
+            let mut settings = Settings::new();
+            settings.seed = seed;
+            settings.search_threads = threads;
+            let mut s = Solver::new(worldshardest(), get_ordering());
+            s.with_settings(settings);
+            s.solve().unwrap();
+            (s.get_seed(), s.get_total_guesses(), s.get_max_depth_reached())
+        };
+        let first = run(Some(5), 1);
+        assert_eq!(first.0, 5);
+        assert_eq!(run(Some(5), 1), first);
+
+        // the seed picked when none is given replays the same way
+        let picked = run(None, 1);
+        assert_eq!(run(Some(picked.0), 1), picked);
+
+        // every branch of the parallel search is seeded from it too
+        let parallel = run(Some(5), 2);
+        assert_eq!(parallel.0, 5);
+        assert!(parallel.1 > 0);
+    }
+
+    #[test]
+    fn same_search_as_before_the_trail() {
+        // guesses and max depth from the engine that rescanned the whole
+        // board on every backtrack, with the default ordering, unrandomized
+        let expected = [
+            ("8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..", false, 828, 13),
+            ("8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..", true, 139, 7),
+            ("27...1.46.5.2...3....3.6...6.......7.4.....2.9.......3...19.....2...5.7.7..6...8.", false, 179, 10),
+            ("27...1.46.5.2...3....3.6...6.......7.4.....2.9.......3...19.....2...5.7.7..6...8.", true, 4, 1),
+            ("...9.3.......8....5......71.....1.938...7.....1..29...3.7.....4.49...8.....4..23.", false, 749, 11),
+            ("...9.3.......8....5......71.....1.938...7.....1..29...3.7.....4.49...8.....4..23.", true, 9, 4),
+            (".4.3...5...945....63........8...1...3...76...9....38..4..7...2.5.......1..31.2..9", false, 241, 14),
+            (".4.3...5...945....63........8...1...3...76...9....38..4..7...2.5.......1..31.2..9", true, 6, 2),
+        ];
+        for (line, is_poe_enabled, guesses, depth) in expected.iter() {
+            let mut settings = Settings::new();
+            settings.is_poe_enabled = *is_poe_enabled;
+            let mut ordering = guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+            ordering.with_randomization(false);
+            let mut s = Solver::new(SudokuPuzzle::new(SudokuPuzzle::parse_line(line).unwrap()), Box::new(ordering));
+            s.with_settings(settings);
+            s.solve().unwrap();
+            assert_eq!((s.get_total_guesses(), s.get_max_depth_reached()), (*guesses, *depth), "{} poe={}", line, is_poe_enabled);
+        }
+    }
+
+    #[test]
+    fn parallel_search() {
+        let worldshardest = || SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut sequential = Solver::new(worldshardest(), get_ordering());
+        sequential.solve().unwrap();
+        let solution = sequential.get_puzzle().to_line();
+
+        for split_depth in [1, 2] {
+            let mut settings = Settings::new();
+            settings.search_threads = 4;
+            settings.split_depth = split_depth;
+            let mut s = Solver::new(worldshardest(), get_ordering());
+            s.with_settings(settings);
+            s.solve().unwrap();
+            assert_eq!(s.get_puzzle().to_line(), solution);
+            assert!(s.validate_solution().is_ok());
+            // the guesses made while splitting count too
+            assert!(s.get_total_guesses() >= split_depth);
+            assert!(s.get_max_depth_reached() >= split_depth);
+            let mut names: Vec<String> = s.get_constraint_times_map().into_keys().collect();
+            let mut expected: Vec<String> = sequential.get_constraint_times_map().into_keys().collect();
+            names.sort();
+            expected.sort();
+            assert_eq!(names, expected);
+        }
+
+        // many solutions: whichever worker is first wins
+        let mut settings = Settings::new();
+        settings.search_threads = 4;
+        let mut s = solver_for_line(&format!("{}874236591953481627126795834619523748587649213432178956", ".".repeat(27)));
+        s.with_settings(settings.clone());
+        s.solve().unwrap();
+        assert!(s.validate_solution().is_ok());
+
+        // running out of depth is still an error
+        settings.max_search_depth = Some(1);
+        let mut s = Solver::new(worldshardest(), get_ordering());
+        s.with_settings(settings);
+        match s.solve() {
+            Err(ConstraintViolation::DepthLimitExceeded { limit, .. }) => assert_eq!(limit, 1),
+            other => panic!("unexpected: {:?}", other),
+        }
+    }
+
+    // empties (9,9) whatever it is given, without saying so
+    struct EmptiesLastSquare;
+    impl crate::constraint::Constraint for EmptiesLastSquare {
+        fn process(&mut self, _queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+            puzzle.get_square(9,9).bitmap_elimination(crate::squares::full_bitmap(9));
+            Ok(())
+        }
+        fn name(&self) -> String {
+            "EmptiesLastSquare".to_string()
+        }
+        fn elapsed_millis(&self) -> u128 {
+            0
+        }
+    }
+
+    // unit-based, and only notes the units it is shown and what they lost
+    struct UnitRecorder {
+        seen: Arc<Mutex<Vec<(Unit, usize)>>>,
+    }
+    impl crate::constraint::Constraint for UnitRecorder {
+        fn process(&mut self, _queue_item: &QueueItem, _puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+            Ok(())
+        }
+        fn name(&self) -> String {
+            "UnitRecorder".to_string()
+        }
+        fn elapsed_millis(&self) -> u128 {
+            0
+        }
+        fn is_unit_based(&self) -> bool {
+            true
+        }
+        fn process_unit(&mut self, unit: Unit, removed: usize, _puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+            self.seen.lock().unwrap().push((unit, removed));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn unit_constraints_only_see_changed_units() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let recorded = seen.clone();
+        let mut settings = Settings::new();
+        settings.pipeline = crate::pipeline::Pipeline::parse("NoRowDuplicates").unwrap()
+            .with_constraint(move |_| Box::new(UnitRecorder { seen: recorded.clone() }));
+        let mut s = solver_for_line(&".".repeat(81));
+        s.with_settings(settings);
+        s.remember_bitmaps();
+
+        s.puzzle.get_square(2,5).eliminate(3);
+        s.puzzle.get_square(2,6).eliminate(4);
+        s.enqueue_all_dirty_and_mark_clean();
+        assert_eq!(s.propagate(), Ok(false));
+        // row 2 and box 2 hold both squares, columns 5 and 6 one each
+        let (three, four) = (crate::squares::bit(3), crate::squares::bit(4));
+        assert_eq!(*seen.lock().unwrap(), vec![
+            (Unit::Row(2), three | four),
+            (Unit::Column(5), three),
+            (Unit::Column(6), four),
+            (Unit::Box(2), three | four),
+        ]);
+
+        // nothing changed, nothing to look at
+        seen.lock().unwrap().clear();
+        s.enqueue_all_dirty_and_mark_clean();
+        assert_eq!(s.propagate(), Ok(false));
+        assert!(seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn queued_squares_carry_what_they_lost() {
+        let mut s = solver_for_line(&".".repeat(81));
+        s.remember_bitmaps();
+        let queued = |s: &mut Solver| -> Vec<(usize, usize, usize)> {
+            s.enqueue_all_dirty_and_mark_clean();
+            s.queue_item_list.drain(..).map(|qitem| (qitem.row, qitem.column, qitem.removed)).collect()
+        };
+        let bit = crate::squares::bit;
+
+        s.puzzle.get_square(2,5).eliminate(3);
+        assert_eq!(queued(&mut s), vec![(2, 5, bit(3))]);
+
+        // only what went since it was last queued, not 3 again
+        s.puzzle.get_square(2,5).eliminate_list(&[4, 7]);
+        s.puzzle.get_square(2,5).eliminate(3);
+        assert_eq!(queued(&mut s), vec![(2, 5, bit(4) | bit(7))]);
+
+        // changes between queuings add up; squares handed out but unchanged
+        // are not queued
+        s.puzzle.get_square(9,9).eliminate(3);
+        s.puzzle.get_square(9,9).select(5);
+        s.puzzle.get_square(1,1);
+        assert_eq!(queued(&mut s), vec![(9, 9, crate::squares::full_bitmap(9) & !bit(5))]);
+        assert_eq!(queued(&mut s), vec![]);
+    }
+
+    #[test]
+    fn parallel_search_with_nothing_to_guess() {
+        // propagation stalls with (9,9) empty and every other square solved,
+        // so there is no guess to split on
+        let solved = "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+        let line = format!("{}.", &solved[..80]);
+        for threads in [1, 2] {
+            let mut settings = Settings::new();
+            settings.search_threads = threads;
+            settings.pipeline = crate::pipeline::Pipeline::parse("NoRowDuplicates").unwrap()
+                .with_constraint(|_| Box::new(EmptiesLastSquare));
+            let mut s = solver_for_line(&line);
+            s.with_settings(settings);
+            assert_eq!(s.solve(), Err(ConstraintViolation::NoCandidates { row: 9, column: 9 }), "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn parallel_search_adds_up_its_parts() {
+        let worldshardest = SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle);
+        let mut settings = Settings::new();
+        settings.search_threads = 3;
+        settings.split_depth = 2;
+        let mut s = Solver::new(worldshardest, get_ordering());
+        s.with_settings(settings);
+        s.solve().unwrap();
+
+        // the splitting, then at least one branch
+        assert!(s.parts.len() >= 2);
+        assert_eq!(s.get_total_guesses(), s.parts.iter().map(|part| part.total_guesses).sum::<usize>());
+        assert_eq!(s.get_max_depth_reached(), s.parts.iter().map(|part| part.max_depth_reached).max().unwrap());
+        // this solver's own constraints time what ran before the split, so
+        // constraint_times holds just the parts' times
+        for part in s.parts.iter() {
+            assert!(part.constraint_times.keys().all(|name| s.constraint_times.contains_key(name)));
+        }
+        for (name, millis) in s.constraint_times.iter() {
+            let sum: u128 = s.parts.iter().map(|part| part.constraint_times.get(name).copied().unwrap_or(0)).sum();
+            assert_eq!(*millis, sum, "{}", name);
+        }
+    }
+
+    #[test]
+    fn same_answer_on_any_number_of_threads() {
+        // each has exactly one solution, so which worker finds it first
+        // does not matter
+        let lines = [
+            SudokuPuzzle::new(crate::sample_puzzles::get_by_id("worldshardest").unwrap().puzzle).to_line(),
+            "8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4..".to_string(),
+            "...9.3.......8....5......71.....1.938...7.....1..29...3.7.....4.49...8.....4..23.".to_string(),
+        ];
+        for line in lines.iter() {
+            let solve = |search_threads: usize, split_depth: usize| {
+                let mut settings = Settings::new();
+                settings.search_threads = search_threads;
+                settings.split_depth = split_depth;
+                let mut s = solver_for_line(line);
+                s.with_settings(settings);
+                s.solve().unwrap();
+                s.get_puzzle().to_line()
+            };
+            let solution = solve(1, 1);
+            assert!(!solution.contains('.'));
+            for (search_threads, split_depth) in [(2, 1), (4, 1), (4, 3)] {
+                assert_eq!(solve(search_threads, split_depth), solution, "{} on {} threads", line, search_threads);
+            }
+        }
+    }
+
+    // a valid grid of any size: row r is the first row shifted by
+    // box_size * (r % box_size) + r / box_size
+    fn pattern_solution(box_size: usize) -> String {
+        let size = box_size * box_size;
+        (0..size).flat_map(|r| (0..size).map(move |c| {
+            let value = (box_size * (r % box_size) + r / box_size + c) % size + 1;
+            crate::sudoku_puzzle::value_to_char(value, size)
+        })).collect()
+    }
+
+    #[test]
+    fn solve_4x4() {
+        let solution = pattern_solution(2);
+        assert_eq!(solution, "1234341223414123");
+        let mut s = solver_for_line("1..4..1..3.14..3");
+        assert_eq!(s.count_solutions(2), Ok(1));
+        assert_eq!(s.get_puzzle().to_line(), solution);
+
+        // every 4x4 grid
+        let mut s = solver_for_line(&".".repeat(16));
+        assert_eq!(s.count_solutions(300), Ok(288));
+    }
+
+    #[test]
+    fn weak_pipelines_never_report_partial_solutions() {
+        // with only rows checked, propagation stops early and the search
+        // does the rest, but only a validated grid counts as solved
+        let mut settings = Settings::new();
+        settings.pipeline = crate::pipeline::Pipeline::parse("NoRowDuplicates").unwrap();
+        let mut s = solver_for_line("1..4..1..3.14..3");
+        s.with_settings(settings);
+        assert_eq!(s.solve(), Ok(()));
+        assert_eq!(s.get_puzzle().to_line(), "1234341223414123");
+        assert!(s.validate_solution().is_ok());
+    }
+
+    #[test]
+    fn solve_16x16() {
+        let solution = pattern_solution(4);
+        assert_eq!(&solution[..16], "0123456789ABCDEF");
+        // blank out about a third of the squares
+        let line: String = solution.chars().enumerate()
+            .map(|(index, c)| match (index * 7 + index / 16) % 3 {
+                0 => '.',
+                _ => c,
+            })
+            .collect();
+        let mut s = solver_for_line(&line);
+        assert_eq!(s.get_puzzle().size(), 16);
+        s.solve().unwrap();
+        assert_eq!(s.get_puzzle().to_line(), solution);
+
+        // a duplicate given in the same box
+        let mut line = ".".repeat(256);
+        line.replace_range(0..1, "F");
+        line.replace_range(17..18, "F");
+        assert_eq!(solver_for_line(&line).solve(),
+                   Err(ConstraintViolation::DuplicateDigit { unit: Unit::Box(1), digit: 16, row: 2, column: 2 }));
+    }
+
+    #[test]
+    fn default_depth_follows_the_board() {
+        // an empty 16x16 board takes about 150 levels of guesses, far more
+        // than a 9x9 one ever needs; not randomized, since some random
+        // orders take far longer on an empty board
+        let empty_board = || {
+            let mut ordering = guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9));
+            ordering.with_randomization(false);
+            Solver::new(SudokuPuzzle::try_new(SudokuPuzzle::parse_line(&".".repeat(256)).unwrap()).unwrap(), Box::new(ordering))
+        };
+        let mut s = empty_board();
+        s.with_settings(Settings::new());
+        s.solve().unwrap();
+        assert!(s.validate_solution().is_ok());
+        assert!(s.get_max_depth_reached() > 30);
+        assert_eq!(s.max_search_depth(), 256);
+
+        // set explicitly, the limit holds whatever the board
+        let mut settings = Settings::new();
+        settings.max_search_depth = Some(30);
+        let mut s = empty_board();
+        s.with_settings(settings);
+        assert!(matches!(s.solve(), Err(ConstraintViolation::DepthLimitExceeded { limit: 30, .. })));
+        assert_eq!(solver_for_line(&".".repeat(81)).max_search_depth(), 81);
+    }
+
+    fn get_ordering() -> Box<dyn GuessOrdering> {
+        Box::new(guess_ordering::SimpleGuessOrderingByTuple::new((4,3,2,5,6,7,8,9)))
     }
 
     fn get_sample_puzzle() -> SudokuPuzzle {
@@ -1,204 +1,132 @@
-pub fn get_bitmap_values() -> Vec<usize> {
-    return [511, 1, 2, 4, 8, 16, 32, 64, 128, 256]
-        .iter().cloned().collect();
+use crate::grid::Grid;
+use crate::grid::SquareSet;
+
+// boards are size x size, see SudokuPuzzle::size
+pub const MAX_SIZE: usize = 25;
+
+// value 1 is bit 0, value 2 is bit 1, ...
+pub fn bit(value: usize) -> usize {
+    1 << (value - 1)
 }
 
-pub trait Square {
-    fn count_remaining(&self) -> usize;
-    fn has_single_value(&self) -> bool;
-    fn get_single_value(&mut self) -> usize;
-    fn get_single_value_nomut(&self) -> usize;
-    fn eliminate(&mut self, arg: usize) -> ();
-    fn eliminate_list(&mut self, arg: &Vec<usize>) -> ();
-    fn bitmap_elimination(&mut self, bitmap: usize) -> ();
-    fn select(&mut self, arg: usize) -> ();
-    fn is_dirty(&self) -> bool;
-    fn clean(&mut self) -> ();
-    fn is_possible(&self, value: usize) -> bool;
-    fn values_remaining(&self) -> Vec<usize>;
-    fn get_bitmap(&self) -> usize;
-    fn clone(&self) -> Box<dyn Square>;
+// every value possible: 511 for 9x9, 65535 for 16x16
+pub fn full_bitmap(size: usize) -> usize {
+    (1 << size) - 1
 }
 
-#[derive(Default)]
-pub struct DerivedSquare {
-    bitmap: usize,
-    single_value: Option<usize>,
-    dirty: bool,
+// the values whose bits are set, lowest first
+pub fn bitmap_values(bitmap: usize) -> Vec<usize> {
+    (1..=MAX_SIZE)
+        .filter(|value| bitmap & bit(*value) != 0)
+        .collect()
 }
-impl DerivedSquare {
-    pub fn copy(initial_state_bitmap: usize) -> Self {
-        DerivedSquare {
-            bitmap: initial_state_bitmap,
-            ..Default::default()
-        }
-    }
-    pub fn new() -> Self {
-        DerivedSquare {
-            bitmap: get_bitmap_values()[0],
-            ..Default::default()
-        }
-    }
-    pub fn number_of_bits(bitmap: usize) -> usize {
-        match bitmap {
-            0 => 0,
-            _ => 1 + DerivedSquare::number_of_bits(bitmap & bitmap-1),
-        }
-    }
-    fn calculate_single_value(&self) -> usize {
-        let mut ret_value = 0;
-        [1,2,3,4,5,6,7,8,9]
-            .iter()
-            .for_each(|i| if self.is_possible(*i) { ret_value = *i });
-        match ret_value {
-            0 => panic!("error finding single value!"),
-            _ => ret_value,
-        }
-    }
+
+// how many candidates a bitmap holds
+pub fn number_of_bits(bitmap: usize) -> usize {
+    bitmap.count_ones() as usize
 }
-impl Square for DerivedSquare {
-    fn clone(&self) -> Box<dyn Square> {
-        Box::new(DerivedSquare::copy(self.get_bitmap()))
+
+// one square of a SudokuPuzzle's grid, as get_square hands it out. A given
+// ignores eliminate and select; any other square panics when told to
+// select a value it no longer has, and is dirty from the moment it loses a
+// candidate until clean is called, which is how the solver finds the
+// squares that changed
+pub struct Square<'a> {
+    grid: &'a mut Grid,
+    // the board's dirty squares, this one among them while it is dirty
+    dirty: &'a mut SquareSet,
+    index: usize,
+}
+impl<'a> Square<'a> {
+    pub fn new(grid: &'a mut Grid, dirty: &'a mut SquareSet, index: usize) -> Self {
+        Square { grid, dirty, index }
     }
-    fn count_remaining(&self) -> usize {
-        DerivedSquare::number_of_bits(self.bitmap)
+    pub fn count_remaining(&self) -> usize {
+        self.grid.count_remaining(self.index)
     }
-    fn has_single_value(&self) -> bool {
+    pub fn has_single_value(&self) -> bool {
         self.count_remaining() == 1
     }
-    fn get_single_value_nomut(&self) -> usize {
-        if !self.has_single_value() {
-            panic!("do not call get_single_value if no single value!");
+    pub fn get_single_value(&self) -> usize {
+        match self.grid.get_single_value(self.index) {
+            Some(value) => value,
+            None => panic!("do not call get_single_value if no single value!"),
         }
-        self.calculate_single_value()
     }
-    // this is mutable because of memoization on derived squares
-    fn get_single_value(&mut self) -> usize {
-        if !self.has_single_value() {
-            panic!("do not call get_single_value if no single value!");
-        }
-        match self.single_value {
-            Some(i) => i,
-            None => {
-                let single_value = self.calculate_single_value();
-                self.single_value = Some(single_value);
-                single_value
-            }
+    pub fn eliminate(&mut self, value: usize) {
+        if self.grid.is_given(self.index) {
+            return;
         }
-    }
-    fn eliminate(&mut self, arg: usize) -> () {
-        // not sure why the "mut" is not required
-        if arg < 1 || arg > 9 {
-            panic!("Sudoku only works 1-9: {}", arg);
+        if !(1..=self.grid.size()).contains(&value) {
+            panic!("Sudoku only works 1-{}: {}", self.grid.size(), value);
         }
-        self.bitmap_elimination(get_bitmap_values()[arg])
+        self.bitmap_elimination(bit(value))
     }
-    fn eliminate_list(&mut self, arg: &Vec<usize>) -> () {
-        let mut cumulative_bitmap: usize = 0;
-        for el in arg {
-            cumulative_bitmap = cumulative_bitmap ^ get_bitmap_values()[*el];
-        }
-        self.bitmap_elimination(cumulative_bitmap)
-        // for loop also did not work (borrow checker)
-        // for el in arg {
-        //     self.eliminate(el);
-        // }
-
-        // iterators did not work
-        // arg
-        //     .iter()
-        //     .for_each(|i| self.eliminate(*i))
+    pub fn eliminate_list(&mut self, values: &[usize]) {
+        let bitmap = values.iter().fold(0, |acc, value| acc ^ bit(*value));
+        self.bitmap_elimination(bitmap)
     }
-    fn bitmap_elimination(&mut self, bitmap: usize) -> () {
-        let previous_bitmap = self.bitmap;
-        self.bitmap = self.bitmap & (get_bitmap_values()[0] ^ bitmap);
-        if previous_bitmap != self.bitmap {
-            self.dirty = true;
+    pub fn bitmap_elimination(&mut self, bitmap: usize) {
+        if self.grid.bitmap_elimination(self.index, bitmap) {
+            self.dirty.insert(self.index);
         }
-        ()
     }
-    fn select(&mut self, arg: usize) -> () {
-        match self.is_possible(arg) {
-            false => panic!("cannot select"),
-            true => {
-                let is_nop = get_bitmap_values()[arg] == self.bitmap;
-                match is_nop {
-                    true => (),
-                    false => {
-                        self.dirty = true;
-                        self.bitmap = get_bitmap_values()[arg];
-                        ()
-                    }
-                }
-            }
+    pub fn select(&mut self, value: usize) {
+        if self.grid.select(self.index, value) {
+            self.dirty.insert(self.index);
         }
     }
-    fn is_dirty(&self) -> bool { self.dirty }
-    fn clean(&mut self) -> () {
-        self.dirty = false;
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.contains(self.index)
     }
-    fn is_possible(&self, value: usize) -> bool {
-        self.bitmap & get_bitmap_values()[value] != 0
+    pub fn clean(&mut self) {
+        self.dirty.remove(self.index);
     }
-    fn values_remaining(&self) -> Vec<usize> {
-        [1,2,3,4,5,6,7,8,9]
-            .iter()
-            .filter(|i| self.is_possible(**i as usize))
-            .map(|i| *i as usize)
-            .collect()
+    pub fn is_possible(&self, value: usize) -> bool {
+        (1..=self.grid.size()).contains(&value) && self.get_bitmap() & bit(value) != 0
     }
-    fn get_bitmap(&self) -> usize { self.bitmap }
-}
-
-pub struct InitialSquare {
-    value: usize,
-}
-impl InitialSquare {
-    pub fn new(value: usize) -> Self {
-        assert!(value>=1 && value<=9);
-        InitialSquare {
-            value
-        }
+    pub fn values_remaining(&self) -> Vec<usize> {
+        bitmap_values(self.get_bitmap())
     }
-}
-impl Square for InitialSquare {
-    fn clone(&self) -> Box<dyn Square> {
-        Box::new(InitialSquare::new(self.get_single_value_nomut()))
+    pub fn get_bitmap(&self) -> usize {
+        self.grid.get_bitmap(self.index)
     }
-    fn count_remaining(&self) -> usize { 1 }
-    fn has_single_value(&self) -> bool { true }
-    fn get_single_value(&mut self) -> usize { self.value }
-    fn get_single_value_nomut(&self) -> usize { self.value }
-    fn eliminate(&mut self, _arg: usize) -> () {}
-    fn eliminate_list(&mut self, _arg: &Vec<usize>) -> () {}
-    fn bitmap_elimination(&mut self, _bitmap: usize) -> () {}
-    fn select(&mut self, _arg: usize) -> () {}
-    fn is_dirty(&self) -> bool { false }
-    fn clean(&mut self) -> () {}
-    fn is_possible(&self, value: usize) -> bool { value == self.value }
-    fn values_remaining(&self) -> Vec<usize> {
-        [self.value].iter().cloned().collect()
+    // puts back candidates taken away, when the solver backtracks; leaves
+    // the square clean
+    pub fn restore(&mut self, bitmap: usize) {
+        self.grid.restore(self.index, bitmap);
+        self.dirty.remove(self.index);
     }
-    fn get_bitmap(&self) -> usize { get_bitmap_values()[self.value] }
 }
 
 #[cfg(test)]
 mod tests {
 
-    // use super::InitialSquare;
-
     #[test]
     fn always_pass() {
         assert!(true);
     }
 
+    // square 0 of an empty board, and the board's dirty squares
+    fn open(size: usize) -> (super::Grid, super::SquareSet) {
+        (super::Grid::new(size), super::SquareSet::new())
+    }
+
+    // square 0 of a 9x9 board, given value
+    fn given(value: usize) -> (super::Grid, super::SquareSet) {
+        let mut grid = super::Grid::new(9);
+        grid.give(0, value);
+        (grid, super::SquareSet::new())
+    }
+
     mod derived_square {
-        use super::super::DerivedSquare;
         use super::super::Square;
+        use super::open;
 
         #[test]
         fn get_bitmap() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             assert!(s.get_bitmap() == 511);
             [3,4,5,6,7,8,9]
                 .iter()
@@ -208,7 +136,8 @@ mod tests {
 
         #[test]
         fn values_remaining() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             [1,2,3,4,5,6,7,8,9]
                 .iter()
                 .zip(&s.values_remaining())
@@ -224,7 +153,8 @@ mod tests {
 
         #[test]
         fn is_possible() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             [1,2,3,4,5,6,7,8,9]
                 .iter()
                 .for_each(|i| assert!(s.is_possible(*i)));
@@ -239,12 +169,12 @@ mod tests {
                         _ => assert!(!s.is_possible(*i)),
                     }
                 });
-            
         }
 
         #[test]
         fn clean() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             assert!(!s.is_dirty());
             s.select(3);
             assert!(s.is_dirty());
@@ -255,7 +185,8 @@ mod tests {
 
         #[test]
         fn is_dirty() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             assert!(!s.is_dirty());
             assert!(!s.is_dirty());
             s.select(3);
@@ -266,27 +197,45 @@ mod tests {
 
         #[test]
         fn select() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             s.select(1);
             s.select(1);
         }
 
+        #[test]
+        fn restore() {
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
+            s.select(4);
+            assert_eq!(s.get_single_value(), 4);
+            s.restore(0b101000);
+            assert!(!s.is_dirty());
+            assert_eq!(s.values_remaining(), vec![4,6]);
+            s.eliminate(4);
+            assert_eq!(s.get_single_value(), 6);
+        }
+
         #[test]
         fn bitmap_elimination() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             s.bitmap_elimination(8);
             s.bitmap_elimination(8);
         }
 
         #[test]
         fn eliminate_list() {
-            let mut s = DerivedSquare::new();
-            s.eliminate_list(&vec![1, 2]);
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
+            s.eliminate_list(&[1, 2]);
+            assert!(s.get_bitmap() == 508);
         }
 
         #[test]
         fn eliminate() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             s.eliminate(1);
             s.eliminate(2);
         }
@@ -294,13 +243,15 @@ mod tests {
         #[test]
         #[should_panic]
         fn get_single_value_panic() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let s = Square::new(&mut grid, &mut dirty, 0);
             s.get_single_value();
         }
 
         #[test]
         fn get_single_value() {
-            let mut s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
             s.select(1);
             assert!(s.get_single_value() == 1);
             assert!(s.get_single_value() == 1);
@@ -308,27 +259,40 @@ mod tests {
 
         #[test]
         fn has_single_value() {
-            let s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let s = Square::new(&mut grid, &mut dirty, 0);
             assert!(!s.has_single_value());
             assert!(!s.has_single_value());
         }
 
         #[test]
         fn count_remaining() {
-            let s = DerivedSquare::new();
+            let (mut grid, mut dirty) = open(9);
+            let s = Square::new(&mut grid, &mut dirty, 0);
             assert!(s.count_remaining() == 9);
             assert!(s.count_remaining() == 9);
         }
+
+        #[test]
+        fn sixteen() {
+            let (mut grid, mut dirty) = open(16);
+            let mut s = Square::new(&mut grid, &mut dirty, 0);
+            assert!(s.get_bitmap() == 65535);
+            (1..=15).for_each(|i| s.eliminate(i));
+            assert!(s.get_single_value() == 16);
+            assert!(!s.is_possible(17));
+        }
     }
 
     mod initial_square {
 
-        use super::super::InitialSquare;
         use super::super::Square;
+        use super::given;
 
         #[test]
         fn get_bitmap() {
-            let s = InitialSquare::new(5);
+            let (mut grid, mut dirty) = given(5);
+            let s = Square::new(&mut grid, &mut dirty, 0);
             // this is how bitmap values is structured (5th element is 2^^4)
             assert!(s.get_bitmap() == 16);
             assert!(s.get_bitmap() == 16);
@@ -336,9 +300,9 @@ mod tests {
 
         #[test]
         fn values_remaining() {
-            let s = InitialSquare::new(5);
+            let (mut grid, mut dirty) = given(5);
+            let s = Square::new(&mut grid, &mut dirty, 0);
 
-            let remaining = s.values_remaining();
             let remaining = s.values_remaining();
 
             assert!(remaining.len() == 1);
@@ -347,7 +311,8 @@ mod tests {
 
         #[test]
         fn is_possible() {
-            let s = InitialSquare::new(5);
+            let (mut grid, mut dirty) = given(5);
+            let s = Square::new(&mut grid, &mut dirty, 0);
             [1,2,3,4,6,7,8,9]
                 .iter()
                 .for_each(|val| assert!(!s.is_possible(*val)));
@@ -356,55 +321,67 @@ mod tests {
 
         #[test]
         fn clean() {
-            let mut nine = InitialSquare::new(9);
+            let (mut grid, mut dirty) = given(9);
+            let mut nine = Square::new(&mut grid, &mut dirty, 0);
             nine.clean();
             nine.clean();
         }
 
         #[test]
         fn is_dirty() {
-            let eight = InitialSquare::new(8);
+            let (mut grid, mut dirty) = given(8);
+            let mut eight = Square::new(&mut grid, &mut dirty, 0);
+            eight.eliminate(8);
             assert!(!eight.is_dirty());
             assert!(!eight.is_dirty());
         }
 
         #[test]
         fn select() {
-            let mut seven = InitialSquare::new(7);
+            let (mut grid, mut dirty) = given(7);
+            let mut seven = Square::new(&mut grid, &mut dirty, 0);
             seven.select(0);
             seven.select(1);
             seven.select(7);
             seven.select(9);
             seven.select(10);
+            assert!(seven.get_single_value() == 7);
         }
 
         #[test]
         fn bitmap_elimination() {
-            let mut six = InitialSquare::new(6);
-            six.bitmap_elimination(8);
-            six.bitmap_elimination(8);
+            let (mut grid, mut dirty) = given(6);
+            let mut six = Square::new(&mut grid, &mut dirty, 0);
+            six.bitmap_elimination(32);
+            six.bitmap_elimination(32);
+            assert!(six.get_single_value() == 6);
         }
 
         #[test]
         fn eliminate_list() {
-            let mut five = InitialSquare::new(5);
+            let (mut grid, mut dirty) = given(5);
+            let mut five = Square::new(&mut grid, &mut dirty, 0);
             let some_values = vec![1, 2];
             five.eliminate_list(&some_values);
             five.eliminate_list(&some_values);
+            assert!(five.get_single_value() == 5);
         }
 
         #[test]
         fn eliminate() {
-            let mut four = InitialSquare::new(4);
+            let (mut grid, mut dirty) = given(4);
+            let mut four = Square::new(&mut grid, &mut dirty, 0);
             four.eliminate(0);
             four.eliminate(1);
             four.eliminate(4);
             four.eliminate(10);
+            assert!(four.get_single_value() == 4);
         }
 
         #[test]
         fn get_single_value() {
-            let mut three = InitialSquare::new(3);
+            let (mut grid, mut dirty) = given(3);
+            let three = Square::new(&mut grid, &mut dirty, 0);
             assert!(three.get_single_value() == 3);
             assert!(three.get_single_value() == 3);
         }
@@ -413,36 +390,38 @@ mod tests {
         fn new() {
             [1,2,3,4,5,6,7,8,9]
                 .iter()
-                .for_each(|i| { InitialSquare::new(*i); () })
+                .for_each(|i| { given(*i); })
         }
 
         #[test]
         #[should_panic]
         fn new_toohigh() {
-            InitialSquare::new(10);
+            given(10);
         }
 
         #[test]
         #[should_panic(expected="assert")]
         fn new_toolow() {
-            InitialSquare::new(0);
+            given(0);
         }
 
         #[test]
         fn has_single_value() {
-            let one = InitialSquare::new(1);
+            let (mut grid, mut dirty) = given(1);
+            let one = Square::new(&mut grid, &mut dirty, 0);
             assert!(one.has_single_value());
             assert!(one.has_single_value());
         }
 
         #[test]
         fn count_remaining() {
-            let two = InitialSquare::new(2);
+            let (mut grid, mut dirty) = given(2);
+            let two = Square::new(&mut grid, &mut dirty, 0);
             assert!(two.count_remaining() == 1);
             assert!(two.count_remaining() == 1);
         }
-        
+
     }
-    
+
 
 }
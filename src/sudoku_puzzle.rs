@@ -1,61 +1,255 @@
 //mod squares;
 
-use crate::squares::InitialSquare;
-use crate::squares::DerivedSquare;
 use crate::squares::Square;
+use crate::squares;
 use crate::config;
+use crate::grid::Grid;
+use crate::grid::SquareSet;
+use std::fmt;
+
+// box sizes we can parse: 4x4, 9x9, 16x16 and 25x25 boards
+pub const BOX_SIZES: [usize; 4] = [2, 3, 4, 5];
+
+// 4 for a 16x16 board, None if size is not one of the supported sizes
+pub fn box_size_for(size: usize) -> Option<usize> {
+    BOX_SIZES.iter().find(|box_size| *box_size * *box_size == size).copied()
+}
+
+// the characters for values 1..=size: digits up to 9x9, hex digits for
+// 16x16 (value 1 is '0') and letters for 25x25 (value 1 is 'A')
+pub fn alphabet(size: usize) -> &'static str {
+    match size {
+        16 => "0123456789ABCDEF",
+        25 => "ABCDEFGHIJKLMNOPQRSTUVWXY",
+        _ => &"123456789"[..size],
+    }
+}
+
+// lowercase letters are accepted too
+pub fn char_to_value(c: char, size: usize) -> Option<usize> {
+    alphabet(size)
+        .chars()
+        .position(|a| a == c.to_ascii_uppercase())
+        .map(|index| index + 1)
+}
+
+pub fn value_to_char(value: usize, size: usize) -> char {
+    alphabet(size).as_bytes()[value - 1] as char
+}
+
+// in the single-line format '.' is always a blank; '0' is too, unless it is
+// part of the alphabet
+fn is_blank(c: char, size: usize) -> bool {
+    c == '.' || (c == '0' && size <= 9)
+}
+
+fn sizes_list(sizes: &[usize]) -> String {
+    let sizes: Vec<String> = sizes.iter().map(|size| size.to_string()).collect();
+    format!("{} or {}", sizes[..sizes.len()-1].join(", "), sizes[sizes.len()-1])
+}
+
+// rows and columns are 1-based, as in get_square
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    WrongLength { length: usize },
+    WrongRowCount { rows: usize },
+    WrongRowLength { row: usize, length: usize, expected: usize },
+    InvalidCharacter { row: usize, column: usize, character: char },
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::WrongLength { length } => {
+                let lengths: Vec<usize> = BOX_SIZES.iter().map(|b| b.pow(4)).collect();
+                write!(f, "expected {} squares, found {}", sizes_list(&lengths), length)
+            },
+            ParseError::WrongRowCount { rows } => {
+                let sizes: Vec<usize> = BOX_SIZES.iter().map(|b| b * b).collect();
+                write!(f, "expected {} rows, found {}", sizes_list(&sizes), rows)
+            },
+            ParseError::WrongRowLength { row, length, expected } =>
+                write!(f, "row {}: expected {} squares, found {}", row, expected, length),
+            ParseError::InvalidCharacter { row, column, character } =>
+                write!(f, "row {}, column {}: unexpected character '{}'", row, column, character),
+        }
+    }
+}
 
 pub struct SudokuPuzzle {
-    pub initial_strings: Vec<String>,
-    pub rows: Vec<Vec<Box<dyn Square>>>,
+    // every square's candidates, and which squares were given; see Grid
+    grid: Grid,
+    box_size: usize,
+    // the squares that lost candidates since they were last cleaned, see
+    // Square::is_dirty
+    dirty: SquareSet,
+    // indexes of the squares handed out by get_square since the last
+    // take_touched, each once; constraints only change squares through
+    // get_square, so no other square can have changed
+    touched: Vec<usize>,
+    is_touched: SquareSet,
 }
 impl SudokuPuzzle {
     pub fn new(initial_strings: Vec<String>) -> Self {
-        assert_eq!(initial_strings.len(), 9);
-        initial_strings
-            .iter()
-            .for_each(|s| assert_eq!(s.len(), 9));
-        let rows = SudokuPuzzle::calculate_rows_from_initial_strings(&initial_strings);
-        SudokuPuzzle {
-            initial_strings,
-            rows: rows,
+        match SudokuPuzzle::try_new(initial_strings) {
+            Ok(puzzle) => puzzle,
+            Err(e) => panic!("invalid puzzle: {}", e),
         }
     }
-    pub fn clone_from(from_puzzle: &SudokuPuzzle) -> Self {
-        // this is buggy (it always creates derived squares)
-        // update: bug fixed! (uses clone)
-        let mut rows: Vec<Vec<Box<dyn Square>>> = vec![];
+    // the board size follows from the number of rows; each row has one
+    // character per square, ' ' for blanks, see alphabet
+    pub fn try_new(initial_strings: Vec<String>) -> Result<Self, ParseError> {
+        let size = initial_strings.len();
+        if box_size_for(size).is_none() {
+            return Err(ParseError::WrongRowCount { rows: size });
+        }
+        for (i, s) in initial_strings.iter().enumerate() {
+            let length = s.chars().count();
+            if length != size {
+                return Err(ParseError::WrongRowLength { row: i+1, length, expected: size });
+            }
+            if let Some((j, c)) = s.chars().enumerate().find(|(_, c)| *c != ' ' && char_to_value(*c, size).is_none()) {
+                return Err(ParseError::InvalidCharacter { row: i+1, column: j+1, character: c });
+            }
+        }
+        let mut grid = Grid::new(size);
+        for (index, c) in initial_strings.concat().chars().enumerate() {
+            if c != ' ' {
+                grid.give(index, char_to_value(c, size).unwrap());
+            }
+        }
+        Ok(SudokuPuzzle::from_grid(&grid))
+    }
+    // the rows new() was given: the givens, ' ' for every other square
+    pub fn initial_strings(&self) -> Vec<String> {
+        let size = self.size();
+        self.grid.givens_line()
+            .replace('.', " ")
+            .chars()
+            .collect::<Vec<char>>()
+            .chunks(size)
+            .map(|row| row.iter().collect())
+            .collect()
+    }
+    // squares per row, column and box
+    pub fn size(&self) -> usize {
+        self.box_size * self.box_size
+    }
+    pub fn box_size(&self) -> usize {
+        self.box_size
+    }
+    // every (row, column), 1-based, row by row
+    pub fn coordinates(&self) -> Vec<(usize, usize)> {
+        let size = self.size();
+        (1..=size).flat_map(|r| (1..=size).map(move |c| (r, c))).collect()
+    }
+    // the common single-line format: all squares, row by row, '.' for blanks
+    // ('0' too on boards up to 9x9); the length gives the board size
+    // (16, 81, 256 or 625 squares); returns the row strings expected by new()
+    pub fn parse_line(line: &str) -> Result<Vec<String>, ParseError> {
+        let line = line.trim();
+        let length = line.chars().count();
+        let size = match BOX_SIZES.iter().find(|b| b.pow(4) == length) {
+            Some(box_size) => box_size * box_size,
+            None => return Err(ParseError::WrongLength { length }),
+        };
+        let mut initial_strings: Vec<String> = vec![String::new(); size];
+        for (index, c) in line.chars().enumerate() {
+            let (row, column) = (index / size, index % size);
+            match char_to_value(c, size) {
+                Some(value) => initial_strings[row].push(value_to_char(value, size)),
+                None if is_blank(c, size) => initial_strings[row].push(' '),
+                None => return Err(ParseError::InvalidCharacter { row: row+1, column: column+1, character: c }),
+            }
+        }
+        Ok(initial_strings)
+    }
+    // accepts either the single-line format or one line per row, each with up
+    // to a row's worth of squares ('.', ' ' or, up to 9x9, '0' for blanks;
+    // short rows padded with blanks); lines starting with '#' are comments
+    pub fn parse_text(text: &str) -> Result<Vec<String>, ParseError> {
+        let mut lines: Vec<&str> = text
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        while lines.last().is_some_and(|line| line.trim().is_empty()) {
+            lines.pop();
+        }
+        if lines.len() == 1 {
+            return SudokuPuzzle::parse_line(lines[0]);
+        }
+        let size = lines.len();
+        if box_size_for(size).is_none() {
+            return Err(ParseError::WrongRowCount { rows: size });
+        }
 
         let mut initial_strings: Vec<String> = vec![];
-        for i in 0..9 {
-            initial_strings.push(from_puzzle.initial_strings[i].clone());
+        for (index, line) in lines.iter().enumerate() {
+            let mut row = String::new();
+            for (column, c) in line.trim_end().chars().enumerate() {
+                match char_to_value(c, size) {
+                    Some(value) => row.push(value_to_char(value, size)),
+                    None if is_blank(c, size) || c == ' ' => row.push(' '),
+                    None => return Err(ParseError::InvalidCharacter { row: index+1, column: column+1, character: c }),
+                }
+            }
+            let length = row.chars().count();
+            if length > size {
+                return Err(ParseError::WrongRowLength { row: index+1, length, expected: size });
+            }
+            initial_strings.push(format!("{:width$}", row, width = size));
         }
-
-        from_puzzle.rows
-            .iter()
-            .for_each(|from_row| {
-                let mut row: Vec<Box<dyn Square>> = vec![];
-                (*from_row)
-                    .iter()
-                    .for_each(|sq| {
-                        // let _unused = sq.clone(); // this works
-                        // let _unused: Box<dyn Square> = (*sq).clone(); // so does this
-                        // row.push(Box::new(DerivedSquare::copy(sq.get_bitmap())));
-                        // row.push(sq.clone());
-                        // row.push(Box::new(*(sq.clone())));
-                        // row.push(Box::new(DerivedSquare::copy(sq.get_bitmap()))); // this works
-                        row.push((*sq).clone()); // so does this
-                    });
-                rows.push(row);
-            });
-
+        Ok(initial_strings)
+    }
+    // inverse of parse_line: solved squares in the board's alphabet, everything else as '.'
+    pub fn to_line(&self) -> String {
+        self.grid.to_line()
+    }
+    pub fn clone_from(from_puzzle: &SudokuPuzzle) -> Self {
+        SudokuPuzzle::from_grid(&from_puzzle.grid)
+    }
+    // a puzzle on a copy of grid, its squares clean; allocates nothing for
+    // boards up to 9x9, see Grid
+    pub fn from_grid(grid: &Grid) -> Self {
         SudokuPuzzle {
-            initial_strings,
-            rows,
+            grid: grid.clone(),
+            box_size: box_size_for(grid.size()).expect("grids are always a supported size"),
+            dirty: SquareSet::new(),
+            touched: Vec::new(),
+            is_touched: SquareSet::new(),
+        }
+    }
+    // the board for reading; changes go through get_square
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+    pub fn get_square(&mut self, row: usize, column: usize) -> Square<'_> {
+        let index = (row-1)*self.size() + (column-1);
+        if !self.is_touched.contains(index) {
+            self.is_touched.insert(index);
+            self.touched.push(index);
         }
+        Square::new(&mut self.grid, &mut self.dirty, index)
     }
-    pub fn get_square(&mut self, row: usize, column: usize) -> &mut Box<dyn Square> {
-        &mut self.rows[row-1][column-1]
+    // for the solver, which keeps track of what it changes itself: these
+    // leave the square off the touched list
+    pub fn is_dirty(&self, index: usize) -> bool {
+        self.dirty.contains(index)
+    }
+    pub fn clean(&mut self, index: usize) {
+        self.dirty.remove(index);
+    }
+    // see Square::restore
+    pub fn restore(&mut self, index: usize, bitmap: usize) {
+        self.grid.restore(index, bitmap);
+        self.dirty.remove(index);
+    }
+    // the squares get_square handed out since the last call, in the order
+    // they were first handed out
+    pub fn take_touched(&mut self) -> Vec<usize> {
+        for index in self.touched.iter() {
+            self.is_touched.remove(*index);
+        }
+        std::mem::take(&mut self.touched)
     }
     pub fn debug_print_puzzle(&self) {
         if config::DEBUG_PRINT_ENABLED {
@@ -63,19 +257,17 @@ impl SudokuPuzzle {
         }
     }
     pub fn print_puzzle(&self) {
-        println!("-----------");
-        self.rows
-            .iter()
+        let size = self.size();
+        let border = "-".repeat(size + 2);
+        println!("{}", border);
+        (0..size)
             .for_each(|row| {
-                //println!("{}", (*row)[0].count_remaining());
                 print!("|");
-                (*row)
-                    .iter()
-                    .for_each(|boxsquare| {
-                        //print!(".")
-                        match (**boxsquare).has_single_value() {
-                            true => print!("{}", (**boxsquare).get_single_value_nomut()),
-                            false => print!(" "),
+                (0..size)
+                    .for_each(|column| {
+                        match self.grid.get_single_value(row*size + column) {
+                            Some(value) => print!("{}", value_to_char(value, size)),
+                            None => print!(" "),
                         }
                     });
                 println!("|");
@@ -85,27 +277,42 @@ impl SudokuPuzzle {
                     //     print!("{}", '.');
                     // });
             });
-        println!("-----------");
+        println!("{}", border);
     }
-    fn calculate_rows_from_initial_strings(initial_strings: &Vec<String>) -> Vec<Vec<Box<dyn Square>>> {
-        let mut rows: Vec<Vec<Box<dyn Square>>> = vec![];
-
-        initial_strings
+    // the grid with every square's remaining candidates, columns padded to
+    // the widest square and boxes separated by '|' and '-'
+    pub fn candidates_text(&self) -> String {
+        let size = self.size();
+        let cells: Vec<Vec<String>> = (0..size)
+            .map(|row| (0..size).map(|column| {
+                squares::bitmap_values(self.grid.get_bitmap(row*size + column))
+                    .into_iter()
+                    .map(|value| value_to_char(value, size))
+                    .collect::<String>()
+            }).collect())
+            .collect();
+        let width = cells.iter().flat_map(|row| row.iter()).map(|cell| cell.len()).max().unwrap_or(1);
+        let lines: Vec<String> = cells
             .iter()
-            .for_each(|s| {
-                let mut row: Vec<Box<dyn Square>> = vec![];
-                (*s)
-                    .chars()
-                    .for_each(|c| {
-                        match c {
-                            ' ' => row.push(Box::new(DerivedSquare::new())),
-                            _ => row.push(Box::new(InitialSquare::new(c.to_string().parse::<usize>().unwrap()))),
-                        }
-                    });
-                rows.push(row);
-            });
-
-        rows
+            .map(|row| {
+                row.chunks(self.box_size)
+                    .map(|chunk| chunk.iter().map(|cell| format!("{:<width$}", cell, width = width)).collect::<Vec<String>>().join(" "))
+                    .collect::<Vec<String>>()
+                    .join(" | ")
+            })
+            .collect();
+        let separator = lines[0]
+            .chars()
+            .map(|c| match c {
+                '|' => '+',
+                _ => '-',
+            })
+            .collect::<String>();
+        lines
+            .chunks(self.box_size)
+            .map(|chunk| chunk.iter().map(|line| line.trim_end()).collect::<Vec<&str>>().join("\n"))
+            .collect::<Vec<String>>()
+            .join(&format!("\n{}\n", separator))
     }
 }
 
@@ -138,7 +345,97 @@ mod tests {
 
     #[test]
     fn create() {
-        let puzzle = SudokuPuzzle::new(empty_puzzle());
+        let _puzzle = SudokuPuzzle::new(empty_puzzle());
+    }
+
+    const LINE: &str = "7...12.89..8.57.....1.6....8......91......6..12......4....2.7.....64.2..43.17...6";
+
+    #[test]
+    fn line_round_trip() {
+        let mut puzzle = SudokuPuzzle::try_new(SudokuPuzzle::parse_line(LINE).unwrap()).unwrap();
+        assert_eq!(puzzle.initial_strings()[0], "7   12 89");
+        assert_eq!(puzzle.get_square(1,1).get_single_value(), 7);
+        assert_eq!(puzzle.get_square(1,2).count_remaining(), 9);
+        assert_eq!(puzzle.get_square(9,9).get_single_value(), 6);
+        assert_eq!(puzzle.to_line(), LINE);
+
+        let zeros = LINE.replace('.', "0");
+        assert_eq!(SudokuPuzzle::new(SudokuPuzzle::parse_line(&zeros).unwrap()).to_line(), LINE);
+    }
+
+    #[test]
+    fn line_errors() {
+        assert_eq!(SudokuPuzzle::parse_line("123").unwrap_err(), ParseError::WrongLength { length: 3 });
+        let bad = format!("{}x{}", &LINE[..10], &LINE[11..]);
+        assert_eq!(SudokuPuzzle::parse_line(&bad).unwrap_err(),
+                   ParseError::InvalidCharacter { row: 2, column: 2, character: 'x' });
+    }
+
+    #[test]
+    fn try_new_errors() {
+        let mut rows = empty_puzzle();
+        rows.pop();
+        assert_eq!(SudokuPuzzle::try_new(rows).err(), Some(ParseError::WrongRowCount { rows: 8 }));
+        let mut rows = empty_puzzle();
+        rows[3] = "12".to_string();
+        assert_eq!(SudokuPuzzle::try_new(rows).err(), Some(ParseError::WrongRowLength { row: 4, length: 2, expected: 9 }));
+        let mut rows = empty_puzzle();
+        rows[8] = "        0".to_string();
+        assert_eq!(SudokuPuzzle::try_new(rows).err(),
+                   Some(ParseError::InvalidCharacter { row: 9, column: 9, character: '0' }));
+    }
+
+    #[test]
+    fn parse_text() {
+        let grid = "# comment\n7...12.89\n..8.57\n..1.6\n8......91\n......6..\n12......4\n....2.7..\n...64.2..\n43.17...6\n\n";
+        let rows = SudokuPuzzle::parse_text(grid).unwrap();
+        assert_eq!(rows[1], "  8 57   ");
+        assert_eq!(SudokuPuzzle::try_new(rows).unwrap().to_line(), LINE);
+        assert_eq!(SudokuPuzzle::parse_text(&format!("{}\n", LINE)).unwrap()[0], "7   12 89");
+        assert_eq!(SudokuPuzzle::parse_text("1\n2\n").unwrap_err(), ParseError::WrongRowCount { rows: 2 });
+    }
+
+    #[test]
+    fn other_sizes() {
+        let rows = SudokuPuzzle::parse_line("1..4..1..1..4..1").unwrap();
+        assert_eq!(rows, vec!["1  4", "  1 ", " 1  ", "4  1"]);
+        let mut puzzle = SudokuPuzzle::new(rows);
+        assert_eq!((puzzle.size(), puzzle.box_size()), (4, 2));
+        assert_eq!(puzzle.get_square(1,2).count_remaining(), 4);
+        assert_eq!(puzzle.to_line(), "1..4..1..1..4..1");
+
+        // 16x16: '0' is the first value, letters may be lowercase
+        let line = format!("0f.a{}", ".".repeat(252));
+        let mut puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line(&line).unwrap());
+        assert_eq!((puzzle.size(), puzzle.box_size()), (16, 4));
+        assert_eq!(puzzle.get_square(1,1).get_single_value(), 1);
+        assert_eq!(puzzle.get_square(1,2).get_single_value(), 16);
+        assert_eq!(puzzle.get_square(1,3).count_remaining(), 16);
+        assert_eq!(puzzle.to_line(), format!("0F.A{}", ".".repeat(252)));
+        assert_eq!(SudokuPuzzle::parse_line(&format!("G{}", ".".repeat(255))).unwrap_err(),
+                   ParseError::InvalidCharacter { row: 1, column: 1, character: 'G' });
+
+        // 25x25 uses letters
+        let line = format!("AY{}", ".".repeat(623));
+        let mut puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line(&line).unwrap());
+        assert_eq!(puzzle.get_square(1,2).get_single_value(), 25);
+        assert_eq!(puzzle.to_line(), line);
+
+        // rows work too, and their number gives the size
+        let rows = SudokuPuzzle::parse_text("12
+..12
+
+3
+").unwrap();
+        assert_eq!(rows, vec!["12  ", "  12", "    ", "3   "]);
+        assert_eq!(SudokuPuzzle::parse_text("1
+2
+3
+4
+5
+").unwrap_err(), ParseError::WrongRowCount { rows: 5 });
+        assert_eq!(format!("{}", ParseError::WrongLength { length: 3 }),
+                   "expected 16, 81, 256 or 625 squares, found 3");
     }
 
     #[test]
@@ -171,5 +468,18 @@ mod tests {
         assert_eq!(another_clone.get_square(4,1).count_remaining(), 9);
         assert_eq!(yet_another.get_square(4,1).count_remaining(), 8);
     }
+
+    #[test]
+    fn candidates_text() {
+        let mut puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line("1...........4...").unwrap());
+        puzzle.get_square(1,2).eliminate(1);
+        puzzle.get_square(1,2).eliminate(3);
+        assert_eq!(puzzle.candidates_text(), "\
+1    24   | 1234 1234
+1234 1234 | 1234 1234
+----------+----------
+1234 1234 | 1234 1234
+4    1234 | 1234 1234");
+    }
     
 }
@@ -1,4 +1,5 @@
 #[cfg(test)]
+#[allow(dead_code)]
 mod tests {
 
     struct InnerString {
@@ -43,26 +44,25 @@ mod tests {
         impl Foo {
             // '&' comes after '.' according to rust operator precedence
             fn get_name(&self) -> &String { &self.name }
-            fn set_name(&mut self, name: String) -> () { self.name = name }
+            fn set_name(&mut self, name: String) { self.name = name }
             fn get_bar(&self) -> &Bar { &self.bar }
-            fn set_bar(&mut self, bar: Bar) -> () { self.bar = bar }
+            fn set_bar(&mut self, bar: Bar) { self.bar = bar }
         }
 
         let bar1 = Bar{name: String::from("")};
-        let foo1 = Foo {
+        let _foo1 = Foo {
             name: String::from(""),
             bar: bar1,
         };
 
         // so far so good
 
-        let foo2 = Foo {
+        let _foo2 = Foo {
             name: String::from(""),
             // cannot use bar1
             // bar: bar1,
             bar: Bar{name: String::from("")},
         };
-        
     }
 
     #[test]
@@ -75,7 +75,7 @@ mod tests {
         // In rust, the String type is mutable and the data is stored on the heap
 
         // this works because "let y = x" makes a copy of 5
-        let mut x = 5;
+        let x = 5;
         let y = x;
         assert!(x == 5);
         assert!(y == 5);
@@ -145,7 +145,7 @@ mod tests {
             // Generally: Any scalar type is Copy
             //            Anything which requires [heap] allocation is not Copy
             //            Anything which is a resource is not Copy
-            
+
             #[derive(Debug, Copy, Clone)]
             struct Foo;
 
@@ -167,7 +167,7 @@ mod tests {
 
             let x = String::from("");
 
-            fn do_stuff(s: String) {}
+            fn do_stuff(_s: String) {}
 
             do_stuff(x);
             // Error: value borrowed after move
@@ -175,7 +175,7 @@ mod tests {
 
             let x = String::from("");
 
-            fn do_stuff_ref(s: &String) {}
+            fn do_stuff_ref(_s: &String) {}
 
             do_stuff_ref(&x);
             assert!(x.is_empty()); // works because it was a reference only
@@ -190,7 +190,7 @@ mod tests {
 
             let x = Foo;
 
-            fn do_stuff_x(x: Foo) {}
+            fn do_stuff_x(_x: Foo) {}
 
             do_stuff_x(x);
             assert!(format!("{}", x).is_empty()); // works because x is Copy
@@ -210,10 +210,9 @@ mod tests {
 
             // now s1 is out of scope but s2 is
             assert!(s2.is_empty());
-
         }
 
-        
+
     }
 
     #[test]
@@ -255,7 +254,7 @@ mod tests {
 
         let mut s5a = String::from("");
         let mut s5b = String::from("");
-        let foo5 = InnerStringMutRef2{foo: &mut s5a, bar: &mut s5b};
+        let _foo5 = InnerStringMutRef2{foo: &mut s5a, bar: &mut s5b};
         {
             let foo5b = InnerStringMutRef2{foo: &mut s5a, bar: &mut s5b};
             // works
@@ -0,0 +1,93 @@
+// a constraint written outside the crate, as a user of the library would:
+// the two main diagonals may not repeat a digit either (X-sudoku)
+
+use sudoku_solver::guess_ordering::SimpleGuessOrderingByTuple;
+use sudoku_solver::Constraint;
+use sudoku_solver::ConstraintViolation;
+use sudoku_solver::Pipeline;
+use sudoku_solver::QueueItem;
+use sudoku_solver::Settings;
+use sudoku_solver::Solver;
+use sudoku_solver::SudokuPuzzle;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+struct NoDiagonalDuplicates {
+    calls: Arc<AtomicUsize>,
+}
+impl NoDiagonalDuplicates {
+    fn diagonals(size: usize, row: usize, column: usize) -> Vec<Vec<(usize, usize)>> {
+        let mut diagonals = vec![];
+        if row == column {
+            diagonals.push((1..=size).map(|i| (i, i)).collect());
+        }
+        if row + column == size + 1 {
+            diagonals.push((1..=size).map(|i| (i, size + 1 - i)).collect());
+        }
+        diagonals
+    }
+}
+impl Constraint for NoDiagonalDuplicates {
+    fn process(&mut self, queue_item: &QueueItem, puzzle: &mut SudokuPuzzle) -> Result<(), ConstraintViolation> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let (row, column) = (queue_item.row, queue_item.column);
+        if !puzzle.get_square(row, column).has_single_value() {
+            return Ok(());
+        }
+        let value = puzzle.get_square(row, column).get_single_value();
+        for diagonal in NoDiagonalDuplicates::diagonals(puzzle.size(), row, column) {
+            for (r, c) in diagonal.into_iter().filter(|square| *square != (row, column)) {
+                let mut square = puzzle.get_square(r, c);
+                square.eliminate(value);
+                if square.count_remaining() == 0 {
+                    return Err(ConstraintViolation::NoCandidates { row: r, column: c });
+                }
+            }
+        }
+        Ok(())
+    }
+    fn name(&self) -> String {
+        "NoDiagonalDuplicates".to_string()
+    }
+    fn elapsed_millis(&self) -> u128 {
+        0
+    }
+}
+
+fn diagonals_are_distinct(line: &str) -> bool {
+    let squares: Vec<char> = line.chars().collect();
+    let size = (squares.len() as f64).sqrt() as usize;
+    let main: Vec<char> = (0..size).map(|i| squares[i * size + i]).collect();
+    let anti: Vec<char> = (0..size).map(|i| squares[i * size + size - 1 - i]).collect();
+    [main, anti].iter().all(|diagonal| (0..size).all(|i| !diagonal[i + 1..].contains(&diagonal[i])))
+}
+
+fn count_4x4_solutions(pipeline: Pipeline) -> (usize, Vec<String>) {
+    let mut ordering = SimpleGuessOrderingByTuple::new((2,3,4,5,6,7,8,9));
+    ordering.with_randomization(false);
+    let puzzle = SudokuPuzzle::new(SudokuPuzzle::parse_line(&".".repeat(16)).unwrap());
+    let mut s = Solver::new(puzzle, Box::new(ordering));
+    let mut settings = Settings::new();
+    settings.pipeline = pipeline;
+    s.with_settings(settings);
+    let count = s.count_solutions(300).unwrap();
+    (count, s.get_solutions().iter().map(|solution| solution.to_line()).collect())
+}
+
+#[test]
+fn custom_constraint_runs_in_the_pipeline() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counted = calls.clone();
+    let pipeline = Pipeline::standard()
+        .with_constraint(move |_| Box::new(NoDiagonalDuplicates { calls: counted.clone() }));
+    let (count, solutions) = count_4x4_solutions(pipeline);
+
+    assert!(calls.load(Ordering::SeqCst) > 0);
+    // every 4x4 grid is one of 288, only some of them keep the diagonals apart
+    let (all, _) = count_4x4_solutions(Pipeline::standard());
+    assert_eq!(all, 288);
+    assert!(count > 0 && count < all);
+    assert_eq!(solutions.len(), count);
+    assert!(solutions.iter().all(|solution| diagonals_are_distinct(solution)));
+}